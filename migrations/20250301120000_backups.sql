@@ -0,0 +1,19 @@
+CREATE TABLE backups (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    server_id UUID REFERENCES servers(id) ON DELETE CASCADE NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    size BIGINT NOT NULL,
+    checksum TEXT NOT NULL,
+    trigger TEXT NOT NULL
+);
+
+CREATE INDEX backups_server_id ON backups (server_id, created_at);
+
+CREATE TABLE backup_policies (
+    server_id UUID PRIMARY KEY REFERENCES servers(id) ON DELETE CASCADE,
+    -- minutes between scheduled backups, NULL disables them
+    interval_minutes INT,
+    keep_last INT NOT NULL DEFAULT 5,
+    keep_daily INT NOT NULL DEFAULT 7,
+    keep_weekly INT NOT NULL DEFAULT 4
+);
@@ -0,0 +1,4 @@
+ALTER TABLE backups ADD COLUMN target TEXT NOT NULL DEFAULT 'local';
+
+-- NULL uses the globally configured target
+ALTER TABLE backup_policies ADD COLUMN target TEXT;
@@ -0,0 +1,20 @@
+CREATE TABLE schedules (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    server_id UUID REFERENCES servers(id) ON DELETE CASCADE NOT NULL,
+    name TEXT NOT NULL,
+    cron TEXT NOT NULL,
+    timezone TEXT NOT NULL DEFAULT 'UTC',
+    -- ordered list of actions, see ScheduleAction
+    actions JSONB NOT NULL,
+    only_when_players_online BOOLEAN NOT NULL DEFAULT FALSE,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    -- NULL while the schedule is disabled
+    next_run_at TIMESTAMPTZ,
+    last_run_at TIMESTAMPTZ,
+    last_result TEXT,
+    last_message TEXT,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX schedules_server_id ON schedules (server_id);
+CREATE INDEX schedules_next_run_at ON schedules (next_run_at) WHERE enabled;
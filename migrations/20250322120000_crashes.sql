@@ -0,0 +1,21 @@
+CREATE TABLE crashes (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    server_id UUID REFERENCES servers(id) ON DELETE CASCADE NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    exit_code BIGINT,
+    -- the file name in the server's crash-reports directory, if one was written
+    report_name TEXT,
+    outcome TEXT NOT NULL
+);
+
+CREATE INDEX crashes_server_id ON crashes (server_id, created_at);
+
+CREATE TABLE restart_policies (
+    server_id UUID PRIMARY KEY REFERENCES servers(id) ON DELETE CASCADE,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    -- more crashes than this within the window counts as a crash loop
+    max_restarts INT NOT NULL DEFAULT 5,
+    window_minutes INT NOT NULL DEFAULT 10,
+    backoff_seconds INT NOT NULL DEFAULT 5,
+    max_backoff_seconds INT NOT NULL DEFAULT 300
+);
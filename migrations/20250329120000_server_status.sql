@@ -0,0 +1,11 @@
+ALTER TABLE servers ADD COLUMN status TEXT NOT NULL DEFAULT 'stopped';
+-- whether the server was last started or stopped through waitress
+ALTER TABLE servers ADD COLUMN should_run BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- containers and volumes named like waitress servers that have no server row
+CREATE TABLE orphans (
+    kind TEXT NOT NULL,
+    name TEXT NOT NULL,
+    found_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (kind, name)
+);
@@ -0,0 +1,2 @@
+-- why a server is errored or needs review
+ALTER TABLE servers ADD COLUMN status_reason TEXT;
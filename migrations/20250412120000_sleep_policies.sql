@@ -0,0 +1,8 @@
+CREATE TABLE sleep_policies (
+    server_id UUID PRIMARY KEY REFERENCES servers(id) ON DELETE CASCADE,
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    -- how long the server has to be empty before it's put to sleep
+    idle_minutes INT NOT NULL DEFAULT 15,
+    -- shown in the server list while sleeping, NULL uses the default
+    motd TEXT
+);
@@ -0,0 +1,4 @@
+-- used by the proxy to route connections by the address players join with
+ALTER TABLE servers ADD COLUMN hostname TEXT UNIQUE;
+-- whether the proxy sends a PROXY protocol v2 header to the server
+ALTER TABLE servers ADD COLUMN proxy_protocol BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,10 @@
+CREATE TABLE startup_configs (
+    server_id UUID PRIMARY KEY REFERENCES servers(id) ON DELETE CASCADE,
+    -- the launch command, with {{MEMORY}}, {{JAR}} and {{JAVA_ARGS}} filled in
+    command TEXT NOT NULL,
+    memory_mb INT NOT NULL DEFAULT 1024,
+    jar TEXT NOT NULL DEFAULT 'server.jar',
+    java_args TEXT NOT NULL DEFAULT '',
+    -- extra environment variables for the container
+    env JSONB NOT NULL DEFAULT '{}'
+);
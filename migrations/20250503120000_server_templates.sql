@@ -0,0 +1,2 @@
+-- every server created so far is vanilla minecraft
+ALTER TABLE servers ADD COLUMN template TEXT NOT NULL DEFAULT 'minecraft';
@@ -0,0 +1,11 @@
+-- other users that can manage a server, with what they're allowed to do
+CREATE TABLE server_members (
+    server_id UUID NOT NULL REFERENCES servers(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- a list of permissions, like ["consoleRead", "backups"]
+    permissions JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (server_id, user_id)
+);
+
+CREATE INDEX server_members_user_id ON server_members (user_id);
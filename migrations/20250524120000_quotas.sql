@@ -0,0 +1,19 @@
+-- limits for users without their own, NULL means unlimited
+CREATE TABLE default_quota (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    max_servers INT,
+    max_memory_mb INT,
+    max_disk_mb BIGINT,
+    max_backups INT
+);
+
+INSERT INTO default_quota DEFAULT VALUES;
+
+-- replaces the default quota for a user
+CREATE TABLE user_quotas (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    max_servers INT,
+    max_memory_mb INT,
+    max_disk_mb BIGINT,
+    max_backups INT
+);
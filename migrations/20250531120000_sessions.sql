@@ -0,0 +1,17 @@
+-- a logged in device, kept alive by rotating refresh tokens
+CREATE TABLE sessions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- sha256 of the current refresh token
+    refresh_hash TEXT NOT NULL UNIQUE,
+    -- the token it replaced, using it again means it was stolen
+    previous_hash TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    refreshed_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMPTZ NOT NULL,
+    user_agent TEXT,
+    ip TEXT
+);
+
+CREATE INDEX sessions_user_id ON sessions (user_id);
+CREATE INDEX sessions_previous_hash ON sessions (previous_hash);
@@ -0,0 +1,20 @@
+CREATE TABLE two_factor (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    -- the TOTP secret, hex encoded
+    secret TEXT NOT NULL,
+    -- only once a code has been verified, until then it's being set up
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    -- the last time step a code was used for, so codes can't be replayed
+    last_step BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE recovery_codes (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- sha256 of the code
+    code_hash TEXT NOT NULL,
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX recovery_codes_user_id ON recovery_codes (user_id);
@@ -0,0 +1,22 @@
+-- users from an OIDC provider don't need a password
+ALTER TABLE users ALTER COLUMN password DROP NOT NULL;
+
+CREATE TABLE oidc_identities (
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (issuer, subject)
+);
+
+CREATE INDEX oidc_identities_user_id ON oidc_identities (user_id);
+
+-- logins waiting for the provider to send the user back
+CREATE TABLE oidc_logins (
+    state TEXT PRIMARY KEY,
+    code_verifier TEXT NOT NULL,
+    nonce TEXT NOT NULL,
+    -- set when a logged in user is linking their account
+    link_user UUID REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
@@ -0,0 +1,27 @@
+CREATE TABLE invites (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    -- sha256 of the code, which is only shown when it's created
+    code_hash TEXT UNIQUE NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    note TEXT,
+    -- what users who sign up with it start with, instead of the defaults
+    role TEXT,
+    quota JSONB,
+    -- NULL for unlimited
+    max_uses INT,
+    uses INT NOT NULL DEFAULT 0,
+    expires_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE TABLE invite_uses (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    invite_id UUID REFERENCES invites(id) ON DELETE CASCADE NOT NULL,
+    -- kept after the user is deleted, so uses still add up
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    username TEXT NOT NULL,
+    ip TEXT,
+    used_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX invite_uses_invite_id ON invite_uses (invite_id);
@@ -0,0 +1,4 @@
+-- pending logins only last 10 minutes, so they can just be dropped
+DELETE FROM oidc_logins;
+-- a hash of the secret kept by the browser that started the login
+ALTER TABLE oidc_logins ADD COLUMN browser_hash TEXT NOT NULL;
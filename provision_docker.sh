@@ -2,10 +2,12 @@ echo "waitress, by OPTIMI"
 echo "authored 2025 by maddie null pointer :))"
 echo "THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE."
 
-if [ ! -f server.jar ]; then
-  curl -o server.jar $JAR_URL
-  echo eula=true > eula.txt
+# the template's install script only runs once
+if [ -f install.sh ]; then
+  echo "Installing server"
+  sh install.sh || exit 1
+  rm install.sh
 fi
 
 echo "Starting server"
-exec java -Xmx1024M -Xms1024M -jar server.jar nogui
\ No newline at end of file
+exec sh -c "$STARTUP"
\ No newline at end of file
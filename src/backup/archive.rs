@@ -0,0 +1,140 @@
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+// hashes everything written through it, so the archive doesn't need to be
+// read back to compute its checksum
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    written: u64,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes a gzipped tarball of `source` to `dest`, returning its size in bytes
+/// and hex encoded sha256 checksum. This blocks, so run it on a blocking
+/// thread. Setting `cancel` stops the archive before the next file.
+pub fn create_archive(
+    source: &Path,
+    dest: &Path,
+    cancel: &AtomicBool,
+) -> io::Result<(u64, String)> {
+    let partial = dest.with_extension("partial");
+    let result = write_archive(source, &partial, cancel);
+    match result {
+        Ok(result) => {
+            fs::rename(&partial, dest)?;
+            Ok(result)
+        }
+        Err(e) => {
+            fs::remove_file(&partial).ok();
+            Err(e)
+        }
+    }
+}
+
+fn write_archive(source: &Path, dest: &Path, cancel: &AtomicBool) -> io::Result<(u64, String)> {
+    let file = File::create(dest)?;
+    let writer = HashingWriter {
+        inner: file,
+        hasher: Sha256::new(),
+        written: 0,
+    };
+    let mut builder = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+    builder.follow_symlinks(false);
+    append_dir(&mut builder, source, Path::new("."), cancel)?;
+    let mut writer = builder.into_inner()?.finish()?;
+    writer.flush()?;
+    writer.inner.sync_all()?;
+    Ok((writer.written, format!("{:x}", writer.hasher.finalize())))
+}
+
+fn append_dir<W: Write>(
+    builder: &mut tar::Builder<W>,
+    root: &Path,
+    path: &Path,
+    cancel: &AtomicBool,
+) -> io::Result<()> {
+    builder.append_dir(path, root.join(path))?;
+    for entry in fs::read_dir(root.join(path))? {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "archive cancelled",
+            ));
+        }
+        let entry = entry?;
+        let name = path.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            append_dir(builder, root, &name, cancel)?;
+        } else {
+            builder.append_path_with_name(entry.path(), name)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn checksum(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Reads through a whole archive to make sure it's a gzipped tarball.
+pub fn validate(path: &Path) -> io::Result<()> {
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(path)?));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        entry.path()?;
+        io::copy(&mut entry, &mut io::sink())?;
+    }
+    Ok(())
+}
+
+/// Replaces `dest` with the contents of an archive. The archive is unpacked
+/// next to `dest` first, so a bad archive leaves `dest` untouched.
+pub fn extract(archive: &Path, dest: &Path) -> io::Result<()> {
+    let staging = dest.with_extension("restoring");
+    let old = dest.with_extension("old");
+    for dir in [&staging, &old] {
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+    }
+
+    fs::create_dir_all(&staging)?;
+    let mut tar = tar::Archive::new(GzDecoder::new(File::open(archive)?));
+    // archives can be uploaded, so setuid bits and the like aren't kept
+    tar.set_preserve_permissions(false);
+    tar.set_mask(0o022);
+    if let Err(e) = tar.unpack(&staging) {
+        fs::remove_dir_all(&staging).ok();
+        return Err(e);
+    }
+
+    if dest.exists() {
+        fs::rename(dest, &old)?;
+    }
+    fs::rename(&staging, dest)?;
+    if old.exists() {
+        fs::remove_dir_all(&old)?;
+    }
+    Ok(())
+}
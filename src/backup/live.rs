@@ -0,0 +1,60 @@
+use std::{future::Future, time::Duration};
+use uuid::Uuid;
+
+use crate::{
+    console::{Console, ConsoleError},
+    db::backup::BackupError,
+    template::SaveCommands,
+};
+
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+const SAVE_TIMEOUT: Duration = Duration::from_secs(120);
+const ARCHIVE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+async fn send(console: &Console, command: &str) -> Result<(), ConsoleError> {
+    tokio::time::timeout(COMMAND_TIMEOUT, console.send(command))
+        .await
+        .map_err(|_| ConsoleError::Timeout)?
+}
+
+/// Runs `archive` while a running server has saving paused with its
+/// template's commands, so the world isn't written to halfway through the
+/// copy. Saving is always resumed afterwards, even if something went wrong.
+pub async fn with_saving_paused<F, T>(
+    server_id: Uuid,
+    save: &SaveCommands,
+    archive: F,
+) -> Result<T, BackupError>
+where
+    F: Future<Output = Result<T, BackupError>>,
+{
+    let console = Console::get(server_id).await?;
+    // subscribe before saving so the log line can't be missed
+    let mut rx = console.subscribe();
+
+    let flushed = async {
+        for command in &save.pause {
+            send(&console, command).await?;
+        }
+        match &save.saved {
+            Some(saved) => Console::wait_for(&mut rx, saved, SAVE_TIMEOUT).await,
+            None => Ok(()),
+        }
+    }
+    .await;
+
+    let result = match flushed {
+        Ok(()) => tokio::time::timeout(ARCHIVE_TIMEOUT, archive)
+            .await
+            .unwrap_or(Err(BackupError::Timeout)),
+        Err(e) => Err(e.into()),
+    };
+
+    for command in &save.resume {
+        if let Err(e) = send(&console, command).await {
+            log::error!("failed to resume saving for {}: {}", server_id, e);
+        }
+    }
+
+    result
+}
@@ -0,0 +1,30 @@
+pub mod archive;
+pub mod live;
+pub mod retention;
+pub mod scheduler;
+pub mod target;
+
+use lazy_static::lazy_static;
+use std::{collections::HashSet, sync::Mutex};
+use uuid::Uuid;
+
+lazy_static! {
+    static ref LOCKED: Mutex<HashSet<Uuid>> = Mutex::new(HashSet::new());
+}
+
+/// Makes sure only one backup operation runs per server at a time. The lock is
+/// released when this is dropped.
+pub struct BackupLock(Uuid);
+
+impl BackupLock {
+    pub fn acquire(server_id: Uuid) -> Option<Self> {
+        let mut locked = LOCKED.lock().unwrap();
+        locked.insert(server_id).then_some(Self(server_id))
+    }
+}
+
+impl Drop for BackupLock {
+    fn drop(&mut self) {
+        LOCKED.lock().unwrap().remove(&self.0);
+    }
+}
@@ -0,0 +1,46 @@
+use chrono::{DateTime, Datelike, Duration, Utc};
+use std::{cmp::Reverse, collections::HashSet};
+use uuid::Uuid;
+
+use crate::db::backup::BackupPolicy;
+
+/// Works out which backups fall outside of a retention policy. The newest
+/// `keep_last` backups are always kept, along with the newest backup of each
+/// of the last `keep_daily` days and `keep_weekly` weeks.
+pub fn backups_to_prune(
+    backups: &[(Uuid, DateTime<Utc>)],
+    policy: &BackupPolicy,
+    now: DateTime<Utc>,
+) -> Vec<Uuid> {
+    let mut backups = backups.to_vec();
+    backups.sort_by_key(|(_, created_at)| Reverse(*created_at));
+
+    let today = now.date_naive();
+    let this_week = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+
+    let mut keep = HashSet::new();
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+
+    for (i, (id, created_at)) in backups.iter().enumerate() {
+        if i < policy.keep_last as usize {
+            keep.insert(*id);
+        }
+
+        let day = created_at.date_naive();
+        if (today - day).num_days() < policy.keep_daily as i64 && days.insert(day) {
+            keep.insert(*id);
+        }
+
+        let week = day - Duration::days(day.weekday().num_days_from_monday() as i64);
+        if (this_week - week).num_weeks() < policy.keep_weekly as i64 && weeks.insert(week) {
+            keep.insert(*id);
+        }
+    }
+
+    backups
+        .into_iter()
+        .map(|(id, _)| id)
+        .filter(|id| !keep.contains(id))
+        .collect()
+}
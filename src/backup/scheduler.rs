@@ -0,0 +1,36 @@
+use actix_web::rt;
+use sqlx::PgPool;
+use std::time::Duration;
+
+use crate::db::{
+    backup::{Backup, BackupPolicy, BackupTrigger},
+    server::Server,
+};
+
+/// Periodically takes backups of servers that have a backup interval set.
+pub async fn run(pool: PgPool) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        let due = match BackupPolicy::due(&pool).await {
+            Ok(due) => due,
+            Err(e) => {
+                log::error!("failed to fetch due backups: {}", e);
+                continue;
+            }
+        };
+
+        for server_id in due {
+            let Some(server) = Server::from_id(server_id, &pool).await else {
+                continue;
+            };
+            let pool = pool.clone();
+            // archiving can take a while, so don't hold up other servers
+            rt::spawn(async move {
+                if let Err(e) = Backup::create(&server, BackupTrigger::Scheduled, &pool).await {
+                    log::error!("scheduled backup of {} failed: {}", server.id, e);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,76 @@
+pub mod s3;
+
+use serde::{Deserialize, Serialize};
+use std::{path::Path, str::FromStr};
+use tokio::fs;
+
+use crate::config::CONFIG;
+use s3::{S3Error, S3Target};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum BackupTargetKind {
+    Local,
+    S3,
+}
+
+impl FromStr for BackupTargetKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(Self::Local),
+            "s3" => Ok(Self::S3),
+            _ => anyhow::bail!("unknown backup target {:?}", s),
+        }
+    }
+}
+
+/// Where backup archives are kept. Archives are always written to the local
+/// backup directory first, and moved to the target once they're complete.
+pub enum BackupTarget {
+    Local,
+    S3(S3Target),
+}
+
+impl BackupTarget {
+    /// Returns `None` if the target isn't configured.
+    pub fn new(kind: BackupTargetKind) -> Option<Self> {
+        match kind {
+            BackupTargetKind::Local => Some(Self::Local),
+            BackupTargetKind::S3 => CONFIG.s3.as_ref().map(|c| Self::S3(S3Target::new(c))),
+        }
+    }
+
+    /// Moves a finished archive at `path` into the target under `name`.
+    pub async fn store(&self, path: &Path, name: &str) -> Result<(), S3Error> {
+        match self {
+            Self::Local => Ok(()),
+            Self::S3(s3) => {
+                s3.upload(&s3.key(name), path).await?;
+                fs::remove_file(path).await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Makes the archive available at `path`, downloading it if it isn't kept
+    /// locally.
+    pub async fn fetch(&self, name: &str, path: &Path) -> Result<(), S3Error> {
+        match self {
+            Self::Local => Ok(()),
+            Self::S3(s3) => s3.download(&s3.key(name), path).await,
+        }
+    }
+
+    pub async fn delete(&self, name: &str, path: &Path) -> Result<(), S3Error> {
+        match self {
+            Self::Local => match fs::remove_file(path).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            },
+            Self::S3(s3) => s3.delete(&s3.key(name)).await,
+        }
+    }
+}
@@ -0,0 +1,382 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::{Method, Response, StatusCode, Url};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, path::Path};
+use thiserror::Error;
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+};
+
+use crate::config::S3Config;
+
+// parts have to be at least 5MiB, apart from the last one
+const PART_SIZE: usize = 16 * 1024 * 1024;
+const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+lazy_static! {
+    static ref CONTENTS: Regex = Regex::new(r"(?s)<Contents>(.*?)</Contents>").unwrap();
+    static ref KEY: Regex = Regex::new(r"<Key>(.*?)</Key>").unwrap();
+    static ref LAST_MODIFIED: Regex = Regex::new(r"<LastModified>(.*?)</LastModified>").unwrap();
+    static ref UPLOAD_ID: Regex = Regex::new(r"<UploadId>(.*?)</UploadId>").unwrap();
+    static ref CONTINUATION: Regex =
+        Regex::new(r"<NextContinuationToken>(.*?)</NextContinuationToken>").unwrap();
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct S3Object {
+    pub key: String,
+    /// `None` if the provider didn't say, or said something unreadable.
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Error)]
+pub enum S3Error {
+    #[error("Request failed: {0}")]
+    RequestError(#[from] reqwest::Error),
+    #[error("S3 returned {0}: {1}")]
+    StatusError(StatusCode, String),
+    #[error("Filesystem error: {0}")]
+    FilesystemError(#[from] std::io::Error),
+    #[error("Invalid S3 configuration: {0}")]
+    ConfigError(String),
+    #[error("Unexpected S3 response")]
+    InvalidResponse,
+}
+
+/// A minimal S3 client, enough to store backups on any S3-compatible service
+/// (AWS, MinIO, R2, ...).
+pub struct S3Target {
+    config: &'static S3Config,
+    client: reqwest::Client,
+}
+
+impl S3Target {
+    pub fn new(config: &'static S3Config) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn key(&self, name: &str) -> String {
+        format!("{}{}", self.config.prefix, name)
+    }
+
+    fn url(&self, key: &str, query: &[(&str, &str)]) -> Result<Url, S3Error> {
+        let endpoint = self.config.endpoint.trim_end_matches('/');
+        let url = if self.config.path_style {
+            format!(
+                "{}/{}/{}",
+                endpoint,
+                self.config.bucket,
+                uri_encode(key, false)
+            )
+        } else {
+            let (scheme, host) = endpoint
+                .split_once("://")
+                .ok_or_else(|| S3Error::ConfigError("endpoint has no scheme".to_string()))?;
+            format!(
+                "{}://{}.{}/{}",
+                scheme,
+                self.config.bucket,
+                host,
+                uri_encode(key, false)
+            )
+        };
+        let mut url = Url::parse(&url).map_err(|e| S3Error::ConfigError(e.to_string()))?;
+        if !query.is_empty() {
+            url.set_query(Some(&canonical_query(query)));
+        }
+        Ok(url)
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<Response, S3Error> {
+        let url = self.url(key, query)?;
+        let payload_hash = if body.is_empty() {
+            EMPTY_SHA256.to_string()
+        } else {
+            format!("{:x}", Sha256::digest(&body))
+        };
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+        let mut headers = BTreeMap::new();
+        headers.insert("host".to_string(), host(&url));
+        headers.insert("x-amz-content-sha256".to_string(), payload_hash.clone());
+        headers.insert("x-amz-date".to_string(), amz_date.clone());
+        let authorization = sign(
+            self.config,
+            method.as_str(),
+            &url,
+            &headers,
+            &payload_hash,
+            &amz_date,
+        );
+
+        let response = self
+            .client
+            .request(method, url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", amz_date)
+            .header("authorization", authorization)
+            .body(body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(S3Error::StatusError(status, body));
+        }
+        Ok(response)
+    }
+
+    /// Uploads a file, splitting it into a multipart upload if it's large.
+    pub async fn upload(&self, key: &str, path: &Path) -> Result<(), S3Error> {
+        let mut file = File::open(path).await?;
+        let size = file.metadata().await?.len() as usize;
+
+        if size <= PART_SIZE {
+            let mut body = Vec::with_capacity(size);
+            file.read_to_end(&mut body).await?;
+            self.request(Method::PUT, key, &[], body).await?;
+            return Ok(());
+        }
+
+        let response = self
+            .request(Method::POST, key, &[("uploads", "")], Vec::new())
+            .await?
+            .text()
+            .await?;
+        let upload_id = UPLOAD_ID
+            .captures(&response)
+            .map(|c| c[1].to_string())
+            .ok_or(S3Error::InvalidResponse)?;
+
+        match self.upload_parts(key, &upload_id, &mut file).await {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                // don't leave the parts lying around, they cost money
+                self.request(Method::DELETE, key, &[("uploadId", &upload_id)], Vec::new())
+                    .await
+                    .ok();
+                Err(e)
+            }
+        }
+    }
+
+    async fn upload_parts(
+        &self,
+        key: &str,
+        upload_id: &str,
+        file: &mut File,
+    ) -> Result<(), S3Error> {
+        let mut parts = Vec::new();
+        loop {
+            let mut part = Vec::with_capacity(PART_SIZE);
+            (&mut *file)
+                .take(PART_SIZE as u64)
+                .read_to_end(&mut part)
+                .await?;
+            if part.is_empty() {
+                break;
+            }
+
+            let number = (parts.len() + 1).to_string();
+            let response = self
+                .request(
+                    Method::PUT,
+                    key,
+                    &[("partNumber", &number), ("uploadId", upload_id)],
+                    part,
+                )
+                .await?;
+            let etag = response
+                .headers()
+                .get("etag")
+                .and_then(|e| e.to_str().ok())
+                .ok_or(S3Error::InvalidResponse)?
+                .to_string();
+            parts.push((number, etag));
+        }
+
+        let mut body = String::from("<CompleteMultipartUpload>");
+        for (number, etag) in parts {
+            body.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                number, etag
+            ));
+        }
+        body.push_str("</CompleteMultipartUpload>");
+
+        let response = self
+            .request(
+                Method::POST,
+                key,
+                &[("uploadId", upload_id)],
+                body.into_bytes(),
+            )
+            .await?
+            .text()
+            .await?;
+        // completing can fail with a 200 status, the error is in the body
+        if response.contains("<Error>") {
+            return Err(S3Error::StatusError(StatusCode::OK, response));
+        }
+        Ok(())
+    }
+
+    /// Starts downloading an object, the body can be streamed from the
+    /// response.
+    pub async fn get(&self, key: &str) -> Result<Response, S3Error> {
+        self.request(Method::GET, key, &[], Vec::new()).await
+    }
+
+    pub async fn download(&self, key: &str, dest: &Path) -> Result<(), S3Error> {
+        let mut response = self.get(key).await?;
+        let mut file = File::create(dest).await?;
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await?;
+        }
+        file.sync_all().await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<(), S3Error> {
+        self.request(Method::DELETE, key, &[], Vec::new()).await?;
+        Ok(())
+    }
+
+    /// Lists every object starting with `prefix`.
+    pub async fn list(&self, prefix: &str) -> Result<Vec<S3Object>, S3Error> {
+        let mut objects = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+            if let Some(token) = &token {
+                query.push(("continuation-token", token));
+            }
+            let response = self
+                .request(Method::GET, "", &query, Vec::new())
+                .await?
+                .text()
+                .await?;
+
+            objects.extend(CONTENTS.captures_iter(&response).filter_map(|contents| {
+                let key = KEY.captures(&contents[1])?[1].to_string();
+                let last_modified = LAST_MODIFIED
+                    .captures(&contents[1])
+                    .and_then(|c| DateTime::parse_from_rfc3339(&c[1]).ok())
+                    .map(|t| t.with_timezone(&Utc));
+                Some(S3Object { key, last_modified })
+            }));
+
+            match CONTINUATION.captures(&response) {
+                Some(next) => token = Some(next[1].to_string()),
+                None => break,
+            }
+        }
+        Ok(objects)
+    }
+}
+
+fn host(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    }
+}
+
+fn uri_encode(s: &str, encode_slash: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            b'/' if !encode_slash => out.push('/'),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn canonical_query(query: &[(&str, &str)]) -> String {
+    let mut query = query
+        .iter()
+        .map(|(k, v)| (uri_encode(k, true), uri_encode(v, true)))
+        .collect::<Vec<_>>();
+    query.sort();
+    query
+        .into_iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Builds an AWS signature v4 `Authorization` header. `headers` must contain
+/// every header that should be signed, with lowercase names.
+pub fn sign(
+    config: &S3Config,
+    method: &str,
+    url: &Url,
+    headers: &BTreeMap<String, String>,
+    payload_hash: &str,
+    amz_date: &str,
+) -> String {
+    let date = &amz_date[..8];
+    let scope = format!("{}/{}/s3/aws4_request", date, config.region);
+
+    let canonical_headers = headers
+        .iter()
+        .map(|(k, v)| format!("{}:{}\n", k, v.trim()))
+        .collect::<String>();
+    let signed_headers = headers.keys().cloned().collect::<Vec<_>>().join(";");
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        url.path(),
+        url.query().unwrap_or_default(),
+        canonical_headers,
+        signed_headers,
+        payload_hash
+    );
+
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+        amz_date,
+        scope,
+        Sha256::digest(canonical_request.as_bytes())
+    );
+
+    let key = hmac(format!("AWS4{}", config.secret_key).as_bytes(), date);
+    let key = hmac(&key, &config.region);
+    let key = hmac(&key, "s3");
+    let key = hmac(&key, "aws4_request");
+    let signature = hmac(&key, &string_to_sign)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        config.access_key, scope, signed_headers, signature
+    )
+}
@@ -1,5 +1,14 @@
 use lazy_static::lazy_static;
 
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use crate::{
+    backup::target::BackupTargetKind, shutdown::ShutdownPolicy, web::rate_limit::RateLimit,
+};
+
 lazy_static! {
     #[derive(Debug)]
     pub static ref CONFIG: Config = Config::new().unwrap();
@@ -7,8 +16,117 @@ lazy_static! {
 
 pub struct Config {
     pub signups_enabled: bool,
+    /// The username and password of the admin created when there are no
+    /// users yet. Without it, the first user to sign up becomes the admin.
+    pub first_admin: Option<(String, String)>,
     pub database_url: String,
     pub jwt_secret: Vec<u8>,
+    pub backup_dir: String,
+    pub crash_dir: String,
+    /// Extra server templates are loaded from `.toml` and `.json` files here.
+    pub template_dir: String,
+    pub backup_target: BackupTargetKind,
+    pub s3: Option<S3Config>,
+    /// The largest backup that can be uploaded, in bytes.
+    pub max_backup_upload: u64,
+    pub shutdown_policy: ShutdownPolicy,
+    /// How long to wait for servers to stop when shutting down.
+    pub shutdown_timeout: Duration,
+    /// Where the hostname proxy listens, off unless `PROXY_ADDRESS` is set.
+    /// `0.0.0.0:25565` is recommended, so players can leave out the port.
+    pub proxy_address: Option<SocketAddr>,
+    /// Users who aren't admins can only give their servers hostnames under
+    /// this domain. Without it, only admins can set hostnames.
+    pub proxy_domain: Option<String>,
+    /// Single sign-on with an OpenID Connect provider, `None` disables it.
+    pub oidc: Option<OidcConfig>,
+    /// Proxies whose `X-Forwarded-For` header is believed.
+    pub trusted_proxies: Vec<IpAddr>,
+    /// Requests per IP to the login and signup routes.
+    pub auth_rate_limit: RateLimit,
+    /// Failed logins before a username is locked out.
+    pub login_max_failures: u32,
+    /// Failed logins before an IP is locked out.
+    pub login_ip_max_failures: u32,
+    /// The first lockout, which doubles with each failure after it.
+    pub login_lockout: Duration,
+    pub login_lockout_max: Duration,
+    /// Console commands per user over websockets.
+    pub ws_command_rate_limit: RateLimit,
+}
+
+pub struct S3Config {
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    pub path_style: bool,
+    pub prefix: String,
+}
+
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    /// Public clients can leave this out, PKCE is used either way.
+    pub client_secret: Option<String>,
+    /// Where the provider sends the user back to, the frontend's callback
+    /// page which passes the code on to `/auth/oidc/callback`.
+    pub redirect_url: String,
+    pub scopes: String,
+    /// The ID token claim new users get their username from.
+    pub username_claim: String,
+    pub groups_claim: String,
+    /// Members of this group are made admins when they log in, and everyone
+    /// else loses admin, so the provider decides who's an admin.
+    pub admin_group: Option<String>,
+    /// Creates accounts for unknown users, otherwise they have to link an
+    /// existing account first.
+    pub auto_provision: bool,
+}
+
+impl OidcConfig {
+    fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(issuer) = std::env::var("OIDC_ISSUER") else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            issuer,
+            client_id: std::env::var("OIDC_CLIENT_ID")?,
+            client_secret: std::env::var("OIDC_CLIENT_SECRET").ok(),
+            redirect_url: std::env::var("OIDC_REDIRECT_URL")?,
+            scopes: std::env::var("OIDC_SCOPES")
+                .unwrap_or_else(|_| "openid profile email".to_string()),
+            username_claim: std::env::var("OIDC_USERNAME_CLAIM")
+                .unwrap_or_else(|_| "preferred_username".to_string()),
+            groups_claim: std::env::var("OIDC_GROUPS_CLAIM")
+                .unwrap_or_else(|_| "groups".to_string()),
+            admin_group: std::env::var("OIDC_ADMIN_GROUP").ok(),
+            auto_provision: std::env::var("OIDC_AUTO_PROVISION")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
+        }))
+    }
+}
+
+impl S3Config {
+    fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(bucket) = std::env::var("S3_BUCKET") else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            endpoint: std::env::var("S3_ENDPOINT")
+                .unwrap_or_else(|_| "https://s3.amazonaws.com".to_string()),
+            bucket,
+            region: std::env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            access_key: std::env::var("S3_ACCESS_KEY")?,
+            secret_key: std::env::var("S3_SECRET_KEY")?,
+            path_style: std::env::var("S3_PATH_STYLE")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()?,
+            prefix: std::env::var("S3_PREFIX").unwrap_or_default(),
+        }))
+    }
 }
 
 impl Config {
@@ -16,12 +134,102 @@ impl Config {
         let signups_enabled = std::env::var("SIGNUPS_ENABLED")
             .unwrap_or_else(|_| "true".to_string())
             .parse()?;
+        let first_admin = match (
+            std::env::var("ADMIN_USERNAME"),
+            std::env::var("ADMIN_PASSWORD"),
+        ) {
+            (Ok(username), Ok(password)) => Some((username, password)),
+            (Err(_), Err(_)) => None,
+            _ => anyhow::bail!("ADMIN_USERNAME and ADMIN_PASSWORD have to be set together"),
+        };
         let database_url = std::env::var("DATABASE_URL")?;
         let jwt_secret = std::env::var("JWT_SECRET")?.into_bytes();
+        let backup_dir = std::env::var("BACKUP_DIR").unwrap_or_else(|_| "backups".to_string());
+        let crash_dir = std::env::var("CRASH_DIR").unwrap_or_else(|_| "crashes".to_string());
+        let template_dir =
+            std::env::var("TEMPLATE_DIR").unwrap_or_else(|_| "templates".to_string());
+        let backup_target = std::env::var("BACKUP_TARGET")
+            .unwrap_or_else(|_| "local".to_string())
+            .parse()?;
+        let s3 = S3Config::from_env()?;
+        let max_backup_upload = std::env::var("MAX_BACKUP_UPLOAD_MB")
+            .unwrap_or_else(|_| "10240".to_string())
+            .parse::<u64>()?
+            * 1024
+            * 1024;
+        let shutdown_policy = std::env::var("SHUTDOWN_POLICY")
+            .unwrap_or_else(|_| "leave".to_string())
+            .parse()?;
+        let shutdown_timeout = Duration::from_secs(
+            std::env::var("SHUTDOWN_TIMEOUT")
+                .unwrap_or_else(|_| "90".to_string())
+                .parse()?,
+        );
+        if backup_target == BackupTargetKind::S3 && s3.is_none() {
+            anyhow::bail!("BACKUP_TARGET is s3, but S3_BUCKET is not set");
+        }
+        // off by default, 25565 might already be taken by a server
+        let proxy_address = match std::env::var("PROXY_ADDRESS") {
+            Ok(address) if !address.is_empty() && address != "off" => Some(address.parse()?),
+            _ => None,
+        };
+        let proxy_domain = std::env::var("PROXY_DOMAIN")
+            .ok()
+            .map(|domain| domain.trim().trim_matches('.').to_lowercase())
+            .filter(|domain| !domain.is_empty());
+        let oidc = OidcConfig::from_env()?;
+        let trusted_proxies = std::env::var("TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|proxy| !proxy.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        let auth_rate_limit = std::env::var("AUTH_RATE_LIMIT")
+            .unwrap_or_else(|_| "20/60".to_string())
+            .parse()?;
+        let login_max_failures = std::env::var("LOGIN_MAX_FAILURES")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse()?;
+        let login_ip_max_failures = std::env::var("LOGIN_IP_MAX_FAILURES")
+            .unwrap_or_else(|_| "20".to_string())
+            .parse()?;
+        let login_lockout = Duration::from_secs(
+            std::env::var("LOGIN_LOCKOUT")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()?,
+        );
+        let login_lockout_max = Duration::from_secs(
+            std::env::var("LOGIN_LOCKOUT_MAX")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()?,
+        );
+        let ws_command_rate_limit = std::env::var("WS_COMMAND_RATE_LIMIT")
+            .unwrap_or_else(|_| "10/5".to_string())
+            .parse()?;
         Ok(Self {
             signups_enabled,
+            first_admin,
             database_url,
             jwt_secret,
+            backup_dir,
+            crash_dir,
+            template_dir,
+            backup_target,
+            s3,
+            max_backup_upload,
+            shutdown_policy,
+            shutdown_timeout,
+            proxy_address,
+            proxy_domain,
+            oidc,
+            trusted_proxies,
+            auth_rate_limit,
+            login_max_failures,
+            login_ip_max_failures,
+            login_lockout,
+            login_lockout_max,
+            ws_command_rate_limit,
         })
     }
 }
@@ -0,0 +1,158 @@
+use actix_web::rt;
+use bollard::{container::AttachContainerOptions, Docker};
+use futures::StreamExt as _;
+use lazy_static::lazy_static;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use thiserror::Error;
+use tokio::{
+    io::AsyncWriteExt as _,
+    sync::{broadcast, mpsc, Mutex},
+};
+use uuid::Uuid;
+
+lazy_static! {
+    static ref CONSOLES: Mutex<HashMap<Uuid, Arc<Console>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Error)]
+pub enum ConsoleError {
+    #[error("Docker error: {0}")]
+    DockerError(#[from] bollard::errors::Error),
+    #[error("The server's console is closed")]
+    Closed,
+    #[error("Timed out waiting for the server")]
+    Timeout,
+}
+
+/// A single attachment to a server container's stdio, shared between everything
+/// that needs the console (websockets, backups, ...).
+pub struct Console {
+    stdin: mpsc::Sender<String>,
+    // only the attach task holds a sender, so receivers see the channel close
+    // when the container stops
+    stdout: broadcast::Receiver<String>,
+}
+
+impl Console {
+    /// Returns the console hub for a server, attaching to its container if
+    /// nothing is attached yet.
+    pub async fn get(server_id: Uuid) -> Result<Arc<Self>, ConsoleError> {
+        let mut consoles = CONSOLES.lock().await;
+        if let Some(console) = consoles.get(&server_id) {
+            // the attach stream ends when the container stops
+            if !console.stdin.is_closed() {
+                return Ok(Arc::clone(console));
+            }
+        }
+
+        let console = Arc::new(Self::attach(server_id).await?);
+        consoles.insert(server_id, Arc::clone(&console));
+        Ok(console)
+    }
+
+    async fn attach(server_id: Uuid) -> Result<Self, ConsoleError> {
+        let docker = Docker::connect_with_local_defaults()?;
+
+        // stdout should have many readers and one writer
+        // stdin should have one reader and many writers
+        // so we need to create a stream for each
+        // unfortunately there's no spmc in tokio, but
+        // we can use broadcast for stdout and mpsc for stdin
+        let (stdout_tx, stdout_rx) = broadcast::channel(512);
+        let (stdin_tx, mut stdin_rx) = mpsc::channel::<String>(512);
+
+        let options = Some(AttachContainerOptions::<String> {
+            stream: Some(true),
+            stdin: Some(true),
+            stdout: Some(true),
+            stderr: Some(true),
+            ..Default::default()
+        });
+
+        let mut stream = docker
+            .attach_container(&format!("waitress-{}", server_id), options)
+            .await?;
+
+        rt::spawn(async move {
+            loop {
+                tokio::select! {
+                    maybe_event = stream.output.next() => {
+                        match maybe_event {
+                            Some(Ok(event)) => {
+                                let bytes = event.into_bytes();
+                                let output = String::from_utf8_lossy(&bytes).to_string();
+                                stdout_tx.send(output).ok();
+                            }
+
+                            Some(Err(e)) => {
+                                log::error!("console error for {}: {}", server_id, e);
+                                break;
+                            }
+
+                            None => break,
+                        }
+                    }
+
+                    maybe_command = stdin_rx.recv() => {
+                        let Some(command) = maybe_command else {
+                            break;
+                        };
+                        stream.input.write_all(command.as_bytes()).await.ok();
+                        stream.input.flush().await.ok();
+                    }
+                }
+            }
+            log::info!("console for {} detached", server_id);
+        });
+
+        Ok(Self {
+            stdin: stdin_tx,
+            stdout: stdout_rx,
+        })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.stdout.resubscribe()
+    }
+
+    pub async fn send(&self, command: impl Into<String>) -> Result<(), ConsoleError> {
+        let command = command.into();
+        log::trace!("sending command: {}", command);
+        self.stdin
+            .send(format!("{}\n", command))
+            .await
+            .map_err(|_| ConsoleError::Closed)
+    }
+
+    /// Waits until the server logs a line containing `needle`.
+    pub async fn wait_for(
+        rx: &mut broadcast::Receiver<String>,
+        needle: &str,
+        timeout: Duration,
+    ) -> Result<(), ConsoleError> {
+        let wait = async {
+            // output is chunked arbitrarily, so a line can be split across
+            // messages
+            let mut buffer = String::new();
+            loop {
+                match rx.recv().await {
+                    Ok(output) => {
+                        buffer.push_str(&output);
+                        if buffer.contains(needle) {
+                            return Ok(());
+                        }
+                        if let Some(i) = buffer.rfind('\n') {
+                            buffer.drain(..=i);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return Err(ConsoleError::Closed),
+                }
+            }
+        };
+
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| ConsoleError::Timeout)?
+    }
+}
@@ -0,0 +1,297 @@
+use argon2::{
+    password_hash::{
+        rand_core::{OsRng, RngCore},
+        SaltString,
+    },
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{types::Json, FromRow, PgPool};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::{
+    member::{Permission, ServerAccess},
+    user::{AuthDecodeError, User},
+};
+use crate::response_codes;
+
+/// Keys look like `waitress_<id>_<secret>`, the id is used to find the key
+/// since the secret is only stored hashed.
+const KEY_PREFIX: &str = "waitress_";
+const MAX_KEYS: i64 = 25;
+/// How long a verified key is trusted without running argon2 again.
+const VERIFIED_FOR: Duration = Duration::from_secs(5 * 60);
+
+lazy_static! {
+    /// Keys that passed argon2 recently, with the hash they were checked
+    /// against and a digest of the secret that passed. The row is still
+    /// fetched every time, so deleted and expired keys stop working at once.
+    static ref VERIFIED: Mutex<HashMap<Uuid, Verified>> = Mutex::new(HashMap::new());
+}
+
+struct Verified {
+    key_hash: String,
+    secret: [u8; 32],
+    at: Instant,
+}
+
+#[derive(FromRow, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    /// The servers the key works on, `None` for every server the user can
+    /// access.
+    pub servers: Option<Json<Vec<Uuid>>>,
+    pub permissions: Json<Vec<Permission>>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyBody {
+    pub name: String,
+    pub servers: Option<Vec<Uuid>>,
+    pub permissions: Vec<Permission>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Error)]
+pub enum ApiKeyError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("Failed to hash the key: {0}")]
+    HashError(argon2::password_hash::Error),
+    #[error("Threading error")]
+    ThreadError,
+    #[error("API key not found")]
+    KeyNotFound,
+    #[error("Invalid API key: {0}")]
+    InvalidKey(&'static str),
+    #[error("You can have at most 25 API keys")]
+    TooManyKeys,
+}
+
+response_codes!(ApiKeyError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    HashError(INTERNAL_SERVER_ERROR),
+    ThreadError(INTERNAL_SERVER_ERROR),
+    KeyNotFound(NOT_FOUND),
+    InvalidKey(BAD_REQUEST),
+    TooManyKeys(CONFLICT),
+});
+
+impl ApiKey {
+    pub fn is_api_key(token: &str) -> bool {
+        token.starts_with(KEY_PREFIX)
+    }
+
+    /// Creates a key, returning it along with the key itself, which can't be
+    /// seen again.
+    pub async fn create(
+        user_id: Uuid,
+        mut body: ApiKeyBody,
+        pool: &PgPool,
+    ) -> Result<(Self, String), ApiKeyError> {
+        if body.name.trim().is_empty() || body.name.chars().count() > 64 {
+            return Err(ApiKeyError::InvalidKey(
+                "the name must be between 1 and 64 characters",
+            ));
+        }
+        if body.expires_at.is_some_and(|expires| expires <= Utc::now()) {
+            return Err(ApiKeyError::InvalidKey(
+                "the expiry has to be in the future",
+            ));
+        }
+        let count =
+            sqlx::query_scalar!("SELECT COUNT(*) FROM api_keys WHERE user_id = $1", user_id)
+                .fetch_one(pool)
+                .await?;
+        if count.unwrap_or(0) >= MAX_KEYS {
+            return Err(ApiKeyError::TooManyKeys);
+        }
+        body.permissions.sort();
+        body.permissions.dedup();
+
+        let mut bytes = [0u8; 24];
+        OsRng.fill_bytes(&mut bytes);
+        let secret = bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        let hash = {
+            let secret = secret.clone();
+            tokio::task::spawn_blocking(move || {
+                let salt = SaltString::generate(&mut OsRng);
+                Argon2::default()
+                    .hash_password(secret.as_bytes(), &salt)
+                    .map(|hash| hash.to_string())
+                    .map_err(ApiKeyError::HashError)
+            })
+            .await
+            .map_err(|_| ApiKeyError::ThreadError)??
+        };
+
+        let key = sqlx::query_as!(
+            ApiKey,
+            r#"INSERT INTO api_keys (user_id, name, key_hash, servers, permissions, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, name, servers AS "servers: _", permissions AS "permissions: _",
+                created_at, expires_at, last_used_at"#,
+            user_id,
+            body.name.trim(),
+            hash,
+            body.servers.map(Json) as _,
+            Json(&body.permissions) as _,
+            body.expires_at
+        )
+        .fetch_one(pool)
+        .await?;
+        let token = format!("{}{}_{}", KEY_PREFIX, key.id.simple(), secret);
+        Ok((key, token))
+    }
+
+    /// Checks a key from the `Authorization` header, and marks it as used.
+    pub async fn authenticate(token: &str, pool: &PgPool) -> Result<(Self, User), AuthDecodeError> {
+        let (id, secret) = token
+            .strip_prefix(KEY_PREFIX)
+            .and_then(|rest| rest.split_once('_'))
+            .ok_or(AuthDecodeError::InvalidApiKey)?;
+        let id = Uuid::try_parse(id).map_err(|_| AuthDecodeError::InvalidApiKey)?;
+
+        let row = sqlx::query!(
+            r#"SELECT id, user_id, name, key_hash, servers AS "servers: Json<Vec<Uuid>>",
+                permissions AS "permissions: Json<Vec<Permission>>",
+                created_at, expires_at, last_used_at
+            FROM api_keys
+            WHERE id = $1 AND (expires_at IS NULL OR expires_at > NOW())"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        .ok()
+        .flatten()
+        .ok_or(AuthDecodeError::InvalidApiKey)?;
+
+        let digest: [u8; 32] = Sha256::digest(secret.as_bytes()).into();
+        let cached = VERIFIED.lock().unwrap().get(&id).is_some_and(|verified| {
+            verified.at.elapsed() < VERIFIED_FOR
+                && verified.key_hash == row.key_hash
+                && verified.secret == digest
+        });
+        if !cached {
+            let (secret, hash) = (secret.to_string(), row.key_hash.clone());
+            let valid = tokio::task::spawn_blocking(move || {
+                PasswordHash::new(&hash).is_ok_and(|hash| {
+                    Argon2::default()
+                        .verify_password(secret.as_bytes(), &hash)
+                        .is_ok()
+                })
+            })
+            .await
+            .unwrap_or(false);
+            if !valid {
+                return Err(AuthDecodeError::InvalidApiKey);
+            }
+            let mut verified = VERIFIED.lock().unwrap();
+            verified.retain(|_, verified| verified.at.elapsed() < VERIFIED_FOR);
+            verified.insert(
+                id,
+                Verified {
+                    key_hash: row.key_hash,
+                    secret: digest,
+                    at: Instant::now(),
+                },
+            );
+        }
+
+        // once a minute is plenty, and saves a write on every request
+        sqlx::query!(
+            "UPDATE api_keys SET last_used_at = NOW()
+            WHERE id = $1 AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')",
+            id
+        )
+        .execute(pool)
+        .await
+        .ok();
+
+        let user = User::from_id(row.user_id, pool)
+            .await
+            .ok_or(AuthDecodeError::NonexistentUser)?;
+        let key = ApiKey {
+            id: row.id,
+            user_id: row.user_id,
+            name: row.name,
+            servers: row.servers,
+            permissions: row.permissions,
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+            last_used_at: row.last_used_at,
+        };
+        Ok((key, user))
+    }
+
+    /// Narrows what the key's user can do on a server down to the key's
+    /// scope. Keys never get owner access, even from the owner.
+    pub fn restrict(&self, server_id: Uuid, access: ServerAccess) -> Option<ServerAccess> {
+        if let Some(servers) = &self.servers {
+            if !servers.contains(&server_id) {
+                return None;
+            }
+        }
+        let permissions = self
+            .permissions
+            .iter()
+            .copied()
+            .filter(|permission| access.allows(*permission))
+            .collect();
+        Some(ServerAccess::Member(permissions))
+    }
+
+    pub async fn list(user_id: Uuid, pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            ApiKey,
+            r#"SELECT id, user_id, name, servers AS "servers: _", permissions AS "permissions: _",
+                created_at, expires_at, last_used_at
+            FROM api_keys WHERE user_id = $1
+            ORDER BY created_at"#,
+            user_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Deletes every key of the user, for when their password changes.
+    pub async fn revoke_all(user_id: Uuid, pool: &PgPool) -> Result<(), sqlx::Error> {
+        sqlx::query!("DELETE FROM api_keys WHERE user_id = $1", user_id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete(id: Uuid, user_id: Uuid, pool: &PgPool) -> Result<(), ApiKeyError> {
+        let result = sqlx::query!(
+            "DELETE FROM api_keys WHERE id = $1 AND user_id = $2",
+            id,
+            user_id
+        )
+        .execute(pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(ApiKeyError::KeyNotFound);
+        }
+        Ok(())
+    }
+}
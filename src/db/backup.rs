@@ -0,0 +1,630 @@
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt as _};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use thiserror::Error;
+use tokio::{fs, io::AsyncWriteExt as _};
+use uuid::Uuid;
+
+use crate::{
+    backup::{
+        archive, live, retention,
+        target::{
+            s3::{S3Error, S3Target},
+            BackupTarget, BackupTargetKind,
+        },
+        BackupLock,
+    },
+    config::CONFIG,
+    console::ConsoleError,
+    response_codes,
+};
+
+use super::{
+    quota::{self, Quota, QuotaExceeded, Usage},
+    server::{Server, ServerStartError, ServerStopError},
+};
+
+// an upload's row is committed right after it, so anything without one that
+// is older than this was left behind
+const PRUNE_GRACE: chrono::Duration = chrono::Duration::hours(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum BackupTrigger {
+    Manual,
+    Scheduled,
+    Upload,
+    // taken automatically before a restore
+    Safety,
+}
+
+#[derive(FromRow, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Backup {
+    pub id: Uuid,
+    pub server_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub size: i64,
+    pub checksum: String,
+    pub trigger: BackupTrigger,
+    pub target: BackupTargetKind,
+}
+
+#[derive(FromRow, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupPolicy {
+    /// Minutes between scheduled backups, `None` disables them.
+    pub interval_minutes: Option<i32>,
+    pub keep_last: i32,
+    pub keep_daily: i32,
+    pub keep_weekly: i32,
+    /// Where backups are stored, `None` uses the global default.
+    pub target: Option<BackupTargetKind>,
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        Self {
+            interval_minutes: None,
+            keep_last: 5,
+            keep_daily: 7,
+            keep_weekly: 4,
+            target: None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("Filesystem error: {0}")]
+    FilesystemError(#[from] std::io::Error),
+    #[error("A backup is already in progress for this server")]
+    BackupInProgress,
+    #[error("Backup not found")]
+    BackupNotFound,
+    #[error("Threading error")]
+    ThreadError,
+    #[error("Invalid backup policy: {0}")]
+    InvalidPolicy(&'static str),
+    #[error("Console error: {0}")]
+    ConsoleError(#[from] ConsoleError),
+    #[error("Timed out creating the backup")]
+    Timeout,
+    #[error("The backup archive is corrupted")]
+    ChecksumMismatch,
+    #[error("The uploaded file is not a valid backup archive")]
+    InvalidArchive,
+    #[error("Backups can be at most {0}MB")]
+    TooLarge(u64),
+    #[error("Failed to stop server: {0}")]
+    StopError(#[from] ServerStopError),
+    #[error("Failed to start server: {0}")]
+    StartError(#[from] ServerStartError),
+    #[error("Backup storage error: {0}")]
+    StorageError(#[from] S3Error),
+    #[error("The {0:?} backup target is not configured")]
+    TargetUnavailable(BackupTargetKind),
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(#[from] QuotaExceeded),
+}
+
+response_codes!(BackupError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    FilesystemError(INTERNAL_SERVER_ERROR),
+    BackupInProgress(CONFLICT),
+    BackupNotFound(NOT_FOUND),
+    ThreadError(INTERNAL_SERVER_ERROR),
+    InvalidPolicy(BAD_REQUEST),
+    ConsoleError(INTERNAL_SERVER_ERROR),
+    Timeout(INTERNAL_SERVER_ERROR),
+    ChecksumMismatch(CONFLICT),
+    InvalidArchive(BAD_REQUEST),
+    TooLarge(PAYLOAD_TOO_LARGE),
+    StopError(INTERNAL_SERVER_ERROR),
+    StartError(INTERNAL_SERVER_ERROR),
+    StorageError(INTERNAL_SERVER_ERROR),
+    TargetUnavailable(BAD_REQUEST),
+    QuotaExceeded(FORBIDDEN),
+});
+
+impl Backup {
+    pub fn server_dir(server_id: Uuid) -> PathBuf {
+        PathBuf::from(&CONFIG.backup_dir).join(server_id.to_string())
+    }
+
+    /// Where the archive is kept locally, or staged while it's downloaded from
+    /// a remote target.
+    pub fn path(&self) -> PathBuf {
+        Self::server_dir(self.server_id).join(format!("{}.tar.gz", self.id))
+    }
+
+    pub fn object_name(server_id: Uuid, id: Uuid) -> String {
+        format!("{}/{}.tar.gz", server_id, id)
+    }
+
+    pub fn target(&self) -> Result<BackupTarget, BackupError> {
+        BackupTarget::new(self.target).ok_or(BackupError::TargetUnavailable(self.target))
+    }
+
+    pub async fn from_id(id: Uuid, server_id: Uuid, pool: &PgPool) -> Option<Self> {
+        sqlx::query_as!(
+            Backup,
+            r#"SELECT id, server_id, created_at, size, checksum, trigger AS "trigger: _",
+                target AS "target: _"
+            FROM backups WHERE id = $1 AND server_id = $2"#,
+            id,
+            server_id
+        )
+        .fetch_one(pool)
+        .await
+        .ok()
+    }
+
+    pub async fn get_all(server_id: Uuid, pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Backup,
+            r#"SELECT id, server_id, created_at, size, checksum, trigger AS "trigger: _",
+                target AS "target: _"
+            FROM backups WHERE server_id = $1 ORDER BY created_at DESC"#,
+            server_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Archives the server's volume and records it, then prunes old backups
+    /// according to the server's retention policy.
+    pub async fn create(
+        server: &Server,
+        trigger: BackupTrigger,
+        pool: &PgPool,
+    ) -> Result<Self, BackupError> {
+        let _lock = BackupLock::acquire(server.id).ok_or(BackupError::BackupInProgress)?;
+        Self::create_locked(server, trigger, pool).await
+    }
+
+    async fn create_locked(
+        server: &Server,
+        trigger: BackupTrigger,
+        pool: &PgPool,
+    ) -> Result<Self, BackupError> {
+        let target_kind = BackupPolicy::get(server.id, pool).await?.target_kind();
+        let target =
+            BackupTarget::new(target_kind).ok_or(BackupError::TargetUnavailable(target_kind))?;
+        // the size is only known once the archive is written, see `insert`
+        let extra = Usage {
+            backups: 1,
+            ..Default::default()
+        };
+        Quota::check::<BackupError>(server.owner, extra, pool).await?;
+
+        let id = Uuid::new_v4();
+        let dir = Self::server_dir(server.id);
+        fs::create_dir_all(&dir).await?;
+        let path = dir.join(format!("{}.tar.gz", id));
+
+        log::info!("backing up {} to {}", server.id, path.display());
+        let source = PathBuf::from(server.volume_path());
+        let dest = path.clone();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let archive = {
+            let cancel = Arc::clone(&cancel);
+            async move {
+                let handle = tokio::task::spawn_blocking(move || {
+                    archive::create_archive(&source, &dest, &cancel)
+                });
+                handle
+                    .await
+                    .map_err(|_| BackupError::ThreadError)?
+                    .map_err(BackupError::from)
+            }
+        };
+
+        // if docker can't be reached, the server can't be running either
+        let running = server.is_running().await.unwrap_or(false);
+        let result = match server.template().and_then(|t| t.save.clone()) {
+            Some(save) if running => live::with_saving_paused(server.id, &save, archive).await,
+            _ => archive.await,
+        };
+        // stops the archive if it was abandoned by a timeout
+        cancel.store(true, Ordering::Relaxed);
+        let (size, checksum) = result?;
+
+        let backup = Self::insert(
+            id,
+            server,
+            size,
+            checksum,
+            trigger,
+            (target_kind, &target),
+            pool,
+        )
+        .await?;
+
+        if let Err(e) = Self::prune(server.id, pool).await {
+            log::error!("failed to prune backups for {}: {}", server.id, e);
+        }
+
+        Ok(backup)
+    }
+
+    // moves a finished archive to its target and records it, cleaning up if
+    // it doesn't fit in the owner's quota or either fails
+    async fn insert(
+        id: Uuid,
+        server: &Server,
+        size: u64,
+        checksum: String,
+        trigger: BackupTrigger,
+        (target_kind, target): (BackupTargetKind, &BackupTarget),
+        pool: &PgPool,
+    ) -> Result<Self, BackupError> {
+        let server_id = server.id;
+        let name = Self::object_name(server_id, id);
+        let path = Self::server_dir(server_id).join(format!("{}.tar.gz", id));
+        let extra = Usage {
+            backups: 1,
+            disk_mb: quota::to_mb(size),
+            ..Default::default()
+        };
+        // uploads can be slow, so they can't hold the quota lock, an archive
+        // that turns out not to fit is deleted again instead
+        if let Err(e) = target.store(&path, &name).await {
+            fs::remove_file(&path).await.ok();
+            return Err(e.into());
+        }
+        let result = async {
+            let mut tx = pool.begin().await?;
+            Quota::check_locked::<BackupError>(server.owner, extra, &mut tx).await?;
+            let backup = sqlx::query_as!(
+                Backup,
+                r#"INSERT INTO backups (id, server_id, size, checksum, trigger, target)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                RETURNING id, server_id, created_at, size, checksum, trigger AS "trigger: _",
+                    target AS "target: _""#,
+                id,
+                server_id,
+                size as i64,
+                checksum,
+                trigger as _,
+                target_kind as _
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            Ok(backup)
+        }
+        .await;
+
+        if result.is_err() {
+            target.delete(&name, &path).await.ok();
+        }
+        result
+    }
+
+    /// Stores an uploaded archive as a new backup of the server. The archive is
+    /// checked to be a valid gzipped tarball before it's recorded.
+    pub async fn import<S, B, E>(
+        server: &Server,
+        mut body: S,
+        pool: &PgPool,
+    ) -> Result<Self, BackupError>
+    where
+        S: Stream<Item = Result<B, E>> + Unpin,
+        B: AsRef<[u8]>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let _lock = BackupLock::acquire(server.id).ok_or(BackupError::BackupInProgress)?;
+        let target_kind = BackupPolicy::get(server.id, pool).await?.target_kind();
+        let target =
+            BackupTarget::new(target_kind).ok_or(BackupError::TargetUnavailable(target_kind))?;
+        // the size is only known once the archive is written, see `insert`
+        let extra = Usage {
+            backups: 1,
+            ..Default::default()
+        };
+        Quota::check::<BackupError>(server.owner, extra, pool).await?;
+
+        let id = Uuid::new_v4();
+        let dir = Self::server_dir(server.id);
+        fs::create_dir_all(&dir).await?;
+        let path = dir.join(format!("{}.tar.gz", id));
+        let partial = path.with_extension("partial");
+
+        let written = async {
+            let mut file = fs::File::create(&partial).await?;
+            let mut hasher = Sha256::new();
+            let mut size = 0u64;
+            while let Some(chunk) = body.next().await {
+                let chunk = chunk.map_err(std::io::Error::other)?;
+                let chunk = chunk.as_ref();
+                hasher.update(chunk);
+                size += chunk.len() as u64;
+                if size > CONFIG.max_backup_upload {
+                    return Err(BackupError::TooLarge(
+                        CONFIG.max_backup_upload / 1024 / 1024,
+                    ));
+                }
+                file.write_all(chunk).await?;
+            }
+            file.sync_all().await?;
+
+            let check = partial.clone();
+            tokio::task::spawn_blocking(move || archive::validate(&check))
+                .await
+                .map_err(|_| BackupError::ThreadError)?
+                .map_err(|_| BackupError::InvalidArchive)?;
+
+            fs::rename(&partial, &path).await?;
+            Ok::<_, BackupError>((size, format!("{:x}", hasher.finalize())))
+        }
+        .await;
+
+        let (size, checksum) = match written {
+            Ok(written) => written,
+            Err(e) => {
+                fs::remove_file(&partial).await.ok();
+                return Err(e);
+            }
+        };
+
+        Self::insert(
+            id,
+            server,
+            size,
+            checksum,
+            BackupTrigger::Upload,
+            (target_kind, &target),
+            pool,
+        )
+        .await
+    }
+
+    /// Replaces the server's volume with the contents of this backup. The
+    /// server is stopped first and started again once the restore is done.
+    /// With `safety_snapshot`, the current volume is backed up beforehand.
+    pub async fn restore(
+        &self,
+        server: &Server,
+        safety_snapshot: bool,
+        pool: &PgPool,
+    ) -> Result<(), BackupError> {
+        let _lock = BackupLock::acquire(server.id).ok_or(BackupError::BackupInProgress)?;
+
+        let target = self.target()?;
+        let source = self.path();
+        fs::create_dir_all(Self::server_dir(server.id)).await?;
+        target
+            .fetch(&Self::object_name(self.server_id, self.id), &source)
+            .await?;
+
+        let result = self
+            .restore_from(&source, server, safety_snapshot, pool)
+            .await;
+        // remote archives were only downloaded for the restore
+        if !matches!(target, BackupTarget::Local) {
+            fs::remove_file(&source).await.ok();
+        }
+        result
+    }
+
+    async fn restore_from(
+        &self,
+        source: &Path,
+        server: &Server,
+        safety_snapshot: bool,
+        pool: &PgPool,
+    ) -> Result<(), BackupError> {
+        let checksum = {
+            let source = source.to_path_buf();
+            tokio::task::spawn_blocking(move || archive::checksum(&source))
+                .await
+                .map_err(|_| BackupError::ThreadError)??
+        };
+        if checksum != self.checksum {
+            return Err(BackupError::ChecksumMismatch);
+        }
+
+        log::info!("restoring backup {} of {}", self.id, server.id);
+        server.stop().await?;
+
+        let restored = async {
+            if safety_snapshot {
+                Self::create_locked(server, BackupTrigger::Safety, pool).await?;
+            }
+            let source = source.to_path_buf();
+            let volume = PathBuf::from(server.volume_path());
+            tokio::task::spawn_blocking(move || archive::extract(&source, &volume))
+                .await
+                .map_err(|_| BackupError::ThreadError)??;
+            Ok::<_, BackupError>(())
+        }
+        .await;
+
+        // the volume is untouched if the restore failed, so the server can
+        // come back as it was
+        if let Err(e) = restored {
+            log::error!(
+                "failed to restore backup {} of {}: {}",
+                self.id,
+                server.id,
+                e
+            );
+            if let Err(e) = server.start(pool).await {
+                log::error!("failed to restart {} after the restore: {}", server.id, e);
+            }
+            return Err(e);
+        }
+        server.start(pool).await?;
+        Ok(())
+    }
+
+    pub async fn delete(self, pool: &PgPool) -> Result<(), BackupError> {
+        let target = self.target()?;
+        sqlx::query!("DELETE FROM backups WHERE id = $1", self.id)
+            .execute(pool)
+            .await?;
+        target
+            .delete(&Self::object_name(self.server_id, self.id), &self.path())
+            .await?;
+        Ok(())
+    }
+
+    /// Removes every archive a server has, wherever it's stored. Used once the
+    /// server itself is gone.
+    pub async fn delete_all(server_id: Uuid) -> Result<(), BackupError> {
+        match fs::remove_dir_all(Self::server_dir(server_id)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        if let Some(BackupTarget::S3(s3)) = BackupTarget::new(BackupTargetKind::S3) {
+            for object in s3.list(&s3.key(&format!("{}/", server_id))).await? {
+                s3.delete(&object.key).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes scheduled backups that fall outside of the retention policy.
+    /// Manual backups are only ever deleted by the user.
+    pub async fn prune(server_id: Uuid, pool: &PgPool) -> Result<(), BackupError> {
+        let policy = BackupPolicy::get(server_id, pool).await?;
+        let all = Self::get_all(server_id, pool).await?;
+        // servers that only ever backed up locally have nothing to prune there
+        let uses_s3 = policy.target_kind() == BackupTargetKind::S3
+            || all.iter().any(|b| b.target == BackupTargetKind::S3);
+        let backups: Vec<Backup> = all
+            .into_iter()
+            .filter(|b| b.trigger == BackupTrigger::Scheduled)
+            .collect();
+        let times = backups
+            .iter()
+            .map(|b| (b.id, b.created_at))
+            .collect::<Vec<_>>();
+        let prune = retention::backups_to_prune(&times, &policy, Utc::now());
+
+        for backup in backups.into_iter().filter(|b| prune.contains(&b.id)) {
+            log::info!("pruning backup {} of {}", backup.id, server_id);
+            backup.delete(pool).await?;
+        }
+
+        if !uses_s3 {
+            return Ok(());
+        }
+        if let Some(BackupTarget::S3(s3)) = BackupTarget::new(BackupTargetKind::S3) {
+            Self::prune_remote(server_id, &s3, pool).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes objects that have no backup row, like the leftovers of an
+    /// upload that failed halfway or a row that was deleted while the target
+    /// was unreachable. Recent objects are left alone, their rows might not
+    /// be committed yet.
+    pub async fn prune_remote(
+        server_id: Uuid,
+        s3: &S3Target,
+        pool: &PgPool,
+    ) -> Result<(), BackupError> {
+        let known = Self::get_all(server_id, pool)
+            .await?
+            .into_iter()
+            .map(|b| s3.key(&Self::object_name(b.server_id, b.id)))
+            .collect::<HashSet<_>>();
+        let cutoff = Utc::now() - PRUNE_GRACE;
+        for object in s3.list(&s3.key(&format!("{}/", server_id))).await? {
+            let old = object.last_modified.is_some_and(|t| t < cutoff);
+            if old && !known.contains(&object.key) {
+                log::info!("pruning orphaned backup object {}", object.key);
+                s3.delete(&object.key).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BackupPolicy {
+    pub fn target_kind(&self) -> BackupTargetKind {
+        self.target.unwrap_or(CONFIG.backup_target)
+    }
+
+    pub async fn get(server_id: Uuid, pool: &PgPool) -> Result<Self, sqlx::Error> {
+        let policy = sqlx::query_as!(
+            BackupPolicy,
+            r#"SELECT interval_minutes, keep_last, keep_daily, keep_weekly, target AS "target: _"
+            FROM backup_policies WHERE server_id = $1"#,
+            server_id
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(policy.unwrap_or_default())
+    }
+
+    pub async fn set(&self, server_id: Uuid, pool: &PgPool) -> Result<(), BackupError> {
+        if matches!(self.interval_minutes, Some(i) if i < 5) {
+            return Err(BackupError::InvalidPolicy(
+                "backups can be scheduled at most every 5 minutes",
+            ));
+        }
+        if self.keep_last < 0 || self.keep_daily < 0 || self.keep_weekly < 0 {
+            return Err(BackupError::InvalidPolicy(
+                "retention counts cannot be negative",
+            ));
+        }
+        let target_kind = self.target_kind();
+        if BackupTarget::new(target_kind).is_none() {
+            return Err(BackupError::TargetUnavailable(target_kind));
+        }
+
+        sqlx::query!(
+            "INSERT INTO backup_policies (server_id, interval_minutes, keep_last, keep_daily, keep_weekly, target)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (server_id) DO UPDATE SET
+                interval_minutes = EXCLUDED.interval_minutes,
+                keep_last = EXCLUDED.keep_last,
+                keep_daily = EXCLUDED.keep_daily,
+                keep_weekly = EXCLUDED.keep_weekly,
+                target = EXCLUDED.target",
+            server_id,
+            self.interval_minutes,
+            self.keep_last,
+            self.keep_daily,
+            self.keep_weekly,
+            self.target as _
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Servers whose last scheduled backup is older than their interval.
+    pub async fn due(pool: &PgPool) -> Result<Vec<Uuid>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT p.server_id FROM backup_policies p
+            WHERE p.interval_minutes IS NOT NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM backups b
+                WHERE b.server_id = p.server_id
+                AND b.trigger = 'scheduled'
+                AND b.created_at > NOW() - make_interval(mins => p.interval_minutes)
+            )"
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(rows.into_iter().map(|r| r.server_id).collect())
+    }
+}
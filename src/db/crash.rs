@@ -0,0 +1,219 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::{path::PathBuf, time::Duration};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{config::CONFIG, response_codes};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum CrashOutcome {
+    /// A restart was scheduled according to the restart policy.
+    Restarting,
+    /// The server crashed too often in a short time, so it was left stopped.
+    CrashLoop,
+    /// Restarting is disabled for this server.
+    NotRestarted,
+}
+
+#[derive(FromRow, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Crash {
+    pub id: Uuid,
+    pub server_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub exit_code: Option<i64>,
+    pub report_name: Option<String>,
+    pub outcome: CrashOutcome,
+}
+
+#[derive(FromRow, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestartPolicy {
+    pub enabled: bool,
+    /// How many crashes are restarted within `window_minutes` before the
+    /// server is considered to be crash looping.
+    pub max_restarts: i32,
+    pub window_minutes: i32,
+    /// The delay before the first restart, doubled for every recent crash.
+    pub backoff_seconds: i32,
+    pub max_backoff_seconds: i32,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_restarts: 5,
+            window_minutes: 10,
+            backoff_seconds: 5,
+            max_backoff_seconds: 300,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CrashError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("Filesystem error: {0}")]
+    FilesystemError(#[from] std::io::Error),
+    #[error("Crash not found")]
+    CrashNotFound,
+    #[error("This crash has no crash report")]
+    ReportNotFound,
+    #[error("Invalid restart policy: {0}")]
+    InvalidPolicy(&'static str),
+}
+
+response_codes!(CrashError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    FilesystemError(INTERNAL_SERVER_ERROR),
+    CrashNotFound(NOT_FOUND),
+    ReportNotFound(NOT_FOUND),
+    InvalidPolicy(BAD_REQUEST),
+});
+
+impl Crash {
+    pub fn server_dir(server_id: Uuid) -> PathBuf {
+        PathBuf::from(&CONFIG.crash_dir).join(server_id.to_string())
+    }
+
+    /// Where the copy of the crash report is kept.
+    pub fn report_path(server_id: Uuid, id: Uuid) -> PathBuf {
+        Self::server_dir(server_id).join(format!("{}.txt", id))
+    }
+
+    pub async fn from_id(id: Uuid, server_id: Uuid, pool: &PgPool) -> Option<Self> {
+        sqlx::query_as!(
+            Crash,
+            r#"SELECT id, server_id, created_at, exit_code, report_name, outcome AS "outcome: _"
+            FROM crashes WHERE id = $1 AND server_id = $2"#,
+            id,
+            server_id
+        )
+        .fetch_one(pool)
+        .await
+        .ok()
+    }
+
+    pub async fn get_all(server_id: Uuid, pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Crash,
+            r#"SELECT id, server_id, created_at, exit_code, report_name, outcome AS "outcome: _"
+            FROM crashes WHERE server_id = $1 ORDER BY created_at DESC"#,
+            server_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn insert(
+        id: Uuid,
+        server_id: Uuid,
+        exit_code: Option<i64>,
+        report_name: Option<String>,
+        outcome: CrashOutcome,
+        pool: &PgPool,
+    ) -> Result<Self, sqlx::Error> {
+        sqlx::query_as!(
+            Crash,
+            r#"INSERT INTO crashes (id, server_id, exit_code, report_name, outcome)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, server_id, created_at, exit_code, report_name, outcome AS "outcome: _""#,
+            id,
+            server_id,
+            exit_code,
+            report_name,
+            outcome as _
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    /// How many times the server crashed in the last `minutes`.
+    pub async fn count_recent(
+        server_id: Uuid,
+        minutes: i32,
+        pool: &PgPool,
+    ) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query!(
+            "SELECT COUNT(*) FROM crashes
+            WHERE server_id = $1 AND created_at > NOW() - make_interval(mins => $2)",
+            server_id,
+            minutes
+        )
+        .fetch_one(pool)
+        .await?
+        .count;
+        Ok(count.unwrap_or(0))
+    }
+}
+
+impl RestartPolicy {
+    /// How long to wait before restarting a server that crashed `crashes`
+    /// times within the window (counting the current crash), or `None` if it
+    /// shouldn't be restarted at all.
+    pub fn restart_delay(&self, crashes: i64) -> Option<Duration> {
+        if !self.enabled || crashes > self.max_restarts as i64 {
+            return None;
+        }
+        let exponent = (crashes.max(1) - 1).min(31) as u32;
+        let delay = (self.backoff_seconds as u64).saturating_mul(1 << exponent);
+        Some(Duration::from_secs(
+            delay.min(self.max_backoff_seconds as u64),
+        ))
+    }
+
+    pub async fn get(server_id: Uuid, pool: &PgPool) -> Result<Self, sqlx::Error> {
+        let policy = sqlx::query_as!(
+            RestartPolicy,
+            "SELECT enabled, max_restarts, window_minutes, backoff_seconds, max_backoff_seconds
+            FROM restart_policies WHERE server_id = $1",
+            server_id
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(policy.unwrap_or_default())
+    }
+
+    pub async fn set(&self, server_id: Uuid, pool: &PgPool) -> Result<(), CrashError> {
+        if self.max_restarts < 0 {
+            return Err(CrashError::InvalidPolicy("maxRestarts cannot be negative"));
+        }
+        if self.window_minutes < 1 {
+            return Err(CrashError::InvalidPolicy(
+                "the crash loop window must be at least a minute",
+            ));
+        }
+        if self.backoff_seconds < 0 || self.max_backoff_seconds < self.backoff_seconds {
+            return Err(CrashError::InvalidPolicy(
+                "maxBackoffSeconds must be at least backoffSeconds",
+            ));
+        }
+
+        sqlx::query!(
+            "INSERT INTO restart_policies
+                (server_id, enabled, max_restarts, window_minutes, backoff_seconds, max_backoff_seconds)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (server_id) DO UPDATE SET
+                enabled = EXCLUDED.enabled,
+                max_restarts = EXCLUDED.max_restarts,
+                window_minutes = EXCLUDED.window_minutes,
+                backoff_seconds = EXCLUDED.backoff_seconds,
+                max_backoff_seconds = EXCLUDED.max_backoff_seconds",
+            server_id,
+            self.enabled,
+            self.max_restarts,
+            self.window_minutes,
+            self.backoff_seconds,
+            self.max_backoff_seconds
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{types::Json, FromRow, PgPool};
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::{
+    quota::{Quota, QuotaError},
+    user::{User, UserCreationError, UserRole},
+};
+use crate::response_codes;
+
+#[derive(FromRow, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Invite {
+    pub id: Uuid,
+    pub created_by: Option<Uuid>,
+    pub note: Option<String>,
+    /// The role users who sign up with it get, instead of the usual one.
+    pub role: Option<UserRole>,
+    /// The quota users who sign up with it get, instead of the default one.
+    pub quota: Option<Json<Quota>>,
+    /// `None` for unlimited.
+    pub max_uses: Option<i32>,
+    pub uses: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InviteBody {
+    pub note: Option<String>,
+    pub role: Option<UserRole>,
+    pub quota: Option<Quota>,
+    pub max_uses: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(FromRow, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InviteUse {
+    /// `None` once the user is deleted.
+    pub user_id: Option<Uuid>,
+    pub username: String,
+    pub ip: Option<String>,
+    pub used_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Error)]
+pub enum InviteError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("Invite not found")]
+    InviteNotFound,
+    #[error("The invite code is invalid, used up or has expired")]
+    InvalidCode,
+    #[error("Invalid invite: {0}")]
+    InvalidInvite(&'static str),
+    #[error("{0}")]
+    InvalidQuota(#[from] QuotaError),
+    #[error("Failed to create the user: {0}")]
+    UserCreationError(UserCreationError),
+    #[error("User already exists")]
+    UserAlreadyExists,
+}
+
+response_codes!(InviteError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    InviteNotFound(NOT_FOUND),
+    InvalidCode(FORBIDDEN),
+    InvalidInvite(BAD_REQUEST),
+    InvalidQuota(BAD_REQUEST),
+    UserCreationError(INTERNAL_SERVER_ERROR),
+    UserAlreadyExists(CONFLICT),
+});
+
+impl From<UserCreationError> for InviteError {
+    fn from(e: UserCreationError) -> Self {
+        match e {
+            UserCreationError::UserAlreadyExists => Self::UserAlreadyExists,
+            e => Self::UserCreationError(e),
+        }
+    }
+}
+
+impl Invite {
+    /// Creates an invite, returning it along with its code, which can't be
+    /// seen again.
+    pub async fn create(
+        created_by: Option<Uuid>,
+        body: InviteBody,
+        pool: &PgPool,
+    ) -> Result<(Self, String), InviteError> {
+        if body.max_uses.is_some_and(|uses| uses < 1) {
+            return Err(InviteError::InvalidInvite(
+                "it has to be usable at least once",
+            ));
+        }
+        if body.expires_at.is_some_and(|expires| expires <= Utc::now()) {
+            return Err(InviteError::InvalidInvite(
+                "the expiry has to be in the future",
+            ));
+        }
+        if let Some(quota) = &body.quota {
+            quota.validate()?;
+        }
+
+        let mut bytes = [0u8; 12];
+        OsRng.fill_bytes(&mut bytes);
+        let code = bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        let invite = sqlx::query_as!(
+            Invite,
+            r#"INSERT INTO invites (code_hash, created_by, note, role, quota, max_uses, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, created_by, note, role AS "role: _", quota AS "quota: _", max_uses,
+                uses, expires_at, created_at"#,
+            hash(&code),
+            created_by,
+            body.note,
+            body.role as _,
+            body.quota.map(Json) as _,
+            body.max_uses,
+            body.expires_at
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok((invite, code))
+    }
+
+    /// Signs a user up with an invite, whether or not signups are enabled.
+    /// The use is only counted if the user is created.
+    pub async fn redeem(
+        code: &str,
+        username: String,
+        password: String,
+        ip: Option<String>,
+        pool: &PgPool,
+    ) -> Result<User, InviteError> {
+        let mut tx = pool.begin().await?;
+        // locks the invite, so the last use can't be taken twice
+        let invite = sqlx::query_as!(
+            Invite,
+            r#"UPDATE invites SET uses = uses + 1
+            WHERE code_hash = $1
+                AND (expires_at IS NULL OR expires_at > NOW())
+                AND (max_uses IS NULL OR uses < max_uses)
+            RETURNING id, created_by, note, role AS "role: _", quota AS "quota: _", max_uses,
+                uses, expires_at, created_at"#,
+            hash(code.trim())
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(InviteError::InvalidCode)?;
+
+        let mut user = User::create(username, password, &mut *tx).await?;
+        if let Some(role) = invite.role {
+            sqlx::query!(
+                "UPDATE users SET role = $2 WHERE id = $1",
+                user.id,
+                role as _
+            )
+            .execute(&mut *tx)
+            .await?;
+            user.role = role;
+        }
+        if let Some(Json(quota)) = &invite.quota {
+            sqlx::query!(
+                "INSERT INTO user_quotas (user_id, max_servers, max_memory_mb, max_disk_mb, max_backups)
+                VALUES ($1, $2, $3, $4, $5)",
+                user.id,
+                quota.max_servers,
+                quota.max_memory_mb,
+                quota.max_disk_mb,
+                quota.max_backups
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query!(
+            "INSERT INTO invite_uses (invite_id, user_id, username, ip) VALUES ($1, $2, $3, $4)",
+            invite.id,
+            user.id,
+            user.username,
+            ip
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    pub async fn list(pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Invite,
+            r#"SELECT id, created_by, note, role AS "role: _", quota AS "quota: _", max_uses,
+                uses, expires_at, created_at
+            FROM invites ORDER BY created_at DESC"#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn uses(id: Uuid, pool: &PgPool) -> Result<Vec<InviteUse>, InviteError> {
+        let exists = sqlx::query_scalar!("SELECT EXISTS(SELECT 1 FROM invites WHERE id = $1)", id)
+            .fetch_one(pool)
+            .await?;
+        if exists != Some(true) {
+            return Err(InviteError::InviteNotFound);
+        }
+        Ok(sqlx::query_as!(
+            InviteUse,
+            "SELECT user_id, username, ip, used_at FROM invite_uses
+            WHERE invite_id = $1 ORDER BY used_at",
+            id
+        )
+        .fetch_all(pool)
+        .await?)
+    }
+
+    /// Revokes an invite. Users who already signed up with it are kept.
+    pub async fn delete(id: Uuid, pool: &PgPool) -> Result<(), InviteError> {
+        let result = sqlx::query!("DELETE FROM invites WHERE id = $1", id)
+            .execute(pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(InviteError::InviteNotFound);
+        }
+        Ok(())
+    }
+}
+
+// codes are random enough that a plain hash is fine
+fn hash(code: &str) -> String {
+    format!("{:x}", Sha256::digest(code.as_bytes()))
+}
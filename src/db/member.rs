@@ -0,0 +1,159 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, FromRow, PgPool};
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::{server::Server, user::User};
+use crate::response_codes;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Permission {
+    /// Watching the console and reading crash reports.
+    ConsoleRead,
+    /// Running commands in the console.
+    ConsoleWrite,
+    /// Editing the server's files, like server.properties.
+    Files,
+    Backups,
+    Schedules,
+    /// Starting, stopping and restarting the server.
+    Power,
+    /// Startup, sleep, routing and crash settings.
+    Settings,
+}
+
+/// What a user can do on a server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerAccess {
+    /// The owner, or an admin. Can do anything, including deleting the
+    /// server and managing its members.
+    Owner,
+    Member(Vec<Permission>),
+}
+
+impl ServerAccess {
+    /// `None` if the user has no access to the server at all.
+    pub async fn get(
+        server: &Server,
+        user: &User,
+        pool: &PgPool,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        if server.owner == user.id || user.is_admin() {
+            return Ok(Some(Self::Owner));
+        }
+        let permissions = sqlx::query_scalar!(
+            r#"SELECT permissions AS "permissions: Json<Vec<Permission>>"
+            FROM server_members WHERE server_id = $1 AND user_id = $2"#,
+            server.id,
+            user.id
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(permissions.map(|permissions| Self::Member(permissions.0)))
+    }
+
+    pub fn allows(&self, permission: Permission) -> bool {
+        match self {
+            Self::Owner => true,
+            Self::Member(permissions) => permissions.contains(&permission),
+        }
+    }
+}
+
+#[derive(FromRow, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerMember {
+    pub user_id: Uuid,
+    pub username: String,
+    pub permissions: Json<Vec<Permission>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Error)]
+pub enum MemberError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("Server not found")]
+    ServerNotFound,
+    #[error("User not found")]
+    UserNotFound,
+    #[error("That user is not a member of this server")]
+    MemberNotFound,
+    #[error("The owner of a server can't be added as a member")]
+    IsOwner,
+}
+
+response_codes!(MemberError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    ServerNotFound(NOT_FOUND),
+    UserNotFound(NOT_FOUND),
+    MemberNotFound(NOT_FOUND),
+    IsOwner(BAD_REQUEST),
+});
+
+impl ServerMember {
+    pub async fn list(server_id: Uuid, pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            ServerMember,
+            r#"SELECT m.user_id, u.username, m.permissions AS "permissions: _", m.created_at
+            FROM server_members m JOIN users u ON u.id = m.user_id
+            WHERE m.server_id = $1
+            ORDER BY m.created_at"#,
+            server_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Gives a user access to the server. Inviting someone who's already a
+    /// member replaces their permissions.
+    pub async fn invite(
+        server: &Server,
+        username: &str,
+        mut permissions: Vec<Permission>,
+        pool: &PgPool,
+    ) -> Result<Self, MemberError> {
+        let user = User::from_username(username, pool)
+            .await
+            .ok_or(MemberError::UserNotFound)?;
+        if user.id == server.owner {
+            return Err(MemberError::IsOwner);
+        }
+        permissions.sort();
+        permissions.dedup();
+
+        let created_at = sqlx::query_scalar!(
+            "INSERT INTO server_members (server_id, user_id, permissions)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (server_id, user_id) DO UPDATE SET permissions = EXCLUDED.permissions
+            RETURNING created_at",
+            server.id,
+            user.id,
+            Json(&permissions) as _
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(Self {
+            user_id: user.id,
+            username: user.username,
+            permissions: Json(permissions),
+            created_at,
+        })
+    }
+
+    pub async fn revoke(server_id: Uuid, user_id: Uuid, pool: &PgPool) -> Result<(), MemberError> {
+        let result = sqlx::query!(
+            "DELETE FROM server_members WHERE server_id = $1 AND user_id = $2",
+            server_id,
+            user_id
+        )
+        .execute(pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(MemberError::MemberNotFound);
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,17 @@
+pub mod api_key;
+pub mod backup;
+pub mod crash;
+pub mod invite;
+pub mod member;
+pub mod oidc;
+pub mod orphan;
+pub mod quota;
+pub mod schedule;
 pub mod server;
+pub mod session;
+pub mod sleep;
+pub mod startup;
+pub mod two_factor;
 pub mod user;
 
 use sqlx::PgPool;
@@ -0,0 +1,104 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+// logins are only kept for 10 minutes, this is plenty for those
+const MAX_PENDING_LOGINS: i64 = 1000;
+
+/// A login that's been sent off to the provider.
+#[derive(FromRow, Debug)]
+pub struct PendingLogin {
+    pub state: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    pub link_user: Option<Uuid>,
+    /// The hash of a secret only the browser that started the login has, so
+    /// nobody else can finish it.
+    pub browser_hash: String,
+}
+
+/// A provider account linked to a user.
+#[derive(FromRow, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Identity {
+    pub issuer: String,
+    pub subject: String,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PendingLogin {
+    /// Saves the login, returning `false` if there are too many already.
+    pub async fn create(&self, pool: &PgPool) -> Result<bool, sqlx::Error> {
+        sqlx::query!("DELETE FROM oidc_logins WHERE created_at < NOW() - INTERVAL '10 minutes'")
+            .execute(pool)
+            .await?;
+        let result = sqlx::query!(
+            "INSERT INTO oidc_logins (state, code_verifier, nonce, link_user, browser_hash)
+            SELECT $1, $2, $3, $4, $5
+            WHERE (SELECT COUNT(*) FROM oidc_logins) < $6",
+            self.state,
+            self.code_verifier,
+            self.nonce,
+            self.link_user,
+            self.browser_hash,
+            MAX_PENDING_LOGINS
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Finds the login a callback is for. Each one can only be used once, and
+    /// only for 10 minutes.
+    pub async fn take(state: &str, pool: &PgPool) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            PendingLogin,
+            "DELETE FROM oidc_logins
+            WHERE state = $1 AND created_at > NOW() - INTERVAL '10 minutes'
+            RETURNING state, code_verifier, nonce, link_user, browser_hash",
+            state
+        )
+        .fetch_optional(pool)
+        .await
+    }
+}
+
+impl Identity {
+    pub async fn user_id(
+        issuer: &str,
+        subject: &str,
+        pool: &PgPool,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        sqlx::query_scalar!(
+            "SELECT user_id FROM oidc_identities WHERE issuer = $1 AND subject = $2",
+            issuer,
+            subject
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Links a provider account to a user, returning `None` if it's already
+    /// linked to someone else.
+    pub async fn link(
+        issuer: &str,
+        subject: &str,
+        user_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Identity,
+            "INSERT INTO oidc_identities (issuer, subject, user_id) VALUES ($1, $2, $3)
+            ON CONFLICT (issuer, subject) DO UPDATE SET issuer = EXCLUDED.issuer
+                WHERE oidc_identities.user_id = EXCLUDED.user_id
+            RETURNING issuer, subject, user_id, created_at",
+            issuer,
+            subject,
+            user_id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+}
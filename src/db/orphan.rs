@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum OrphanKind {
+    Container,
+    /// A docker volume.
+    Volume,
+    /// A directory in `volumes/`.
+    Directory,
+}
+
+impl OrphanKind {
+    /// Replaces the recorded orphans of this kind with `names`, returning the
+    /// ones that weren't recorded before.
+    pub async fn sync(self, names: &[String], pool: &PgPool) -> Result<Vec<String>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        sqlx::query!(
+            "DELETE FROM orphans WHERE kind = $1 AND NOT (name = ANY($2))",
+            self as _,
+            names
+        )
+        .execute(&mut *tx)
+        .await?;
+        let new = sqlx::query!(
+            "INSERT INTO orphans (kind, name) SELECT $1, UNNEST($2::TEXT[])
+            ON CONFLICT DO NOTHING RETURNING name",
+            self as _,
+            names
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(new.into_iter().map(|r| r.name).collect())
+    }
+}
@@ -0,0 +1,338 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgExecutor, PgPool};
+use std::{fs, io, path::Path};
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::{server::Server, user::User};
+use crate::response_codes;
+
+const MB: u64 = 1024 * 1024;
+// the first key of the advisory locks held while checking quotas, the second
+// is the owner
+const QUOTA_LOCK: i32 = 0x7175_6f74;
+
+/// Limits on what a user's servers can use, `None` is unlimited.
+#[derive(FromRow, PartialEq, Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quota {
+    pub max_servers: Option<i32>,
+    /// The memory of all of the user's servers together.
+    pub max_memory_mb: Option<i32>,
+    /// The size of the user's volumes and backups together.
+    pub max_disk_mb: Option<i64>,
+    pub max_backups: Option<i32>,
+}
+
+/// What a user's servers use, or what an operation would add to it.
+#[derive(PartialEq, Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Usage {
+    pub servers: i64,
+    pub memory_mb: i64,
+    pub disk_mb: i64,
+    pub backups: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaStatus {
+    pub quota: Quota,
+    pub usage: Usage,
+    /// Whether the user has their own quota instead of the default one.
+    pub custom: bool,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum QuotaExceeded {
+    #[error("you can have at most {0} servers")]
+    Servers(i32),
+    #[error("your servers can use at most {0}MB of memory together")]
+    Memory(i32),
+    #[error("your servers and backups can use at most {0}MB of disk space together")]
+    Disk(i64),
+    #[error("you can have at most {0} backups")]
+    Backups(i32),
+}
+
+#[derive(Debug, Error)]
+pub enum QuotaError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Invalid quota: {0}")]
+    InvalidQuota(&'static str),
+}
+
+response_codes!(QuotaError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    UserNotFound(NOT_FOUND),
+    InvalidQuota(BAD_REQUEST),
+});
+
+impl Quota {
+    pub async fn default_quota<'e>(executor: impl PgExecutor<'e>) -> Result<Self, sqlx::Error> {
+        sqlx::query_as!(
+            Quota,
+            "SELECT max_servers, max_memory_mb, max_disk_mb, max_backups FROM default_quota"
+        )
+        .fetch_one(executor)
+        .await
+    }
+
+    pub async fn set_default(&self, pool: &PgPool) -> Result<(), QuotaError> {
+        self.validate()?;
+        sqlx::query!(
+            "UPDATE default_quota
+            SET max_servers = $1, max_memory_mb = $2, max_disk_mb = $3, max_backups = $4",
+            self.max_servers,
+            self.max_memory_mb,
+            self.max_disk_mb,
+            self.max_backups
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// The user's own quota, if they have one.
+    pub async fn custom<'e>(
+        user_id: Uuid,
+        executor: impl PgExecutor<'e>,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Quota,
+            "SELECT max_servers, max_memory_mb, max_disk_mb, max_backups
+            FROM user_quotas WHERE user_id = $1",
+            user_id
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// The quota that applies to the user.
+    pub async fn of(user_id: Uuid, conn: &mut PgConnection) -> Result<Self, sqlx::Error> {
+        match Self::custom(user_id, &mut *conn).await? {
+            Some(quota) => Ok(quota),
+            None => Self::default_quota(conn).await,
+        }
+    }
+
+    /// Gives the user their own quota, or puts them back on the default one
+    /// with `None`.
+    pub async fn set_custom(
+        user_id: Uuid,
+        quota: Option<&Self>,
+        pool: &PgPool,
+    ) -> Result<(), QuotaError> {
+        User::from_id(user_id, pool)
+            .await
+            .ok_or(QuotaError::UserNotFound)?;
+        let Some(quota) = quota else {
+            sqlx::query!("DELETE FROM user_quotas WHERE user_id = $1", user_id)
+                .execute(pool)
+                .await?;
+            return Ok(());
+        };
+
+        quota.validate()?;
+        sqlx::query!(
+            "INSERT INTO user_quotas (user_id, max_servers, max_memory_mb, max_disk_mb, max_backups)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (user_id) DO UPDATE SET
+                max_servers = EXCLUDED.max_servers,
+                max_memory_mb = EXCLUDED.max_memory_mb,
+                max_disk_mb = EXCLUDED.max_disk_mb,
+                max_backups = EXCLUDED.max_backups",
+            user_id,
+            quota.max_servers,
+            quota.max_memory_mb,
+            quota.max_disk_mb,
+            quota.max_backups
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn status(user_id: Uuid, pool: &PgPool) -> Result<QuotaStatus, sqlx::Error> {
+        let mut conn = pool.acquire().await?;
+        let custom = Self::custom(user_id, &mut *conn).await?;
+        Ok(QuotaStatus {
+            custom: custom.is_some(),
+            quota: match custom {
+                Some(quota) => quota,
+                None => Self::default_quota(&mut *conn).await?,
+            },
+            usage: Usage::of(user_id, &mut conn).await?,
+        })
+    }
+
+    /// Checks that `extra` fits in the owner's quota on top of what they
+    /// already use.
+    pub async fn check<E>(owner: Uuid, extra: Usage, pool: &PgPool) -> Result<(), E>
+    where
+        E: From<sqlx::Error> + From<QuotaExceeded>,
+    {
+        let mut conn = pool.acquire().await?;
+        Self::check_on(owner, extra, &mut conn).await
+    }
+
+    /// Like `check`, but holds the owner's quota lock until `tx` ends. Saving
+    /// what was checked in `tx` means concurrent checks can't both pass on
+    /// the same room.
+    pub async fn check_locked<E>(owner: Uuid, extra: Usage, tx: &mut PgConnection) -> Result<(), E>
+    where
+        E: From<sqlx::Error> + From<QuotaExceeded>,
+    {
+        sqlx::query!(
+            "SELECT pg_advisory_xact_lock($1, hashtext($2))",
+            QUOTA_LOCK,
+            owner.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+        Self::check_on(owner, extra, tx).await
+    }
+
+    async fn check_on<E>(owner: Uuid, extra: Usage, conn: &mut PgConnection) -> Result<(), E>
+    where
+        E: From<sqlx::Error> + From<QuotaExceeded>,
+    {
+        let quota = Self::of(owner, conn).await?;
+        // counting disk usage means walking every volume
+        if quota == Self::default() {
+            return Ok(());
+        }
+        let usage = Usage::of(owner, conn).await?;
+        Ok(quota.allows(&usage, &extra)?)
+    }
+
+    /// Whether the owner uses more disk space than they're allowed. Volumes
+    /// can grow without waitress doing anything, so this is checked
+    /// separately from `check`.
+    pub async fn check_disk<E>(owner: Uuid, pool: &PgPool) -> Result<(), E>
+    where
+        E: From<sqlx::Error> + From<QuotaExceeded>,
+    {
+        let mut conn = pool.acquire().await?;
+        let Some(max_disk_mb) = Self::of(owner, &mut conn).await?.max_disk_mb else {
+            return Ok(());
+        };
+        if Usage::of(owner, &mut conn).await?.disk_mb > max_disk_mb {
+            return Err(QuotaExceeded::Disk(max_disk_mb).into());
+        }
+        Ok(())
+    }
+
+    /// Only what's being added is limited, so things that shrink or stay the
+    /// same are always allowed, even over the quota.
+    pub fn allows(&self, usage: &Usage, extra: &Usage) -> Result<(), QuotaExceeded> {
+        fn exceeds(max: Option<i64>, used: i64, extra: i64) -> bool {
+            extra > 0 && max.is_some_and(|max| used + extra > max)
+        }
+
+        if exceeds(
+            self.max_servers.map(i64::from),
+            usage.servers,
+            extra.servers,
+        ) {
+            return Err(QuotaExceeded::Servers(self.max_servers.unwrap_or_default()));
+        }
+        if exceeds(
+            self.max_memory_mb.map(i64::from),
+            usage.memory_mb,
+            extra.memory_mb,
+        ) {
+            return Err(QuotaExceeded::Memory(
+                self.max_memory_mb.unwrap_or_default(),
+            ));
+        }
+        if exceeds(self.max_disk_mb, usage.disk_mb, extra.disk_mb) {
+            return Err(QuotaExceeded::Disk(self.max_disk_mb.unwrap_or_default()));
+        }
+        if exceeds(
+            self.max_backups.map(i64::from),
+            usage.backups,
+            extra.backups,
+        ) {
+            return Err(QuotaExceeded::Backups(self.max_backups.unwrap_or_default()));
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), QuotaError> {
+        if self.max_servers.is_some_and(|n| n < 0)
+            || self.max_memory_mb.is_some_and(|n| n < 0)
+            || self.max_disk_mb.is_some_and(|n| n < 0)
+            || self.max_backups.is_some_and(|n| n < 0)
+        {
+            return Err(QuotaError::InvalidQuota("limits can't be negative"));
+        }
+        Ok(())
+    }
+}
+
+impl Usage {
+    pub async fn of(owner: Uuid, conn: &mut PgConnection) -> Result<Self, sqlx::Error> {
+        // servers without startup settings use the default memory
+        let servers = sqlx::query!(
+            r#"SELECT COUNT(*) AS "servers!", COALESCE(SUM(COALESCE(c.memory_mb, 1024)), 0) AS "memory_mb!"
+            FROM servers s LEFT JOIN startup_configs c ON c.server_id = s.id
+            WHERE s.owner = $1"#,
+            owner
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        let backups = sqlx::query!(
+            r#"SELECT COUNT(*) AS "count!", COALESCE(SUM(b.size), 0)::BIGINT AS "size!"
+            FROM backups b JOIN servers s ON s.id = b.server_id
+            WHERE s.owner = $1"#,
+            owner
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        let volumes = Server::get_all(owner, conn)
+            .await?
+            .into_iter()
+            .map(|server| server.volume_path())
+            .collect::<Vec<_>>();
+        let volumes_size = tokio::task::spawn_blocking(move || {
+            volumes
+                .iter()
+                .map(|path| dir_size(Path::new(path)).unwrap_or(0))
+                .sum::<u64>()
+        })
+        .await
+        .unwrap_or(0);
+
+        Ok(Self {
+            servers: servers.servers,
+            memory_mb: servers.memory_mb,
+            disk_mb: to_mb(volumes_size + backups.size.max(0) as u64),
+            backups: backups.count,
+        })
+    }
+}
+
+/// Rounds a size in bytes up to whole megabytes.
+pub fn to_mb(bytes: u64) -> i64 {
+    bytes.div_ceil(MB) as i64
+}
+
+// doesn't follow symlinks, so a volume can't count something twice
+fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
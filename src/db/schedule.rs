@@ -0,0 +1,278 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, FromRow, PgPool};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{response_codes, schedule};
+
+// commands and waits are cheap, but a schedule shouldn't be a script
+const MAX_ACTIONS: usize = 32;
+const MAX_WAIT_SECONDS: u64 = 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "camelCase")]
+pub enum ScheduleAction {
+    /// Sends a command to the server console, without the leading slash.
+    Command(String),
+    Start,
+    Stop,
+    Restart,
+    Backup,
+    /// Waits for the given number of seconds before the next action.
+    Wait(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum ScheduleResult {
+    Success,
+    Failed,
+    // the conditions weren't met, or the previous run was still going
+    Skipped,
+}
+
+#[derive(FromRow, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schedule {
+    pub id: Uuid,
+    pub server_id: Uuid,
+    pub name: String,
+    pub cron: String,
+    pub timezone: String,
+    pub actions: Json<Vec<ScheduleAction>>,
+    pub only_when_players_online: bool,
+    pub enabled: bool,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_result: Option<ScheduleResult>,
+    pub last_message: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The user-editable part of a schedule.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleBody {
+    pub name: String,
+    pub cron: String,
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    pub actions: Vec<ScheduleAction>,
+    #[serde(default)]
+    pub only_when_players_online: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Error)]
+pub enum ScheduleError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("Schedule not found")]
+    ScheduleNotFound,
+    #[error("Invalid cron expression: {0}")]
+    InvalidCron(String),
+    #[error("Unknown timezone: {0}")]
+    InvalidTimezone(String),
+    #[error("Invalid schedule: {0}")]
+    InvalidSchedule(&'static str),
+}
+
+response_codes!(ScheduleError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    ScheduleNotFound(NOT_FOUND),
+    InvalidCron(BAD_REQUEST),
+    InvalidTimezone(BAD_REQUEST),
+    InvalidSchedule(BAD_REQUEST),
+});
+
+impl ScheduleBody {
+    /// Checks the schedule and works out when it should first run.
+    fn validate(&self) -> Result<Option<DateTime<Utc>>, ScheduleError> {
+        if self.name.trim().is_empty() {
+            return Err(ScheduleError::InvalidSchedule("name cannot be empty"));
+        }
+        if self.actions.is_empty() {
+            return Err(ScheduleError::InvalidSchedule(
+                "a schedule needs at least one action",
+            ));
+        }
+        if self.actions.len() > MAX_ACTIONS {
+            return Err(ScheduleError::InvalidSchedule(
+                "a schedule can have at most 32 actions",
+            ));
+        }
+        for action in &self.actions {
+            match action {
+                ScheduleAction::Command(command) if command.trim().is_empty() => {
+                    return Err(ScheduleError::InvalidSchedule("commands cannot be empty"))
+                }
+                ScheduleAction::Command(command) if command.contains(['\n', '\r']) => {
+                    return Err(ScheduleError::InvalidSchedule(
+                        "commands must be a single line",
+                    ))
+                }
+                ScheduleAction::Wait(seconds) if *seconds > MAX_WAIT_SECONDS => {
+                    return Err(ScheduleError::InvalidSchedule(
+                        "waits can be at most an hour",
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        let next_run = schedule::next_run(&self.cron, &self.timezone, Utc::now())?;
+        Ok(next_run.filter(|_| self.enabled))
+    }
+}
+
+impl Schedule {
+    pub async fn from_id(id: Uuid, server_id: Uuid, pool: &PgPool) -> Option<Self> {
+        sqlx::query_as!(
+            Schedule,
+            r#"SELECT id, server_id, name, cron, timezone, actions AS "actions: _",
+                only_when_players_online, enabled, next_run_at, last_run_at,
+                last_result AS "last_result: _", last_message, created_at
+            FROM schedules WHERE id = $1 AND server_id = $2"#,
+            id,
+            server_id
+        )
+        .fetch_one(pool)
+        .await
+        .ok()
+    }
+
+    pub async fn get_all(server_id: Uuid, pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Schedule,
+            r#"SELECT id, server_id, name, cron, timezone, actions AS "actions: _",
+                only_when_players_online, enabled, next_run_at, last_run_at,
+                last_result AS "last_result: _", last_message, created_at
+            FROM schedules WHERE server_id = $1 ORDER BY created_at"#,
+            server_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn create(
+        server_id: Uuid,
+        body: ScheduleBody,
+        pool: &PgPool,
+    ) -> Result<Self, ScheduleError> {
+        let next_run_at = body.validate()?;
+        let schedule = sqlx::query_as!(
+            Schedule,
+            r#"INSERT INTO schedules
+                (server_id, name, cron, timezone, actions, only_when_players_online, enabled, next_run_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, server_id, name, cron, timezone, actions AS "actions: _",
+                only_when_players_online, enabled, next_run_at, last_run_at,
+                last_result AS "last_result: _", last_message, created_at"#,
+            server_id,
+            body.name,
+            body.cron,
+            body.timezone,
+            Json(&body.actions) as _,
+            body.only_when_players_online,
+            body.enabled,
+            next_run_at
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(schedule)
+    }
+
+    pub async fn update(self, body: ScheduleBody, pool: &PgPool) -> Result<Self, ScheduleError> {
+        let next_run_at = body.validate()?;
+        let schedule = sqlx::query_as!(
+            Schedule,
+            r#"UPDATE schedules SET
+                name = $2, cron = $3, timezone = $4, actions = $5,
+                only_when_players_online = $6, enabled = $7, next_run_at = $8
+            WHERE id = $1
+            RETURNING id, server_id, name, cron, timezone, actions AS "actions: _",
+                only_when_players_online, enabled, next_run_at, last_run_at,
+                last_result AS "last_result: _", last_message, created_at"#,
+            self.id,
+            body.name,
+            body.cron,
+            body.timezone,
+            Json(&body.actions) as _,
+            body.only_when_players_online,
+            body.enabled,
+            next_run_at
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(schedule)
+    }
+
+    pub async fn delete(self, pool: &PgPool) -> Result<(), ScheduleError> {
+        sqlx::query!("DELETE FROM schedules WHERE id = $1", self.id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Enabled schedules whose next run has come.
+    pub async fn due(pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Schedule,
+            r#"SELECT id, server_id, name, cron, timezone, actions AS "actions: _",
+                only_when_players_online, enabled, next_run_at, last_run_at,
+                last_result AS "last_result: _", last_message, created_at
+            FROM schedules WHERE enabled AND next_run_at <= NOW()"#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Moves the schedule on to its next run. Returns `false` if the schedule
+    /// was changed or claimed by someone else in the meantime.
+    pub async fn advance(
+        &self,
+        next_run_at: Option<DateTime<Utc>>,
+        pool: &PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE schedules SET next_run_at = $2 WHERE id = $1 AND next_run_at = $3",
+            self.id,
+            next_run_at,
+            self.next_run_at
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    pub async fn record(
+        &self,
+        ran_at: DateTime<Utc>,
+        result: ScheduleResult,
+        message: Option<String>,
+        pool: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE schedules SET last_run_at = $2, last_result = $3, last_message = $4
+            WHERE id = $1",
+            self.id,
+            ran_at,
+            result as _,
+            message
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use bollard::{
     container::{
         self, CreateContainerOptions, InspectContainerOptions, RemoveContainerOptions,
-        StartContainerOptions,
+        StartContainerOptions, StopContainerOptions, WaitContainerOptions,
     },
     image::CreateImageOptions,
     secret::{HostConfig, PortBinding},
@@ -11,17 +11,58 @@ use bollard::{
 use chrono::{DateTime, Utc};
 use futures::StreamExt as _;
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
-use std::{collections::HashMap, env};
+use sqlx::{types::Json, FromRow, PgExecutor, PgPool};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    sync::Arc,
+    time::Duration,
+};
 use thiserror::Error;
 use tokio::fs;
 use uuid::Uuid;
 
+// the container label holding the checksum of its startup settings
+const STARTUP_LABEL: &str = "waitress.startup";
+// the launch line of provision scripts written before startup templates
+const LEGACY_LAUNCH: &str = "exec java -Xmx1024M -Xms1024M -jar server.jar nogui";
+
 use crate::{
-    response_codes,
-    version::server::{ServerError, ServerJarInfo},
+    config::CONFIG,
+    console::Console,
+    db::{
+        backup::Backup,
+        crash::Crash,
+        quota::{Quota, QuotaExceeded, Usage},
+        startup::{StartupConfig, StartupError},
+    },
+    response_codes, sleep,
+    template::{self, ConfigFormat, Template, TemplateError},
+    volume, watcher,
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum ServerStatus {
+    /// The container is still being created.
+    Provisioning,
+    Running,
+    Stopped,
+    /// The container is gone, and the server is waiting to be recreated.
+    Missing,
+    /// Stopped for having no players, waitress is listening in its place.
+    Sleeping,
+    /// Restoring the server failed, see the status reason.
+    Errored,
+    /// Something is wrong with the server that needs a human to look at it,
+    /// like its volume having disappeared.
+    NeedsReview,
+    /// Its container and files are being removed, deleting it again finishes
+    /// the job if that failed.
+    Deleting,
+}
+
 #[derive(FromRow, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Server {
@@ -31,18 +72,25 @@ pub struct Server {
     pub port: i32,
     pub name: String,
     pub docker_image: String,
+    /// The container's state, as last seen by the watcher.
+    pub status: ServerStatus,
+    /// Whether the server should be running, as opposed to stopped on
+    /// purpose.
+    pub should_run: bool,
+    pub status_reason: Option<String>,
+    /// The address players can join through the proxy with.
+    pub hostname: Option<String>,
+    /// Whether the proxy forwards the player's address with a PROXY protocol
+    /// header. The server has to be set up to expect it.
+    pub proxy_protocol: bool,
+    /// The id of the template the server was created from.
+    pub template: String,
 }
 
 #[derive(Error, Debug)]
 pub enum ServerProvisionError {
     #[error("Docker error: {0}")]
     DockerError(#[from] bollard::errors::Error),
-    #[error("Mojang API error: {0}")]
-    VersionError(#[from] reqwest::Error),
-    #[error("Version not found")]
-    VersionNotFound,
-    #[error("Server error: {0}")]
-    ServerInfoError(#[from] ServerError),
     #[error("Filesystem error: {0}")]
     FilesystemError(#[from] std::io::Error),
     #[error("Failed to get path")]
@@ -51,17 +99,23 @@ pub enum ServerProvisionError {
     StartError(#[from] ServerStartError),
     #[error("Database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
+    #[error("The server's volume is missing")]
+    VolumeMissing,
+    #[error("Invalid startup settings: {0}")]
+    StartupError(#[from] StartupError),
+    #[error("The server's template no longer exists")]
+    TemplateNotFound,
 }
 
 response_codes!(ServerProvisionError {
     DockerError(INTERNAL_SERVER_ERROR),
-    VersionError(INTERNAL_SERVER_ERROR),
-    VersionNotFound(NOT_FOUND),
-    ServerInfoError(INTERNAL_SERVER_ERROR),
     FilesystemError(INTERNAL_SERVER_ERROR),
     PathError(INTERNAL_SERVER_ERROR),
     StartError(INTERNAL_SERVER_ERROR),
     DatabaseError(INTERNAL_SERVER_ERROR),
+    VolumeMissing(INTERNAL_SERVER_ERROR),
+    StartupError(INTERNAL_SERVER_ERROR),
+    TemplateNotFound(INTERNAL_SERVER_ERROR),
 });
 
 #[derive(Debug, Error)]
@@ -72,10 +126,10 @@ pub enum ServerCreationError {
     ProvisionError(#[from] ServerProvisionError),
     #[error("Port already allocated")]
     PortAlreadyAllocated,
-    #[error("Reqwest error: {0}")]
-    ReqwestError(#[from] reqwest::Error),
-    #[error("Server error: {0}")]
-    ServerError(#[from] ServerError),
+    #[error("Template error: {0}")]
+    TemplateError(#[from] TemplateError),
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(#[from] QuotaExceeded),
 }
 
 #[derive(Debug, Error)]
@@ -84,6 +138,8 @@ pub enum ServerDeletionError {
     DatabaseError(#[from] sqlx::Error),
     #[error("Docker error: {0}")]
     DockerError(#[from] bollard::errors::Error),
+    #[error("Failed to delete the volume: {0}")]
+    FilesystemError(#[from] std::io::Error),
     #[error("Server not found")]
     ServerNotFound,
 }
@@ -91,28 +147,86 @@ pub enum ServerDeletionError {
 response_codes!(ServerDeletionError {
     DatabaseError(INTERNAL_SERVER_ERROR),
     DockerError(INTERNAL_SERVER_ERROR),
+    FilesystemError(INTERNAL_SERVER_ERROR),
     ServerNotFound(NOT_FOUND),
 });
 
+#[derive(Debug, Error)]
+pub enum RoutingError {
+    #[error("Database error: {0}")]
+    DatabaseError(sqlx::Error),
+    #[error("Server not found")]
+    ServerNotFound,
+    #[error("Invalid hostname: {0:?}")]
+    InvalidHostname(String),
+    #[error("Another server already uses this hostname")]
+    HostnameTaken,
+    #[error("{0}")]
+    HostnameNotAllowed(String),
+}
+
+response_codes!(RoutingError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    ServerNotFound(NOT_FOUND),
+    InvalidHostname(BAD_REQUEST),
+    HostnameTaken(CONFLICT),
+    HostnameNotAllowed(FORBIDDEN),
+});
+
 #[derive(Debug, Error)]
 pub enum ServerStartError {
     #[error("Docker error: {0}")]
     DockerError(#[from] bollard::errors::Error),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("Invalid startup settings: {0}")]
+    StartupError(#[from] StartupError),
+    #[error("Failed to recreate the container: {0}")]
+    RecreateFailed(Box<ServerProvisionError>),
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(#[from] QuotaExceeded),
+}
+
+#[derive(Debug, Error)]
+pub enum ServerStopError {
+    #[error("Docker error: {0}")]
+    DockerError(#[from] bollard::errors::Error),
 }
 
 impl Server {
     pub async fn from_id(id: Uuid, pool: &PgPool) -> Option<Self> {
-        sqlx::query_as!(Server, "SELECT * FROM servers WHERE id = $1", id)
-            .fetch_one(pool)
-            .await
-            .ok()
+        sqlx::query_as!(
+            Server,
+            r#"SELECT id, created_at, owner, port, name, docker_image, status AS "status: _",
+                should_run, status_reason, hostname, proxy_protocol, template
+            FROM servers WHERE id = $1"#,
+            id
+        )
+        .fetch_one(pool)
+        .await
+        .ok()
+    }
+
+    /// The server players are joining when they connect with `hostname`.
+    pub async fn from_hostname(hostname: &str, pool: &PgPool) -> Option<Self> {
+        sqlx::query_as!(
+            Server,
+            r#"SELECT id, created_at, owner, port, name, docker_image, status AS "status: _",
+                should_run, status_reason, hostname, proxy_protocol, template
+            FROM servers WHERE hostname = $1"#,
+            hostname
+        )
+        .fetch_one(pool)
+        .await
+        .ok()
     }
 
     pub async fn create(
         owner: Uuid,
         name: String,
         port: u16,
-        version: impl Into<String>,
+        template: &Template,
+        variables: BTreeMap<String, String>,
         pool: &PgPool,
     ) -> Result<Self, ServerCreationError> {
         // see if a server with this port allocated already exists
@@ -127,24 +241,42 @@ impl Server {
             return Err(ServerCreationError::PortAlreadyAllocated);
         }
 
-        let manifest = crate::version::manifest::VersionManifest::new().await?;
-        let version = manifest
-            .get_version(version.into())
-            .ok_or(ServerProvisionError::VersionNotFound)?;
-        let server_info = version.get_server_info().await?;
+        // the template's variables become the server's environment
+        let startup = StartupConfig {
+            command: template.startup.clone(),
+            env: Json(variables),
+            ..Default::default()
+        };
+        let extra = Usage {
+            servers: 1,
+            memory_mb: startup.memory_mb.into(),
+            ..Default::default()
+        };
+        let image = template.image(&startup.env)?;
 
+        let mut tx = pool.begin().await?;
+        Quota::check_locked::<ServerCreationError>(owner, extra, &mut tx).await?;
         let server = sqlx::query_as!(
             Server,
-            "INSERT INTO servers (owner, name, port, docker_image) VALUES ($1, $2, $3, $4) RETURNING *",
+            r#"INSERT INTO servers (owner, name, port, docker_image, status, template)
+            VALUES ($1, $2, $3, $4, 'provisioning', $5)
+            RETURNING id, created_at, owner, port, name, docker_image, status AS "status: _",
+                should_run, status_reason, hostname, proxy_protocol, template"#,
             owner,
             name,
             port as i32,
-            format!("openjdk:{}", server_info.java_version)
+            image,
+            template.id
         )
-        .fetch_one(pool)
+        .fetch_one(&mut *tx)
         .await?;
+        startup
+            .save(&server, &mut tx)
+            .await
+            .map_err(ServerProvisionError::from)?;
+        tx.commit().await?;
 
-        if let Err(e) = server.provision(&server_info, port).await {
+        if let Err(e) = server.provision(&startup).await {
             sqlx::query!("DELETE FROM servers WHERE id = $1", server.id)
                 .execute(pool)
                 .await?;
@@ -154,17 +286,10 @@ impl Server {
         Ok(server)
     }
 
-    async fn provision(
-        &self,
-        server_info: &ServerJarInfo,
-        port: u16,
-    ) -> Result<(), ServerProvisionError> {
+    async fn provision(&self, startup: &StartupConfig) -> Result<(), ServerProvisionError> {
         let docker = Docker::connect_with_local_defaults()?;
 
-        match self
-            .create_container(&docker, Some(&server_info), port)
-            .await
-        {
+        match self.create_container(&docker, true, startup).await {
             Ok(_) => Ok(()),
             Err(e) => {
                 log::error!("failed to provision server: {}", e);
@@ -173,17 +298,18 @@ impl Server {
         }
     }
 
+    /// Creates the server's container. `install` is set for new servers, to
+    /// create the volume and set up the template's install script.
     async fn create_container(
         &self,
         docker: &Docker,
-        server_info: Option<&crate::version::server::ServerJarInfo>,
-        port: u16,
+        install: bool,
+        startup: &StartupConfig,
     ) -> Result<(), ServerProvisionError> {
-        let image = if let Some(server_info) = server_info {
-            format!("openjdk:{}", server_info.java_version)
-        } else {
-            self.docker_image.clone()
-        };
+        let template = self
+            .template()
+            .ok_or(ServerProvisionError::TemplateNotFound)?;
+        let image = self.docker_image.clone();
 
         let create_image_options = CreateImageOptions {
             from_image: image.as_str(),
@@ -212,7 +338,7 @@ impl Server {
             fs::create_dir(volume_path).await?;
         }
 
-        if let Some(server_info) = server_info {
+        if install {
             let volume_create = CreateVolumeOptions {
                 name: &container_name,
                 driver: &"local".to_string(),
@@ -224,9 +350,23 @@ impl Server {
 
             let script = include_str!("../../provision_docker.sh").replace("\r\n", "\n");
 
-            let script = format!("JAR_URL={}\n{}", server_info.url, script);
-
-            fs::write(format!("{}/provision.sh", volume_path), script).await?;
+            let path = format!("{}/provision.sh", volume_path);
+            volume::write(volume_path, path, script.as_bytes()).await?;
+            if !template.install.is_empty() {
+                let install = template.install.replace("\r\n", "\n");
+                let path = format!("{}/install.sh", volume_path);
+                volume::write(volume_path, path, install.as_bytes()).await?;
+            }
+        } else {
+            // older scripts launch the server themselves, point them at the
+            // startup command instead
+            let path = format!("{}/provision.sh", volume_path);
+            if let Ok(script) = volume::read_to_string(volume_path, &path).await {
+                if script.contains(LEGACY_LAUNCH) {
+                    let script = script.replace(LEGACY_LAUNCH, "exec sh -c \"$STARTUP\"");
+                    volume::write(volume_path, &path, script.as_bytes()).await?;
+                }
+            }
         }
 
         let cmd = vec!["sh", "-c", "cd /data && sh provision.sh"]
@@ -249,16 +389,24 @@ impl Server {
 
         let host_config = HostConfig {
             binds: Some(vec![format!("{}/:/data", abs_path)]),
+            // no swap on top of the limit
+            memory: Some(startup.memory_limit()),
+            memory_swap: Some(startup.memory_limit()),
             // todo: figure this out
             port_bindings: Some({
                 let mut map = HashMap::new();
-                map.insert(
-                    "25565/tcp".to_string(),
-                    Some(vec![PortBinding {
-                        host_ip: Some("127.0.0.1".to_string()),
-                        host_port: Some(port.to_string()),
-                    }]),
-                );
+                for (i, port) in template.ports.iter().enumerate() {
+                    // the game port gets the port allocated to the server,
+                    // docker picks the rest
+                    let host_port = (i == 0).then(|| self.port.to_string());
+                    map.insert(
+                        port.docker_name(),
+                        Some(vec![PortBinding {
+                            host_ip: Some("127.0.0.1".to_string()),
+                            host_port,
+                        }]),
+                    );
+                }
                 map
             }),
             ..Default::default()
@@ -269,16 +417,23 @@ impl Server {
         let container_config = container::Config {
             image: Some(image),
             cmd: Some(cmd),
+            env: Some(startup.container_env()?),
+            labels: Some(HashMap::from([(
+                STARTUP_LABEL.to_string(),
+                startup.checksum()?,
+            )])),
             volumes: Some({
                 let mut map = HashMap::new();
                 map.insert("/data".to_string(), HashMap::new());
                 map
             }),
-            exposed_ports: Some({
-                let mut map = HashMap::new();
-                map.insert("25565/tcp".to_string(), HashMap::new());
-                map
-            }),
+            exposed_ports: Some(
+                template
+                    .ports
+                    .iter()
+                    .map(|port| (port.docker_name(), HashMap::new()))
+                    .collect(),
+            ),
             host_config: Some(host_config),
             open_stdin: Some(true),
             ..Default::default()
@@ -294,15 +449,23 @@ impl Server {
             )
             .await?;
 
-        self.start().await?;
-
         Ok(())
     }
 
+    /// Deletes the server's container and volume before its row, so that a
+    /// failed deletion can be tried again.
     pub async fn delete(self, pool: &PgPool) -> Result<(), ServerDeletionError> {
-        sqlx::query!("DELETE FROM servers WHERE id = $1", self.id)
-            .execute(pool)
-            .await?;
+        // the watcher would otherwise see the container go as a crash, and
+        // recreate it while its volume is being removed
+        sqlx::query!(
+            "UPDATE servers SET should_run = false, status = 'deleting', status_reason = NULL
+            WHERE id = $1",
+            self.id
+        )
+        .execute(pool)
+        .await?;
+        watcher::expect_stop(self.id);
+        sleep::release(self.id).await;
         // delete the docker container if it exists
         let docker = Docker::connect_with_local_defaults()?;
         let container_name = self.container_name();
@@ -311,10 +474,33 @@ impl Server {
             .await
         {
             Self::force_remove(self.id).await?;
-            // delete the volume
-            docker
-                .remove_volume(&container_name, Some(RemoveVolumeOptions { force: true }))
-                .await?;
+        }
+        // the volume might be gone already from an earlier try
+        match docker
+            .remove_volume(&container_name, Some(RemoveVolumeOptions { force: true }))
+            .await
+        {
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => {}
+            result => result?,
+        }
+        match fs::remove_dir_all(self.volume_path()).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        sqlx::query!("DELETE FROM servers WHERE id = $1", self.id)
+            .execute(pool)
+            .await?;
+        // the backup rows are gone with the server, so the archives are too
+        if let Err(e) = Backup::delete_all(self.id).await {
+            log::error!("failed to delete backups of {}: {}", self.id, e);
+        }
+        match fs::remove_dir_all(Crash::server_dir(self.id)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                log::error!("failed to delete crash reports of {}: {}", self.id, e)
+            }
+            _ => {}
         }
         Ok(())
     }
@@ -337,10 +523,163 @@ impl Server {
         Ok(())
     }
 
-    pub async fn get_all(owner: Uuid, pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
-        sqlx::query_as!(Server, "SELECT * FROM servers WHERE owner = $1", owner)
-            .fetch_all(pool)
-            .await
+    pub async fn get_all<'e>(
+        owner: Uuid,
+        executor: impl PgExecutor<'e>,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Server,
+            r#"SELECT id, created_at, owner, port, name, docker_image, status AS "status: _",
+                should_run, status_reason, hostname, proxy_protocol, template
+            FROM servers WHERE owner = $1"#,
+            owner
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// The servers a user owns or is a member of.
+    pub async fn accessible(user_id: Uuid, pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Server,
+            r#"SELECT id, created_at, owner, port, name, docker_image, status AS "status: _",
+                should_run, status_reason, hostname, proxy_protocol, template
+            FROM servers
+            WHERE owner = $1
+                OR id IN (SELECT server_id FROM server_members WHERE user_id = $1)"#,
+            user_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Every server, regardless of owner.
+    pub async fn all(pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Server,
+            r#"SELECT id, created_at, owner, port, name, docker_image, status AS "status: _",
+                should_run, status_reason, hostname, proxy_protocol, template
+            FROM servers"#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Records the container's state, unless the server is being deleted.
+    pub async fn set_status(
+        id: Uuid,
+        status: ServerStatus,
+        pool: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE servers SET status = $2, status_reason = NULL
+            WHERE id = $1 AND status <> 'deleting'",
+            id,
+            status as _
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Sets how the proxy routes players to this server. Hostnames are
+    /// lowercased, since that's how the proxy looks them up. Only admins can
+    /// use hostnames outside of `PROXY_DOMAIN`, so nobody can claim a domain
+    /// that isn't theirs before its owner does.
+    pub async fn set_routing(
+        &mut self,
+        hostname: Option<String>,
+        proxy_protocol: bool,
+        is_admin: bool,
+        pool: &PgPool,
+    ) -> Result<(), RoutingError> {
+        let hostname = hostname.map(|h| h.trim().trim_end_matches('.').to_lowercase());
+        if let Some(hostname) = &hostname {
+            if !is_valid_hostname(hostname) {
+                return Err(RoutingError::InvalidHostname(hostname.clone()));
+            }
+            // keeping a hostname an admin gave the server is fine
+            if !is_admin && self.hostname.as_ref() != Some(hostname) {
+                match &CONFIG.proxy_domain {
+                    Some(domain) if hostname.ends_with(&format!(".{}", domain)) => {}
+                    Some(domain) => {
+                        return Err(RoutingError::HostnameNotAllowed(format!(
+                            "The hostname has to end with .{}",
+                            domain
+                        )))
+                    }
+                    None => {
+                        return Err(RoutingError::HostnameNotAllowed(
+                            "Only admins can set hostnames".to_string(),
+                        ))
+                    }
+                }
+            }
+        }
+
+        sqlx::query!(
+            "UPDATE servers SET hostname = $2, proxy_protocol = $3 WHERE id = $1",
+            self.id,
+            hostname,
+            proxy_protocol
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.is_unique_violation() => RoutingError::HostnameTaken,
+            e => RoutingError::DatabaseError(e),
+        })?;
+
+        self.hostname = hostname;
+        self.proxy_protocol = proxy_protocol;
+        Ok(())
+    }
+
+    /// Records that the container stopped. Sleeping servers stay asleep.
+    pub async fn mark_stopped(id: Uuid, pool: &PgPool) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE servers SET status = 'stopped', status_reason = NULL
+            WHERE id = $1 AND status NOT IN ('sleeping', 'deleting')",
+            id
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Sets a status that needs explaining, like `Errored`.
+    pub async fn flag(
+        id: Uuid,
+        status: ServerStatus,
+        reason: String,
+        pool: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE servers SET status = $2, status_reason = $3 WHERE id = $1",
+            id,
+            status as _,
+            reason
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Records whether the server was started or stopped on purpose. Stops
+    /// that are undone right away, like around a restore, leave this alone.
+    pub async fn set_should_run(
+        id: Uuid,
+        should_run: bool,
+        pool: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE servers SET should_run = $2 WHERE id = $1",
+            id,
+            should_run
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
     }
 
     pub async fn restore_container(
@@ -354,50 +693,55 @@ impl Server {
 
         let container_name = self.container_name();
 
-        if !fs::metadata(self.volume_path()).await.is_ok() {
-            log::warn!("no volume for {}, giving up on it :(", container_name);
-
-            // delete the server from the database
-            sqlx::query!("DELETE FROM servers WHERE id = $1", self.id)
-                .execute(pool)
-                .await?;
-
-            docker
-                .remove_container(
-                    &container_name,
-                    Some(RemoveContainerOptions {
-                        force: true,
-                        ..Default::default()
-                    }),
-                )
-                .await
-                .ok();
-
-            return Ok(());
+        if fs::metadata(self.volume_path()).await.is_err() {
+            return Err(ServerProvisionError::VolumeMissing);
         }
 
-        if let Ok(_) = docker
+        if docker
             .inspect_container(&container_name, None::<InspectContainerOptions>)
             .await
+            .is_ok()
         {
             log::info!("container {} already exists", container_name);
-            if let Err(e) = self.start().await {
-                log::error!("failed to start container {}: {}", container_name, e);
+            if self.should_run {
+                self.start(pool).await?;
             }
             return Ok(()); // container already exists
         }
 
         log::info!("restoring container {}", container_name);
 
-        self.create_container(docker, None, self.port as u16)
-            .await?;
-        self.start().await?;
+        let startup = StartupConfig::get(self.id, pool).await?;
+        self.create_container(docker, false, &startup).await?;
+        self.start(pool).await?;
 
         Ok(())
     }
 
-    pub async fn start(&self) -> Result<(), ServerStartError> {
+    /// Restores the container, flagging the server instead if that fails.
+    /// Servers are never deleted automatically, it's up to an admin to decide
+    /// what to do with them.
+    pub async fn restore_or_flag(&self, docker: &Docker, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let Err(e) = self.restore_container(docker, pool).await else {
+            return Ok(());
+        };
+        log::error!("failed to restore {}: {}", self.container_name(), e);
+        let status = match e {
+            ServerProvisionError::VolumeMissing => ServerStatus::NeedsReview,
+            _ => ServerStatus::Errored,
+        };
+        Self::flag(self.id, status, e.to_string(), pool).await
+    }
+
+    /// Starts the container, recreating it first if the startup settings
+    /// changed since it was created.
+    pub async fn start(&self, pool: &PgPool) -> Result<(), ServerStartError> {
+        // volumes grow on their own, see `watcher::disk` for running servers
+        Quota::check_disk::<ServerStartError>(self.owner, pool).await?;
+        // the container can't bind the port while waitress holds it
+        sleep::release(self.id).await;
         let docker = Docker::connect_with_local_defaults()?;
+        self.apply_startup(&docker, pool).await?;
         docker
             .start_container(
                 &self.container_name(),
@@ -407,7 +751,116 @@ impl Server {
         Ok(())
     }
 
+    // environment variables and the command can only be set when a container
+    // is created, the volume keeps everything else
+    async fn apply_startup(&self, docker: &Docker, pool: &PgPool) -> Result<(), ServerStartError> {
+        let startup = StartupConfig::get(self.id, pool).await?;
+        let checksum = startup.checksum()?;
+        let container = docker
+            .inspect_container(&self.container_name(), None::<InspectContainerOptions>)
+            .await?;
+        let current = container
+            .config
+            .and_then(|c| c.labels)
+            .and_then(|labels| labels.get(STARTUP_LABEL).cloned());
+        if current.as_deref() == Some(checksum.as_str()) {
+            return Ok(());
+        }
+
+        log::info!(
+            "recreating {} to apply its startup settings",
+            self.container_name()
+        );
+        docker
+            .remove_container(
+                &self.container_name(),
+                Some(RemoveContainerOptions {
+                    force: true,
+                    ..Default::default()
+                }),
+            )
+            .await?;
+        self.create_container(docker, false, &startup)
+            .await
+            .map_err(|e| ServerStartError::RecreateFailed(Box::new(e)))
+    }
+
+    /// Asks the server to stop with its template's stop command, killing the
+    /// container if it doesn't exit in time.
+    pub async fn stop(&self) -> Result<(), ServerStopError> {
+        const STOP_TIMEOUT: Duration = Duration::from_secs(60);
+
+        // a crashed server might be waiting to be restarted
+        watcher::stop_requested(self.id);
+        if !self.is_running().await? {
+            return Ok(());
+        }
+
+        let docker = Docker::connect_with_local_defaults()?;
+        let container_name = self.container_name();
+        watcher::expect_stop(self.id);
+        let Some(command) = self.template().and_then(|t| t.stop.clone()) else {
+            docker
+                .stop_container(
+                    &container_name,
+                    Some(StopContainerOptions {
+                        t: STOP_TIMEOUT.as_secs() as i64,
+                    }),
+                )
+                .await?;
+            return Ok(());
+        };
+        if let Ok(console) = Console::get(self.id).await {
+            console.send(command).await.ok();
+        }
+
+        let mut wait = docker.wait_container(&container_name, None::<WaitContainerOptions<String>>);
+        if tokio::time::timeout(STOP_TIMEOUT, wait.next())
+            .await
+            .is_err()
+        {
+            log::warn!("{} did not stop in time, killing it", container_name);
+            docker
+                .stop_container(&container_name, Some(StopContainerOptions { t: 10 }))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn is_running(&self) -> Result<bool, bollard::errors::Error> {
+        let docker = Docker::connect_with_local_defaults()?;
+        let container = docker
+            .inspect_container(&self.container_name(), None::<InspectContainerOptions>)
+            .await?;
+        Ok(container.state.and_then(|s| s.running).unwrap_or(false))
+    }
+
     pub fn volume_path(&self) -> String {
         format!("volumes/{}", self.container_name())
     }
+
+    pub fn template(&self) -> Option<Arc<Template>> {
+        template::get(&self.template)
+    }
+
+    /// Where the server's properties file is, if its template has one.
+    pub fn properties_path(&self) -> Option<String> {
+        let template = self.template()?;
+        let file = template.file(ConfigFormat::Properties)?;
+        Some(format!("{}/{}", self.volume_path(), file.path))
+    }
+}
+
+fn is_valid_hostname(hostname: &str) -> bool {
+    hostname.len() <= 253
+        && hostname.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        })
 }
@@ -0,0 +1,240 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool};
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::user::{AuthDecodeError, User};
+use crate::{config::CONFIG, response_codes};
+
+const ACCESS_TOKEN_LIFETIME: Duration = Duration::minutes(15);
+/// Sessions that aren't refreshed for this long expire.
+const SESSION_LIFETIME: Duration = Duration::days(30);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    sub: Uuid,
+    /// The session the token belongs to, it stops working once that's revoked.
+    sid: Uuid,
+    exp: usize,
+}
+
+#[derive(FromRow, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub refreshed_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+/// A short-lived access token for the `Authorization` header, and the
+/// refresh token to get a new one with.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tokens {
+    pub token: String,
+    pub refresh_token: String,
+    /// When the access token expires.
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("Failed to create auth token: {0}")]
+    JWTError(#[from] jsonwebtoken::errors::Error),
+    #[error("Invalid or expired refresh token")]
+    InvalidRefreshToken,
+    #[error("Session not found")]
+    SessionNotFound,
+}
+
+response_codes!(SessionError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    JWTError(INTERNAL_SERVER_ERROR),
+    InvalidRefreshToken(UNAUTHORIZED),
+    SessionNotFound(NOT_FOUND),
+});
+
+impl Session {
+    /// Logs the user in on a new device.
+    pub async fn create(
+        user: &User,
+        user_agent: Option<String>,
+        ip: Option<String>,
+        pool: &PgPool,
+    ) -> Result<(Self, Tokens), SessionError> {
+        sqlx::query!(
+            "DELETE FROM sessions WHERE user_id = $1 AND expires_at < NOW()",
+            user.id
+        )
+        .execute(pool)
+        .await?;
+
+        let refresh_token = refresh_token();
+        let session = sqlx::query_as!(
+            Session,
+            "INSERT INTO sessions (user_id, refresh_hash, expires_at, user_agent, ip)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, user_id, created_at, refreshed_at, expires_at, user_agent, ip",
+            user.id,
+            hash(&refresh_token),
+            Utc::now() + SESSION_LIFETIME,
+            user_agent,
+            ip
+        )
+        .fetch_one(pool)
+        .await?;
+        let tokens = session.tokens(refresh_token)?;
+        Ok((session, tokens))
+    }
+
+    /// Swaps a refresh token for a new access token and refresh token. Each
+    /// refresh token only works once, and using one a second time revokes the
+    /// session, since someone else must have it.
+    pub async fn refresh(
+        refresh_token: &str,
+        pool: &PgPool,
+    ) -> Result<(Self, Tokens), SessionError> {
+        let old_hash = hash(refresh_token);
+        let refresh_token = self::refresh_token();
+        let session = sqlx::query_as!(
+            Session,
+            "UPDATE sessions
+            SET refresh_hash = $2, previous_hash = refresh_hash, refreshed_at = NOW(), expires_at = $3
+            WHERE refresh_hash = $1 AND expires_at > NOW()
+            RETURNING id, user_id, created_at, refreshed_at, expires_at, user_agent, ip",
+            old_hash,
+            hash(&refresh_token),
+            Utc::now() + SESSION_LIFETIME
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        let Some(session) = session else {
+            let reused = sqlx::query!("DELETE FROM sessions WHERE previous_hash = $1", old_hash)
+                .execute(pool)
+                .await?;
+            if reused.rows_affected() > 0 {
+                log::warn!("a refresh token was used twice, revoked its session");
+            }
+            return Err(SessionError::InvalidRefreshToken);
+        };
+        let tokens = session.tokens(refresh_token)?;
+        Ok((session, tokens))
+    }
+
+    /// Checks an access token, and that its session hasn't been revoked.
+    pub async fn from_token(token: &str, pool: &PgPool) -> Result<(Self, User), AuthDecodeError> {
+        let claims: Claims = jsonwebtoken::decode(
+            token,
+            &jsonwebtoken::DecodingKey::from_secret(&CONFIG.jwt_secret),
+            &jsonwebtoken::Validation::default(),
+        )
+        .map_err(AuthDecodeError::JWTError)?
+        .claims;
+
+        let session = sqlx::query_as!(
+            Session,
+            "SELECT id, user_id, created_at, refreshed_at, expires_at, user_agent, ip
+            FROM sessions WHERE id = $1 AND user_id = $2 AND expires_at > NOW()",
+            claims.sid,
+            claims.sub
+        )
+        .fetch_optional(pool)
+        .await
+        .ok()
+        .flatten()
+        .ok_or(AuthDecodeError::InvalidSession)?;
+        let user = User::from_id(claims.sub, pool)
+            .await
+            .ok_or(AuthDecodeError::NonexistentUser)?;
+        Ok((session, user))
+    }
+
+    pub async fn list(user_id: Uuid, pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Session,
+            "SELECT id, user_id, created_at, refreshed_at, expires_at, user_agent, ip
+            FROM sessions WHERE user_id = $1 AND expires_at > NOW()
+            ORDER BY refreshed_at DESC",
+            user_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn revoke(id: Uuid, user_id: Uuid, pool: &PgPool) -> Result<(), SessionError> {
+        let result = sqlx::query!(
+            "DELETE FROM sessions WHERE id = $1 AND user_id = $2",
+            id,
+            user_id
+        )
+        .execute(pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(SessionError::SessionNotFound);
+        }
+        Ok(())
+    }
+
+    /// Logs the user out everywhere.
+    pub async fn revoke_all(user_id: Uuid, pool: &PgPool) -> Result<(), sqlx::Error> {
+        sqlx::query!("DELETE FROM sessions WHERE user_id = $1", user_id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Logs the user out everywhere but the given session.
+    pub async fn revoke_others(
+        user_id: Uuid,
+        keep: Uuid,
+        pool: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM sessions WHERE user_id = $1 AND id <> $2",
+            user_id,
+            keep
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    fn tokens(&self, refresh_token: String) -> Result<Tokens, jsonwebtoken::errors::Error> {
+        let expires_at = Utc::now() + ACCESS_TOKEN_LIFETIME;
+        let token = jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &Claims {
+                sub: self.user_id,
+                sid: self.id,
+                exp: expires_at.timestamp() as usize,
+            },
+            &jsonwebtoken::EncodingKey::from_secret(&CONFIG.jwt_secret),
+        )?;
+        Ok(Tokens {
+            token,
+            refresh_token,
+            expires_at,
+        })
+    }
+}
+
+fn refresh_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// refresh tokens are random enough that a plain hash is fine
+fn hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
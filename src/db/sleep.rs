@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::server::ServerStopError;
+use crate::response_codes;
+
+pub const DEFAULT_MOTD: &str = "Sleeping, join to wake the server up";
+
+#[derive(FromRow, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepPolicy {
+    pub enabled: bool,
+    /// How long the server has to have no players before it's stopped.
+    pub idle_minutes: i32,
+    /// Shown in the server list while the server is asleep.
+    pub motd: Option<String>,
+}
+
+impl Default for SleepPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_minutes: 15,
+            motd: None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SleepError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("Server not found")]
+    ServerNotFound,
+    #[error("Invalid sleep policy: {0}")]
+    InvalidPolicy(&'static str),
+    #[error("Failed to stop server: {0}")]
+    StopError(#[from] ServerStopError),
+    #[error("Failed to listen on the server's port: {0}")]
+    ListenError(#[from] std::io::Error),
+}
+
+response_codes!(SleepError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    ServerNotFound(NOT_FOUND),
+    InvalidPolicy(BAD_REQUEST),
+    StopError(INTERNAL_SERVER_ERROR),
+    ListenError(INTERNAL_SERVER_ERROR),
+});
+
+impl SleepPolicy {
+    pub async fn get(server_id: Uuid, pool: &PgPool) -> Result<Self, sqlx::Error> {
+        let policy = sqlx::query_as!(
+            SleepPolicy,
+            "SELECT enabled, idle_minutes, motd FROM sleep_policies WHERE server_id = $1",
+            server_id
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(policy.unwrap_or_default())
+    }
+
+    pub async fn set(&self, server_id: Uuid, pool: &PgPool) -> Result<(), SleepError> {
+        if self.idle_minutes < 1 {
+            return Err(SleepError::InvalidPolicy(
+                "servers have to be idle for at least a minute",
+            ));
+        }
+        if matches!(&self.motd, Some(motd) if motd.chars().count() > 256) {
+            return Err(SleepError::InvalidPolicy(
+                "the motd must be at most 256 characters",
+            ));
+        }
+
+        sqlx::query!(
+            "INSERT INTO sleep_policies (server_id, enabled, idle_minutes, motd)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (server_id) DO UPDATE SET
+                enabled = EXCLUDED.enabled,
+                idle_minutes = EXCLUDED.idle_minutes,
+                motd = EXCLUDED.motd",
+            server_id,
+            self.enabled,
+            self.idle_minutes,
+            self.motd
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Running servers that can be put to sleep, with how long they need to
+    /// be idle for.
+    pub async fn enabled(pool: &PgPool) -> Result<Vec<(Uuid, i32)>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT p.server_id, p.idle_minutes FROM sleep_policies p
+            JOIN servers s ON s.id = p.server_id
+            WHERE p.enabled AND s.status = 'running'"
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.server_id, r.idle_minutes))
+            .collect())
+    }
+}
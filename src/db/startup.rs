@@ -0,0 +1,270 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{types::Json, FromRow, PgConnection, PgExecutor, PgPool};
+use std::collections::BTreeMap;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::{
+    quota::{Quota, QuotaExceeded, Usage},
+    server::Server,
+};
+use crate::response_codes;
+
+pub const DEFAULT_COMMAND: &str =
+    "java -Xmx{{MEMORY}} -Xms{{MEMORY}} {{JAVA_ARGS}} -jar {{JAR}} nogui";
+
+// set by waitress itself, so they can't be overridden
+const RESERVED_VARIABLES: &[&str] = &["STARTUP"];
+const MAX_VARIABLES: usize = 64;
+const MAX_LENGTH: usize = 4096;
+
+#[derive(FromRow, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupConfig {
+    /// The command that launches the server. `{{MEMORY}}` (like `1024M`),
+    /// `{{MEMORY_MB}}`, `{{JAR}}` and `{{JAVA_ARGS}}` are replaced with the
+    /// values below.
+    pub command: String,
+    pub memory_mb: i32,
+    pub jar: String,
+    pub java_args: String,
+    /// Extra environment variables passed to the container.
+    pub env: Json<BTreeMap<String, String>>,
+}
+
+impl Default for StartupConfig {
+    fn default() -> Self {
+        Self {
+            command: DEFAULT_COMMAND.to_string(),
+            memory_mb: 1024,
+            jar: "server.jar".to_string(),
+            java_args: String::new(),
+            env: Json(BTreeMap::new()),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum StartupError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("Server not found")]
+    ServerNotFound,
+    #[error("Invalid startup settings: {0}")]
+    InvalidStartup(&'static str),
+    #[error("Unknown variable in the startup command: {0}")]
+    UnknownVariable(String),
+    #[error("Invalid environment variable name: {0:?}")]
+    InvalidVariableName(String),
+    #[error("Invalid value for {0}: {1}")]
+    InvalidVariable(String, String),
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(#[from] QuotaExceeded),
+}
+
+response_codes!(StartupError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    ServerNotFound(NOT_FOUND),
+    InvalidStartup(BAD_REQUEST),
+    UnknownVariable(BAD_REQUEST),
+    InvalidVariableName(BAD_REQUEST),
+    InvalidVariable(BAD_REQUEST),
+    QuotaExceeded(FORBIDDEN),
+});
+
+impl StartupConfig {
+    /// Fills the variables into the command.
+    pub fn render(&self) -> Result<String, StartupError> {
+        let mut rendered = String::with_capacity(self.command.len());
+        let mut rest = self.command.as_str();
+        while let Some(start) = rest.find("{{") {
+            rendered.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find("}}")
+                .ok_or(StartupError::InvalidStartup("unclosed {{ in the command"))?;
+            let name = rest[start + 2..start + end].trim();
+            match name {
+                "MEMORY" => rendered.push_str(&format!("{}M", self.memory_mb)),
+                "MEMORY_MB" => rendered.push_str(&self.memory_mb.to_string()),
+                "JAR" => rendered.push_str(&self.jar),
+                "JAVA_ARGS" => rendered.push_str(&self.java_args),
+                _ => return Err(StartupError::UnknownVariable(name.to_string())),
+            }
+            rest = &rest[start + end + 2..];
+        }
+        rendered.push_str(rest);
+        Ok(rendered)
+    }
+
+    pub fn validate(&self) -> Result<(), StartupError> {
+        if self.command.trim().is_empty() {
+            return Err(StartupError::InvalidStartup("the command cannot be empty"));
+        }
+        if [&self.command, &self.jar, &self.java_args]
+            .iter()
+            .any(|s| s.len() > MAX_LENGTH || s.contains(['\n', '\r', '\0']))
+        {
+            return Err(StartupError::InvalidStartup(
+                "the command, jar and java arguments must be a single line of at most 4096 characters",
+            ));
+        }
+        if !(256..=1024 * 1024).contains(&self.memory_mb) {
+            return Err(StartupError::InvalidStartup(
+                "memory must be between 256MB and 1TB",
+            ));
+        }
+        // the jar lives in the server's volume
+        if self.jar.is_empty()
+            || self.jar.starts_with('/')
+            || self.jar.split('/').any(|part| part == "..")
+            || !self
+                .jar
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/'))
+        {
+            return Err(StartupError::InvalidStartup(
+                "the jar must be a relative path inside the server's folder",
+            ));
+        }
+
+        if self.env.len() > MAX_VARIABLES {
+            return Err(StartupError::InvalidStartup(
+                "a server can have at most 64 environment variables",
+            ));
+        }
+        for (name, value) in self.env.iter() {
+            if !is_valid_variable_name(name) || RESERVED_VARIABLES.contains(&name.as_str()) {
+                return Err(StartupError::InvalidVariableName(name.clone()));
+            }
+            if value.len() > MAX_LENGTH || value.contains('\0') {
+                return Err(StartupError::InvalidStartup(
+                    "environment variables must be at most 4096 characters",
+                ));
+            }
+        }
+
+        self.render()?;
+        Ok(())
+    }
+
+    /// The container's environment, with the rendered command in `STARTUP`
+    /// for the provision script to run.
+    pub fn container_env(&self) -> Result<Vec<String>, StartupError> {
+        let mut env = self
+            .env
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>();
+        env.push(format!("STARTUP={}", self.render()?));
+        Ok(env)
+    }
+
+    /// Identifies the settings a container was created with, so it can be
+    /// recreated when they change.
+    pub fn checksum(&self) -> Result<String, StartupError> {
+        let env = self.container_env()?.join("\0");
+        let settings = format!("{}\0{}", self.memory_limit(), env);
+        Ok(format!("{:x}", Sha256::digest(settings.as_bytes())))
+    }
+
+    /// The container's memory limit in bytes. The JVM uses more than its heap,
+    /// so there's some room on top of `memory_mb`, like other panels do.
+    pub fn memory_limit(&self) -> i64 {
+        let memory_mb = i64::from(self.memory_mb);
+        let overhead = if memory_mb < 2048 {
+            15
+        } else if memory_mb < 4096 {
+            10
+        } else {
+            5
+        };
+        (memory_mb + memory_mb * overhead / 100) * 1024 * 1024
+    }
+
+    pub async fn get<'e>(
+        server_id: Uuid,
+        executor: impl PgExecutor<'e>,
+    ) -> Result<Self, sqlx::Error> {
+        let config = sqlx::query_as!(
+            StartupConfig,
+            r#"SELECT command, memory_mb, jar, java_args, env AS "env: _"
+            FROM startup_configs WHERE server_id = $1"#,
+            server_id
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(config.unwrap_or_default())
+    }
+
+    /// Saves the settings, they're applied the next time the server starts.
+    pub async fn set(&self, server: &Server, pool: &PgPool) -> Result<(), StartupError> {
+        let mut tx = pool.begin().await?;
+        self.save(server, &mut tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Like `set`, in a transaction the quota stays locked in until it ends.
+    pub async fn save(&self, server: &Server, tx: &mut PgConnection) -> Result<(), StartupError> {
+        self.validate()?;
+        let current = Self::get(server.id, &mut *tx).await?;
+        // the template's rules also apply after the server is created, but
+        // values that were already there are left alone
+        if let Some(template) = server.template() {
+            for variable in &template.variables {
+                let Some(value) = self.env.get(&variable.name) else {
+                    // servers from before templates might not have it yet
+                    let required = variable.default.is_none();
+                    if required && current.env.contains_key(&variable.name) {
+                        return Err(StartupError::InvalidVariable(
+                            variable.name.clone(),
+                            "required".to_string(),
+                        ));
+                    }
+                    continue;
+                };
+                if current.env.get(&variable.name) == Some(value) {
+                    continue;
+                }
+                variable.rules.check(value).map_err(|reason| {
+                    StartupError::InvalidVariable(variable.name.clone(), reason)
+                })?;
+            }
+        }
+        let extra = Usage {
+            memory_mb: (self.memory_mb - current.memory_mb).into(),
+            ..Default::default()
+        };
+        Quota::check_locked::<StartupError>(server.owner, extra, tx).await?;
+
+        sqlx::query!(
+            "INSERT INTO startup_configs (server_id, command, memory_mb, jar, java_args, env)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (server_id) DO UPDATE SET
+                command = EXCLUDED.command,
+                memory_mb = EXCLUDED.memory_mb,
+                jar = EXCLUDED.jar,
+                java_args = EXCLUDED.java_args,
+                env = EXCLUDED.env",
+            server.id,
+            self.command,
+            self.memory_mb,
+            self.jar,
+            self.java_args,
+            &self.env as _
+        )
+        .execute(&mut *tx)
+        .await?;
+        Ok(())
+    }
+}
+
+fn is_valid_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    name.len() <= 128
+        && chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
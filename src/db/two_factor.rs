@@ -0,0 +1,332 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::{session::SessionError, user::User};
+use crate::{config::CONFIG, response_codes};
+
+const ISSUER: &str = "Waitress";
+/// Seconds per code.
+const PERIOD: i64 = 30;
+const DIGITS: u32 = 6;
+const RECOVERY_CODES: usize = 10;
+/// How long the second step of a login can take.
+const CHALLENGE_LIFETIME: Duration = Duration::minutes(5);
+
+/// What an authenticator app needs to be set up.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Enrollment {
+    /// Base32, for typing in by hand.
+    pub secret: String,
+    /// An `otpauth://` URI, usually shown as a QR code.
+    pub uri: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub recovery_codes_left: i64,
+}
+
+// handed out after the password is checked, and traded for a session along
+// with a code
+#[derive(Debug, Serialize, Deserialize)]
+struct ChallengeClaims {
+    sub: Uuid,
+    exp: usize,
+    two_factor: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum TwoFactorError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("Failed to create session: {0}")]
+    SessionError(#[from] SessionError),
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Two-factor authentication is already enabled")]
+    AlreadyEnabled,
+    #[error("Two-factor authentication isn't set up")]
+    NotEnrolled,
+    #[error("Invalid two-factor code")]
+    InvalidCode,
+    #[error("The login challenge is invalid or has expired, log in again")]
+    InvalidChallenge,
+}
+
+response_codes!(TwoFactorError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    SessionError(INTERNAL_SERVER_ERROR),
+    UserNotFound(NOT_FOUND),
+    AlreadyEnabled(CONFLICT),
+    NotEnrolled(BAD_REQUEST),
+    InvalidCode(UNAUTHORIZED),
+    InvalidChallenge(UNAUTHORIZED),
+});
+
+pub struct TwoFactor;
+
+impl TwoFactor {
+    pub async fn is_enabled(user_id: Uuid, pool: &PgPool) -> Result<bool, sqlx::Error> {
+        let enabled =
+            sqlx::query_scalar!("SELECT enabled FROM two_factor WHERE user_id = $1", user_id)
+                .fetch_optional(pool)
+                .await?;
+        Ok(enabled == Some(true))
+    }
+
+    pub async fn status(user_id: Uuid, pool: &PgPool) -> Result<TwoFactorStatus, sqlx::Error> {
+        let left = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM recovery_codes WHERE user_id = $1 AND used_at IS NULL",
+            user_id
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(TwoFactorStatus {
+            enabled: Self::is_enabled(user_id, pool).await?,
+            recovery_codes_left: left.unwrap_or(0),
+        })
+    }
+
+    /// Starts setting up 2FA with a new secret. It's only enabled once a code
+    /// from it is verified with `enable`.
+    pub async fn enroll(user: &User, pool: &PgPool) -> Result<Enrollment, TwoFactorError> {
+        if Self::is_enabled(user.id, pool).await? {
+            return Err(TwoFactorError::AlreadyEnabled);
+        }
+        let mut secret = [0u8; 20];
+        OsRng.fill_bytes(&mut secret);
+        sqlx::query!(
+            "INSERT INTO two_factor (user_id, secret) VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE SET secret = EXCLUDED.secret, last_step = NULL",
+            user.id,
+            hex(&secret)
+        )
+        .execute(pool)
+        .await?;
+
+        let secret = base32(&secret);
+        let uri = format!(
+            "otpauth://totp/{issuer}:{label}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={PERIOD}",
+            issuer = ISSUER,
+            label = percent_encode(&user.username),
+        );
+        Ok(Enrollment { secret, uri })
+    }
+
+    /// Turns 2FA on after checking a code, returning the recovery codes.
+    pub async fn enable(
+        user_id: Uuid,
+        code: &str,
+        pool: &PgPool,
+    ) -> Result<Vec<String>, TwoFactorError> {
+        if Self::is_enabled(user_id, pool).await? {
+            return Err(TwoFactorError::AlreadyEnabled);
+        }
+        if !Self::check_totp(user_id, code, pool).await? {
+            return Err(TwoFactorError::InvalidCode);
+        }
+        sqlx::query!(
+            "UPDATE two_factor SET enabled = TRUE WHERE user_id = $1",
+            user_id
+        )
+        .execute(pool)
+        .await?;
+        Ok(Self::new_recovery_codes(user_id, pool).await?)
+    }
+
+    /// Checks a code from the authenticator app, or uses up a recovery code.
+    pub async fn verify(user_id: Uuid, code: &str, pool: &PgPool) -> Result<(), TwoFactorError> {
+        if !Self::is_enabled(user_id, pool).await? {
+            return Err(TwoFactorError::NotEnrolled);
+        }
+        if Self::check_totp(user_id, code, pool).await? {
+            return Ok(());
+        }
+
+        let used = sqlx::query!(
+            "UPDATE recovery_codes SET used_at = NOW()
+            WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL",
+            user_id,
+            hash_recovery_code(code)
+        )
+        .execute(pool)
+        .await?;
+        if used.rows_affected() == 0 {
+            return Err(TwoFactorError::InvalidCode);
+        }
+        Ok(())
+    }
+
+    /// Turns 2FA off. For users who've lost their authenticator and recovery
+    /// codes, this is up to an admin.
+    pub async fn disable(user_id: Uuid, pool: &PgPool) -> Result<(), sqlx::Error> {
+        sqlx::query!("DELETE FROM two_factor WHERE user_id = $1", user_id)
+            .execute(pool)
+            .await?;
+        sqlx::query!("DELETE FROM recovery_codes WHERE user_id = $1", user_id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// A token for the second step of logging in, it can't be used for
+    /// anything else.
+    pub fn challenge(user_id: Uuid) -> Result<String, jsonwebtoken::errors::Error> {
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &ChallengeClaims {
+                sub: user_id,
+                exp: (Utc::now() + CHALLENGE_LIFETIME).timestamp() as usize,
+                two_factor: true,
+            },
+            &jsonwebtoken::EncodingKey::from_secret(&CONFIG.jwt_secret),
+        )
+    }
+
+    /// The user a login challenge was made for.
+    pub fn from_challenge(challenge: &str) -> Result<Uuid, TwoFactorError> {
+        let claims: ChallengeClaims = jsonwebtoken::decode(
+            challenge,
+            &jsonwebtoken::DecodingKey::from_secret(&CONFIG.jwt_secret),
+            &jsonwebtoken::Validation::default(),
+        )
+        .map_err(|_| TwoFactorError::InvalidChallenge)?
+        .claims;
+        if !claims.two_factor {
+            return Err(TwoFactorError::InvalidChallenge);
+        }
+        Ok(claims.sub)
+    }
+
+    // accepts the codes right before and after the current one for clock
+    // drift, but each only once
+    async fn check_totp(user_id: Uuid, code: &str, pool: &PgPool) -> Result<bool, sqlx::Error> {
+        let Some(secret) =
+            sqlx::query_scalar!("SELECT secret FROM two_factor WHERE user_id = $1", user_id)
+                .fetch_optional(pool)
+                .await?
+        else {
+            return Ok(false);
+        };
+        let Some(secret) = unhex(&secret) else {
+            return Ok(false);
+        };
+        let Ok(code) = code.trim().parse::<u32>() else {
+            return Ok(false);
+        };
+
+        let now = Utc::now().timestamp() / PERIOD;
+        let Some(step) = (now - 1..=now + 1).find(|step| totp(&secret, *step) == code) else {
+            return Ok(false);
+        };
+        let result = sqlx::query!(
+            "UPDATE two_factor SET last_step = $2
+            WHERE user_id = $1 AND (last_step IS NULL OR last_step < $2)",
+            user_id,
+            step
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn new_recovery_codes(user_id: Uuid, pool: &PgPool) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query!("DELETE FROM recovery_codes WHERE user_id = $1", user_id)
+            .execute(pool)
+            .await?;
+        let mut codes = Vec::with_capacity(RECOVERY_CODES);
+        for _ in 0..RECOVERY_CODES {
+            let mut bytes = [0u8; 8];
+            OsRng.fill_bytes(&mut bytes);
+            let code = hex(&bytes)
+                .as_bytes()
+                .chunks(4)
+                .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+                .collect::<Vec<_>>()
+                .join("-");
+            sqlx::query!(
+                "INSERT INTO recovery_codes (user_id, code_hash) VALUES ($1, $2)",
+                user_id,
+                hash_recovery_code(&code)
+            )
+            .execute(pool)
+            .await?;
+            codes.push(code);
+        }
+        Ok(codes)
+    }
+}
+
+/// The code for a time step, as described in RFC 6238.
+pub fn totp(secret: &[u8], step: i64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("hmac accepts any key length");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let code = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    code % 10u32.pow(DIGITS)
+}
+
+/// RFC 4648 base32 without padding, which authenticator apps expect.
+pub fn base32(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    for chunk in bytes.chunks(5) {
+        let mut buf = [0u8; 5];
+        buf[..chunk.len()].copy_from_slice(chunk);
+        let bits = u64::from_be_bytes([0, 0, 0, buf[0], buf[1], buf[2], buf[3], buf[4]]);
+        let chars = (chunk.len() * 8).div_ceil(5);
+        for i in 0..chars {
+            let index = (bits >> (35 - i * 5)) & 0x1f;
+            encoded.push(ALPHABET[index as usize] as char);
+        }
+    }
+    encoded
+}
+
+// recovery codes can be typed with or without dashes, in any case
+fn hash_recovery_code(code: &str) -> String {
+    let normalized = code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
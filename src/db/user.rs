@@ -5,11 +5,15 @@ use argon2::{
 use argon2::{PasswordHasher, PasswordVerifier};
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
+use sqlx::{Acquire, FromRow, PgConnection, PgPool, Postgres};
 use thiserror::Error;
 use uuid::Uuid;
 
-use crate::{config::CONFIG, response_codes};
+use super::{
+    server::{Server, ServerDeletionError},
+    session::Session,
+};
+use crate::response_codes;
 
 #[derive(Error, Debug)]
 pub enum UserCreationError {
@@ -21,6 +25,8 @@ pub enum UserCreationError {
     ThreadError,
     #[error("Signups are disabled")]
     SignupsDisabled,
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
 }
 
 response_codes!(UserCreationError {
@@ -28,12 +34,89 @@ response_codes!(UserCreationError {
     UserAlreadyExists(CONFLICT),
     ThreadError(INTERNAL_SERVER_ERROR),
     SignupsDisabled(FORBIDDEN),
+    DatabaseError(INTERNAL_SERVER_ERROR),
 });
 
+// how long after logging in a user without a password can set one
+const RECENT_LOGIN: Duration = Duration::minutes(10);
+// held while creating users, so only one of them can be the first
+const FIRST_USER_LOCK: i64 = 0x7573_6572;
+
 #[derive(Error, Debug)]
-pub enum AuthCreationError {
-    #[error("Failed to create auth token: {0}")]
-    JWTError(jsonwebtoken::errors::Error),
+pub enum UserManagementError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("User not found")]
+    UserNotFound,
+    #[error("There has to be at least one admin")]
+    LastAdmin,
+    #[error("Failed to delete the user's servers: {0}")]
+    ServerDeletionError(#[from] ServerDeletionError),
+}
+
+response_codes!(UserManagementError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    UserNotFound(NOT_FOUND),
+    LastAdmin(CONFLICT),
+    ServerDeletionError(INTERNAL_SERVER_ERROR),
+});
+
+#[derive(Error, Debug)]
+pub enum AccountError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("Failed to hash password: {0}")]
+    HashError(argon2::password_hash::Error),
+    #[error("Threading error")]
+    ThreadError,
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Wrong password")]
+    WrongPassword,
+    #[error("Log in again to set a password")]
+    LoginRequired,
+    #[error("That username is taken")]
+    UsernameTaken,
+    #[error("{0}")]
+    InvalidInput(&'static str),
+    #[error("There has to be at least one admin")]
+    LastAdmin,
+    #[error("Failed to delete the user's servers: {0}")]
+    ServerDeletionError(ServerDeletionError),
+}
+
+response_codes!(AccountError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    HashError(INTERNAL_SERVER_ERROR),
+    ThreadError(INTERNAL_SERVER_ERROR),
+    UserNotFound(NOT_FOUND),
+    WrongPassword(FORBIDDEN),
+    LoginRequired(FORBIDDEN),
+    UsernameTaken(CONFLICT),
+    InvalidInput(BAD_REQUEST),
+    LastAdmin(CONFLICT),
+    ServerDeletionError(INTERNAL_SERVER_ERROR),
+});
+
+impl From<UserCreationError> for AccountError {
+    fn from(e: UserCreationError) -> Self {
+        match e {
+            UserCreationError::HashError(e) => Self::HashError(e),
+            UserCreationError::DatabaseError(e) => Self::DatabaseError(e),
+            _ => Self::ThreadError,
+        }
+    }
+}
+
+impl From<UserManagementError> for AccountError {
+    fn from(e: UserManagementError) -> Self {
+        match e {
+            UserManagementError::DatabaseError(e) => Self::DatabaseError(e),
+            UserManagementError::UserNotFound => Self::UserNotFound,
+            UserManagementError::LastAdmin => Self::LastAdmin,
+            UserManagementError::ServerDeletionError(e) => Self::ServerDeletionError(e),
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -42,12 +125,19 @@ pub enum AuthDecodeError {
     JWTError(jsonwebtoken::errors::Error),
     #[error("User does not exist")]
     NonexistentUser,
+    #[error("The session was revoked or has expired")]
+    InvalidSession,
+    #[error("Invalid or expired API key")]
+    InvalidApiKey,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Claims {
-    sub: Uuid,
-    exp: usize,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum UserRole {
+    /// Can see and manage every user and server.
+    Admin,
+    User,
 }
 
 #[derive(FromRow, PartialEq, Debug, Serialize, Deserialize)]
@@ -56,13 +146,14 @@ pub struct User {
     pub id: Uuid,
     pub username: String,
     pub created_at: DateTime<Utc>,
+    pub role: UserRole,
 }
 
 impl User {
     pub async fn from_id(id: Uuid, pool: &PgPool) -> Option<Self> {
         sqlx::query_as!(
             User,
-            "SELECT id, username, created_at FROM users WHERE id = $1",
+            r#"SELECT id, username, created_at, role AS "role: _" FROM users WHERE id = $1"#,
             id
         )
         .fetch_one(pool)
@@ -70,40 +161,55 @@ impl User {
         .ok()
     }
 
-    pub async fn create(
+    pub async fn create<'a>(
         username: impl Into<String>,
         password: impl Into<String>,
-        pool: &PgPool,
+        conn: impl Acquire<'a, Database = Postgres>,
     ) -> Result<Self, UserCreationError> {
         let username: String = username.into();
-        let password: String = password.into();
-        let hash = tokio::task::spawn_blocking(move || {
-            let hasher = Argon2::default();
-            let salt = SaltString::generate(&mut OsRng);
-            let hash = hasher
-                .hash_password(password.as_bytes(), &salt)
-                .map_err(UserCreationError::HashError)?;
-            let hash = hash.to_string();
-            Ok(hash)
-        })
-        .await
-        .map_err(|_| UserCreationError::ThreadError)??;
+        let hash = hash_password(password.into()).await?;
 
+        let mut tx = conn.begin().await?;
+        sqlx::query!("SELECT pg_advisory_xact_lock($1)", FIRST_USER_LOCK)
+            .execute(&mut *tx)
+            .await?;
+        // the first user is the admin
         let user = sqlx::query_as!(
             User,
-            "INSERT INTO users (username, password) VALUES ($1, $2) RETURNING id, username, created_at",
+            r#"INSERT INTO users (username, password, role)
+            VALUES ($1, $2, CASE WHEN EXISTS(SELECT 1 FROM users) THEN 'user' ELSE 'admin' END)
+            RETURNING id, username, created_at, role AS "role: _""#,
             username,
-            hash.to_string()
+            hash
         )
-        .fetch_one(pool)
-        .await.map_err(|_| UserCreationError::UserAlreadyExists)?;
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| UserCreationError::UserAlreadyExists)?;
+        tx.commit().await?;
 
         Ok(user)
     }
 
+    /// Creates a user without a password, who logs in through an OIDC
+    /// provider. Unlike with signups, the first one isn't made an admin,
+    /// since anyone the provider knows could be first.
+    pub async fn create_external(
+        username: impl Into<String>,
+        pool: &PgPool,
+    ) -> Result<Self, UserCreationError> {
+        sqlx::query_as!(
+            User,
+            r#"INSERT INTO users (username, role) VALUES ($1, 'user')
+            RETURNING id, username, created_at, role AS "role: _""#,
+            username.into()
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|_| UserCreationError::UserAlreadyExists)
+    }
+
     pub async fn verify_password(&self, password: impl Into<String>, pool: &PgPool) -> bool {
         let password_to_verify = password.into();
-        let hasher = Argon2::default();
         let Ok(true_password) = sqlx::query!("SELECT password FROM users WHERE id = $1", self.id)
             .fetch_one(pool)
             .await
@@ -111,53 +217,215 @@ impl User {
             return false;
         };
 
-        let Ok(hash) = PasswordHash::new(&true_password.password) else {
+        // users from an OIDC provider don't have one
+        let Some(true_password) = true_password.password else {
             return false;
         };
 
-        hasher
-            .verify_password(password_to_verify.as_bytes(), &hash)
-            .is_ok()
+        // argon2 is slow on purpose, so keep it off the async threads
+        tokio::task::spawn_blocking(move || {
+            PasswordHash::new(&true_password).is_ok_and(|hash| {
+                Argon2::default()
+                    .verify_password(password_to_verify.as_bytes(), &hash)
+                    .is_ok()
+            })
+        })
+        .await
+        .unwrap_or(false)
     }
 
-    pub async fn create_token(&self) -> Result<String, AuthCreationError> {
-        jsonwebtoken::encode(
-            &jsonwebtoken::Header::default(),
-            &Claims {
-                sub: self.id,
-                exp: (Utc::now() + Duration::days(365)).timestamp() as usize,
-            },
-            &jsonwebtoken::EncodingKey::from_secret(&CONFIG.jwt_secret),
+    /// Users from an OIDC provider might not have one.
+    pub async fn has_password(&self, pool: &PgPool) -> Result<bool, sqlx::Error> {
+        let password = sqlx::query_scalar!("SELECT password FROM users WHERE id = $1", self.id)
+            .fetch_one(pool)
+            .await?;
+        Ok(password.is_some())
+    }
+
+    /// Changes the user's own password, which takes the current one. Users
+    /// without a password can set one without it, but only right after
+    /// logging in, which is when their session was created.
+    pub async fn change_password(
+        &self,
+        current: Option<String>,
+        new: String,
+        logged_in_at: Option<DateTime<Utc>>,
+        pool: &PgPool,
+    ) -> Result<(), AccountError> {
+        if !self.has_password(pool).await? {
+            if logged_in_at.is_none_or(|at| Utc::now() - at >= RECENT_LOGIN) {
+                return Err(AccountError::LoginRequired);
+            }
+        } else if !self
+            .verify_password(current.unwrap_or_default(), pool)
+            .await
+        {
+            return Err(AccountError::WrongPassword);
+        }
+        Self::set_password(self.id, new, pool).await
+    }
+
+    /// Sets a password without checking the current one, for admins.
+    pub async fn set_password(
+        id: Uuid,
+        password: String,
+        pool: &PgPool,
+    ) -> Result<(), AccountError> {
+        if password.is_empty() {
+            return Err(AccountError::InvalidInput("The password can't be empty"));
+        }
+        let hash = hash_password(password).await?;
+        let result = sqlx::query!("UPDATE users SET password = $2 WHERE id = $1", id, hash)
+            .execute(pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(AccountError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    pub async fn rename(&self, username: &str, pool: &PgPool) -> Result<Self, AccountError> {
+        if username.trim().is_empty() || username.trim() != username {
+            return Err(AccountError::InvalidInput(
+                "The username can't be empty or start or end with spaces",
+            ));
+        }
+        if username.chars().count() > 32 {
+            return Err(AccountError::InvalidInput(
+                "The username can be at most 32 characters",
+            ));
+        }
+        sqlx::query_as!(
+            User,
+            r#"UPDATE users SET username = $2 WHERE id = $1
+            RETURNING id, username, created_at, role AS "role: _""#,
+            self.id,
+            username
         )
-        .map_err(|e| AuthCreationError::JWTError(e))
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.is_unique_violation() => AccountError::UsernameTaken,
+            e => AccountError::DatabaseError(e),
+        })?
+        .ok_or(AccountError::UserNotFound)
     }
 
     pub async fn from_token(
         token: impl Into<String>,
         pool: &PgPool,
     ) -> Result<Self, AuthDecodeError> {
-        let claims: Claims = jsonwebtoken::decode(
-            &token.into(),
-            &jsonwebtoken::DecodingKey::from_secret(&CONFIG.jwt_secret),
-            &jsonwebtoken::Validation::default(),
-        )
-        .map_err(|e| AuthDecodeError::JWTError(e))?
-        .claims;
-
-        User::from_id(claims.sub, pool)
-            .await
-            .ok_or_else(|| AuthDecodeError::NonexistentUser)
+        let (_, user) = Session::from_token(&token.into(), pool).await?;
+        Ok(user)
     }
 
     pub async fn from_username(username: impl Into<String>, pool: &PgPool) -> Option<Self> {
         let username = username.into();
         sqlx::query_as!(
             User,
-            "SELECT id, username, created_at FROM users WHERE username = $1",
+            r#"SELECT id, username, created_at, role AS "role: _" FROM users WHERE username = $1"#,
             username
         )
         .fetch_one(pool)
         .await
         .ok()
     }
+
+    /// Whether nobody has signed up yet, in which case the first user can sign
+    /// up even with signups disabled.
+    pub async fn none_exist(pool: &PgPool) -> Result<bool, sqlx::Error> {
+        let exists = sqlx::query_scalar!("SELECT EXISTS(SELECT 1 FROM users)")
+            .fetch_one(pool)
+            .await?;
+        Ok(exists != Some(true))
+    }
+
+    pub async fn all(pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            User,
+            r#"SELECT id, username, created_at, role AS "role: _" FROM users ORDER BY created_at"#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role == UserRole::Admin
+    }
+
+    pub async fn set_role(
+        id: Uuid,
+        role: UserRole,
+        pool: &PgPool,
+    ) -> Result<Self, UserManagementError> {
+        let mut tx = pool.begin().await?;
+        if role != UserRole::Admin && Self::only_admin(id, &mut tx).await? {
+            return Err(UserManagementError::LastAdmin);
+        }
+        let user = sqlx::query_as!(
+            User,
+            r#"UPDATE users SET role = $2 WHERE id = $1
+            RETURNING id, username, created_at, role AS "role: _""#,
+            id,
+            role as _
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(UserManagementError::UserNotFound)?;
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    // locks the admins until the transaction ends, so two of them can't both
+    // be demoted or deleted on the assumption that the other one stays
+    async fn only_admin(id: Uuid, tx: &mut PgConnection) -> Result<bool, sqlx::Error> {
+        let admins = sqlx::query_scalar!("SELECT id FROM users WHERE role = 'admin' FOR UPDATE")
+            .fetch_all(&mut *tx)
+            .await?;
+        Ok(admins == [id])
+    }
+
+    /// Deletes the user along with all of their servers.
+    pub async fn delete(self, pool: &PgPool) -> Result<(), UserManagementError> {
+        // checked again when the user is deleted, this is so their servers
+        // aren't deleted for nothing
+        if Self::only_admin(self.id, &mut *pool.acquire().await?).await? {
+            return Err(UserManagementError::LastAdmin);
+        }
+
+        // the rows would go with the user, but not the containers and volumes.
+        // the user stays until all of them are gone, so this can be retried
+        let mut failed = None;
+        for server in Server::get_all(self.id, pool).await? {
+            let id = server.id;
+            if let Err(e) = server.delete(pool).await {
+                log::error!("failed to delete {} of user {}: {}", id, self.id, e);
+                failed = Some(e);
+            }
+        }
+        if let Some(e) = failed {
+            return Err(e.into());
+        }
+        let mut tx = pool.begin().await?;
+        if Self::only_admin(self.id, &mut tx).await? {
+            return Err(UserManagementError::LastAdmin);
+        }
+        sqlx::query!("DELETE FROM users WHERE id = $1", self.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+async fn hash_password(password: String) -> Result<String, UserCreationError> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(UserCreationError::HashError)
+    })
+    .await
+    .map_err(|_| UserCreationError::ThreadError)?
 }
@@ -1,40 +1,87 @@
+mod backup;
 mod config;
+mod console;
 mod db;
+mod minecraft;
+mod oidc;
+mod proxy;
+mod schedule;
+mod shutdown;
+mod sleep;
+mod template;
 mod tests;
 mod version;
+mod volume;
+mod watcher;
 mod web;
 
 use actix_cors::Cors;
-use actix_web::{middleware::Logger, web::Data, App, HttpServer};
+use actix_web::{middleware::Logger, rt, web::Data, App, HttpServer};
 use bollard::Docker;
 use config::CONFIG;
-use db::{server::Server, Database};
+use db::{
+    server::{Server, ServerStatus},
+    user::User,
+    Database,
+};
 use dotenvy::dotenv;
+use futures::future::join_all;
 use sqlx::PgPool;
 
 async fn restore_servers(pool: &PgPool) -> anyhow::Result<()> {
-    let servers = sqlx::query_as!(Server, "SELECT * FROM servers",)
-        .fetch_all(pool)
-        .await?;
-
+    let servers = Server::all(pool).await?;
     let docker = Docker::connect_with_local_defaults()?;
 
-    for server in servers {
-        server.restore_container(&docker, pool).await?;
+    // one broken server shouldn't keep the others (or the panel) down
+    let results = join_all(
+        servers
+            .iter()
+            .filter(|s| !matches!(s.status, ServerStatus::NeedsReview | ServerStatus::Deleting))
+            .map(|s| s.restore_or_flag(&docker, pool)),
+    )
+    .await;
+    for result in results {
+        if let Err(e) = result {
+            log::error!("failed to record server status: {}", e);
+        }
     }
 
     Ok(())
 }
 
+// so the admin doesn't have to race anyone to sign up first
+async fn create_first_admin(pool: &PgPool) -> anyhow::Result<()> {
+    let Some((username, password)) = &CONFIG.first_admin else {
+        return Ok(());
+    };
+    if User::none_exist(pool).await? {
+        let user = User::create(username, password, pool).await?;
+        log::info!("created the admin {}", user.username);
+    }
+    Ok(())
+}
+
 #[actix_web::main]
 async fn main() -> anyhow::Result<()> {
     dotenv().ok();
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("waitress"));
     let pool = PgPool::connect(&CONFIG.database_url).await?;
+    // load the templates up front, so broken ones are reported right away
+    log::info!("loaded {} server templates", template::all().len());
+    create_first_admin(&pool).await?;
     restore_servers(&pool).await?;
-    let db = Database::new(pool);
+    rt::spawn(backup::scheduler::run(pool.clone()));
+    rt::spawn(schedule::runner::run(pool.clone()));
+    rt::spawn(watcher::run(pool.clone()));
+    rt::spawn(watcher::reconcile::run(pool.clone()));
+    rt::spawn(watcher::disk::run(pool.clone()));
+    rt::spawn(sleep::run(pool.clone()));
+    if let Some(address) = CONFIG.proxy_address {
+        rt::spawn(proxy::run(address, pool.clone()));
+    }
+    let db = Database::new(pool.clone());
     log::info!("waitress is listening on ::9090!");
-    HttpServer::new(move || {
+    let server = HttpServer::new(move || {
         App::new()
             .app_data(Data::new(db.clone()))
             .configure(web::configure)
@@ -47,7 +94,10 @@ async fn main() -> anyhow::Result<()> {
             .wrap(Logger::default())
     })
     .bind(("127.0.0.1", 9090))?
-    .run()
-    .await?;
+    .disable_signals()
+    .run();
+    rt::spawn(shutdown::listen(server.handle()));
+    server.await?;
+    shutdown::stop_servers(&pool).await;
     Ok(())
 }
@@ -0,0 +1,3 @@
+pub mod ping;
+pub mod properties;
+pub mod protocol;
@@ -0,0 +1,53 @@
+use serde::Deserialize;
+use std::{io, time::Duration};
+use tokio::{io::AsyncWriteExt as _, net::TcpStream};
+
+use super::protocol::{packet, read_packet, Handshake, PacketReader};
+use crate::proxy::proxy_protocol;
+
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+// the status can have a base64 favicon in it, but nowhere near this much
+const MAX_STATUS_LENGTH: usize = 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct Players {
+    pub online: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Status {
+    pub players: Players,
+}
+
+/// Asks a server for its status with a Server List Ping, like the multiplayer
+/// menu does.
+pub async fn ping(host: &str, port: u16, proxy_protocol: bool) -> io::Result<Status> {
+    tokio::time::timeout(PING_TIMEOUT, ping_inner(host, port, proxy_protocol))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "ping timed out"))?
+}
+
+async fn ping_inner(host: &str, port: u16, proxy_protocol: bool) -> io::Result<Status> {
+    let mut stream = TcpStream::connect((host, port)).await?;
+    // servers behind the proxy drop connections without a header
+    if proxy_protocol {
+        stream.write_all(&proxy_protocol::local_header()).await?;
+    }
+
+    let handshake = Handshake {
+        // -1 is what clients send when they don't know the server's version
+        protocol_version: -1,
+        server_address: host.to_string(),
+        server_port: port,
+        next_state: 1,
+    };
+    stream.write_all(&handshake.encode()).await?;
+    stream.write_all(&packet(0x00, &[])).await?;
+
+    let body = read_packet(&mut stream, MAX_STATUS_LENGTH).await?;
+
+    let mut reader = PacketReader::new(&body);
+    reader.varint()?;
+    let json = reader.string()?;
+    serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
@@ -0,0 +1,403 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+// keys that waitress relies on for the container's port mapping.
+// letting users change these would break the server in confusing ways
+const PROTECTED_KEYS: &[&str] = &["server-port", "query.port"];
+const PROTECTED_PREFIXES: &[&str] = &["rcon."];
+// never shown to users, who would be able to use rcon directly with them
+const SECRET_KEYS: &[&str] = &["rcon.password"];
+
+const GAMEMODE: &str = "gamemode";
+const DIFFICULTY: &str = "difficulty";
+const MAX_PLAYERS: &str = "max-players";
+const MOTD: &str = "motd";
+const VIEW_DISTANCE: &str = "view-distance";
+const ONLINE_MODE: &str = "online-mode";
+
+const TYPED_KEYS: &[&str] = &[
+    GAMEMODE,
+    DIFFICULTY,
+    MAX_PLAYERS,
+    MOTD,
+    VIEW_DISTANCE,
+    ONLINE_MODE,
+];
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PropertiesError {
+    #[error("{0} is managed by waitress and cannot be changed")]
+    ProtectedKey(String),
+    #[error("Invalid key: {0:?}")]
+    InvalidKey(String),
+    #[error("{0} must be set through its typed field")]
+    TypedKey(String),
+    #[error("Invalid value for {key}: {reason}")]
+    InvalidValue { key: String, reason: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Gamemode {
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
+}
+
+impl Gamemode {
+    fn parse(value: &str) -> Option<Self> {
+        // old versions store gamemodes as numbers
+        match value.trim() {
+            "survival" | "0" => Some(Self::Survival),
+            "creative" | "1" => Some(Self::Creative),
+            "adventure" | "2" => Some(Self::Adventure),
+            "spectator" | "3" => Some(Self::Spectator),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Survival => "survival",
+            Self::Creative => "creative",
+            Self::Adventure => "adventure",
+            Self::Spectator => "spectator",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Peaceful,
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "peaceful" | "0" => Some(Self::Peaceful),
+            "easy" | "1" => Some(Self::Easy),
+            "normal" | "2" => Some(Self::Normal),
+            "hard" | "3" => Some(Self::Hard),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Peaceful => "peaceful",
+            Self::Easy => "easy",
+            Self::Normal => "normal",
+            Self::Hard => "hard",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Line {
+    Entry {
+        key: String,
+        value: String,
+        // the original line, dropped once the value is edited
+        raw: Option<String>,
+    },
+    // comments and blank lines, kept verbatim
+    Other(String),
+}
+
+/// A `server.properties` file that remembers its layout, so that writing it
+/// back only changes the values that were edited.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ServerProperties {
+    lines: Vec<Line>,
+}
+
+/// The typed view of a `server.properties` file returned by the api.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PropertiesView {
+    pub gamemode: Option<Gamemode>,
+    pub difficulty: Option<Difficulty>,
+    pub max_players: Option<u32>,
+    pub motd: Option<String>,
+    pub view_distance: Option<u8>,
+    pub online_mode: Option<bool>,
+    pub other: BTreeMap<String, String>,
+}
+
+/// A partial update to a `server.properties` file. Fields that are `None` are
+/// left untouched.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PropertiesPatch {
+    pub gamemode: Option<Gamemode>,
+    pub difficulty: Option<Difficulty>,
+    pub max_players: Option<u32>,
+    pub motd: Option<String>,
+    pub view_distance: Option<u8>,
+    pub online_mode: Option<bool>,
+    #[serde(default)]
+    pub other: HashMap<String, String>,
+}
+
+pub fn is_protected(key: &str) -> bool {
+    PROTECTED_KEYS.contains(&key) || PROTECTED_PREFIXES.iter().any(|p| key.starts_with(p))
+}
+
+impl ServerProperties {
+    pub fn parse(contents: &str) -> Self {
+        let lines = contents
+            .lines()
+            .map(|line| {
+                let trimmed = line.trim_start();
+                if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with('!') {
+                    return Line::Other(line.to_string());
+                }
+                let (key, value) = split_entry(trimmed);
+                Line::Entry {
+                    key: unescape(key),
+                    value: unescape(value),
+                    raw: Some(line.to_string()),
+                }
+            })
+            .collect();
+        Self { lines }
+    }
+
+    // java's `Properties` keeps the last of any duplicate keys
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.lines.iter().rev().find_map(|line| match line {
+            Line::Entry { key: k, value, .. } if k == key => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    fn set(&mut self, key: &str, value: impl Into<String>) {
+        let value = value.into();
+        for line in self.lines.iter_mut().rev() {
+            if let Line::Entry {
+                key: k,
+                value: v,
+                raw,
+            } = line
+            {
+                if k == key {
+                    if *v != value {
+                        *v = value;
+                        *raw = None;
+                    }
+                    return;
+                }
+            }
+        }
+        self.lines.push(Line::Entry {
+            key: key.to_string(),
+            value,
+            raw: None,
+        });
+    }
+
+    pub fn view(&self) -> PropertiesView {
+        let mut other = BTreeMap::new();
+        for line in &self.lines {
+            if let Line::Entry { key, value, .. } = line {
+                if !TYPED_KEYS.contains(&key.as_str()) && !SECRET_KEYS.contains(&key.as_str()) {
+                    other.insert(key.clone(), value.clone());
+                }
+            }
+        }
+
+        PropertiesView {
+            gamemode: self.get(GAMEMODE).and_then(Gamemode::parse),
+            difficulty: self.get(DIFFICULTY).and_then(Difficulty::parse),
+            max_players: self.get(MAX_PLAYERS).and_then(|v| v.trim().parse().ok()),
+            motd: self.get(MOTD).map(|v| v.to_string()),
+            view_distance: self.get(VIEW_DISTANCE).and_then(|v| v.trim().parse().ok()),
+            online_mode: self.get(ONLINE_MODE).and_then(|v| v.trim().parse().ok()),
+            other,
+        }
+    }
+
+    /// Validates and applies a patch. Nothing is changed if any part of the
+    /// patch is invalid.
+    pub fn apply(&mut self, patch: PropertiesPatch) -> Result<(), PropertiesError> {
+        let mut changes: Vec<(String, String)> = Vec::new();
+
+        if let Some(gamemode) = patch.gamemode {
+            changes.push((GAMEMODE.to_string(), gamemode.as_str().to_string()));
+        }
+
+        if let Some(difficulty) = patch.difficulty {
+            changes.push((DIFFICULTY.to_string(), difficulty.as_str().to_string()));
+        }
+
+        if let Some(max_players) = patch.max_players {
+            if max_players == 0 {
+                return Err(invalid(MAX_PLAYERS, "must be at least 1"));
+            }
+            changes.push((MAX_PLAYERS.to_string(), max_players.to_string()));
+        }
+
+        if let Some(motd) = patch.motd {
+            if motd.chars().count() > 256 {
+                return Err(invalid(MOTD, "must be at most 256 characters"));
+            }
+            if motd.contains(['\n', '\r']) {
+                return Err(invalid(MOTD, "must be a single line"));
+            }
+            changes.push((MOTD.to_string(), motd));
+        }
+
+        if let Some(view_distance) = patch.view_distance {
+            if !(3..=32).contains(&view_distance) {
+                return Err(invalid(VIEW_DISTANCE, "must be between 3 and 32"));
+            }
+            changes.push((VIEW_DISTANCE.to_string(), view_distance.to_string()));
+        }
+
+        if let Some(online_mode) = patch.online_mode {
+            changes.push((ONLINE_MODE.to_string(), online_mode.to_string()));
+        }
+
+        for (key, value) in patch.other {
+            if is_protected(&key) {
+                return Err(PropertiesError::ProtectedKey(key));
+            }
+            if TYPED_KEYS.contains(&key.as_str()) {
+                return Err(PropertiesError::TypedKey(key));
+            }
+            if key.is_empty()
+                || key
+                    .chars()
+                    .any(|c| c.is_whitespace() || matches!(c, '=' | ':' | '#' | '!' | '\\'))
+            {
+                return Err(PropertiesError::InvalidKey(key));
+            }
+            if value.contains(['\n', '\r']) {
+                return Err(invalid(&key, "must be a single line"));
+            }
+            changes.push((key, value));
+        }
+
+        for (key, value) in changes {
+            self.set(&key, value);
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for ServerProperties {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            match line {
+                Line::Entry { raw: Some(raw), .. } | Line::Other(raw) => writeln!(f, "{}", raw)?,
+                Line::Entry { key, value, .. } => writeln!(f, "{}={}", escape(key), escape(value))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid(key: &str, reason: &str) -> PropertiesError {
+    PropertiesError::InvalidValue {
+        key: key.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+// splits a line on the first unescaped `=`, `:` or whitespace, like java's
+// `Properties.load` does
+fn split_entry(line: &str) -> (&str, &str) {
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '=' | ':' => return (&line[..i], line[i + 1..].trim_start()),
+            c if c.is_whitespace() => {
+                let rest = line[i..].trim_start();
+                let rest = rest
+                    .strip_prefix(['=', ':'])
+                    .map(str::trim_start)
+                    .unwrap_or(rest);
+                return (&line[..i], rest);
+            }
+            _ => {}
+        }
+    }
+    (line, "")
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    // \u escapes are utf-16, so surrogate pairs have to be decoded together
+    let mut units: Vec<u16> = Vec::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.clone().next() == Some('u') {
+            chars.next();
+            let hex: String = chars.clone().take(4).collect();
+            if hex.len() == 4 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                chars.nth(3);
+                units.push(u16::from_str_radix(&hex, 16).unwrap());
+                continue;
+            }
+            // java refuses to load these, keep them as they are
+            flush_utf16(&mut units, &mut out);
+            out.push_str("\\u");
+            continue;
+        }
+        flush_utf16(&mut units, &mut out);
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('f') => out.push('\u{c}'),
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+    flush_utf16(&mut units, &mut out);
+    out
+}
+
+fn flush_utf16(units: &mut Vec<u16>, out: &mut String) {
+    out.extend(
+        char::decode_utf16(units.drain(..)).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)),
+    );
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '=' => out.push_str("\\="),
+            ':' => out.push_str("\\:"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if !c.is_ascii() || c.is_ascii_control() => {
+                let mut buf = [0u16; 2];
+                for unit in c.encode_utf16(&mut buf) {
+                    out.push_str(&format!("\\u{:04X}", unit));
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,156 @@
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt as _};
+
+pub fn write_varint(buf: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7f == 0 {
+            buf.push(value as u8);
+            return;
+        }
+        buf.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+pub fn write_string(buf: &mut Vec<u8>, value: &str) {
+    write_varint(buf, value.len() as i32);
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// Prefixes a packet id and body with their length.
+pub fn packet(id: i32, body: &[u8]) -> Vec<u8> {
+    let mut inner = Vec::with_capacity(body.len() + 5);
+    write_varint(&mut inner, id);
+    inner.extend_from_slice(body);
+    let mut packet = Vec::with_capacity(inner.len() + 5);
+    write_varint(&mut packet, inner.len() as i32);
+    packet.extend_from_slice(&inner);
+    packet
+}
+
+/// Kicks a player that's logging in, showing them `reason`.
+pub fn login_disconnect(reason: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    write_string(
+        &mut body,
+        &serde_json::json!({ "text": reason }).to_string(),
+    );
+    packet(0x00, &body)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+pub async fn read_varint<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<i32> {
+    let mut value = 0u32;
+    for i in 0..5 {
+        let byte = reader.read_u8().await?;
+        value |= ((byte & 0x7f) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(invalid("varint is too long"))
+}
+
+/// Reads a length-prefixed packet, returning its body (starting with the
+/// packet id).
+pub async fn read_packet<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    max_length: usize,
+) -> io::Result<Vec<u8>> {
+    let length = read_varint(reader).await?;
+    if length < 0 || length as usize > max_length {
+        return Err(invalid("packet is too long"));
+    }
+    let mut body = vec![0; length as usize];
+    reader.read_exact(&mut body).await?;
+    Ok(body)
+}
+
+/// Reads fields out of a packet body.
+pub struct PacketReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> PacketReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    pub fn varint(&mut self) -> io::Result<i32> {
+        let mut value = 0u32;
+        for i in 0..5 {
+            let (&byte, rest) = self
+                .buf
+                .split_first()
+                .ok_or_else(|| invalid("packet ended early"))?;
+            self.buf = rest;
+            value |= ((byte & 0x7f) as u32) << (7 * i);
+            if byte & 0x80 == 0 {
+                return Ok(value as i32);
+            }
+        }
+        Err(invalid("varint is too long"))
+    }
+
+    pub fn string(&mut self) -> io::Result<String> {
+        let length = self.varint()?;
+        if length < 0 || length as usize > self.buf.len() {
+            return Err(invalid("string is too long"));
+        }
+        let (string, rest) = self.buf.split_at(length as usize);
+        self.buf = rest;
+        String::from_utf8(string.to_vec()).map_err(|_| invalid("string is not utf-8"))
+    }
+
+    pub fn u16(&mut self) -> io::Result<u16> {
+        if self.buf.len() < 2 {
+            return Err(invalid("packet ended early"));
+        }
+        let (value, rest) = self.buf.split_at(2);
+        self.buf = rest;
+        Ok(u16::from_be_bytes([value[0], value[1]]))
+    }
+
+    /// Whatever hasn't been read yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.buf
+    }
+}
+
+/// The first packet of every connection.
+#[derive(Debug)]
+pub struct Handshake {
+    pub protocol_version: i32,
+    pub server_address: String,
+    pub server_port: u16,
+    /// 1 for a status ping, 2 for login (3 for transfers on newer versions).
+    pub next_state: i32,
+}
+
+impl Handshake {
+    pub fn parse(body: &[u8]) -> io::Result<Self> {
+        let mut reader = PacketReader::new(body);
+        if reader.varint()? != 0x00 {
+            return Err(invalid("not a handshake"));
+        }
+        Ok(Self {
+            protocol_version: reader.varint()?,
+            server_address: reader.string()?,
+            server_port: reader.u16()?,
+            next_state: reader.varint()?,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        write_varint(&mut body, self.protocol_version);
+        write_string(&mut body, &self.server_address);
+        body.extend_from_slice(&self.server_port.to_be_bytes());
+        write_varint(&mut body, self.next_state);
+        packet(0x00, &body)
+    }
+}
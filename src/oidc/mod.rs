@@ -0,0 +1,206 @@
+pub mod provider;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    config::OidcConfig,
+    db::{
+        oidc::{Identity, PendingLogin},
+        session::SessionError,
+        user::{User, UserCreationError, UserManagementError, UserRole},
+    },
+    response_codes,
+};
+use provider::{IdClaims, Provider};
+
+#[derive(Debug, Error)]
+pub enum OidcError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("Failed to reach the identity provider: {0}")]
+    RequestError(#[from] reqwest::Error),
+    #[error("The identity provider misbehaved: {0}")]
+    ProviderError(String),
+    #[error("Invalid ID token: {0}")]
+    InvalidToken(String),
+    #[error("The login is invalid or has expired, try again")]
+    InvalidState,
+    #[error("Too many logins are in progress, try again later")]
+    TooManyLogins,
+    #[error("Single sign-on isn't set up")]
+    Disabled,
+    #[error("No account is linked to this identity")]
+    NotLinked,
+    #[error("This identity is already linked to another account")]
+    AlreadyLinked,
+    #[error("An account named {0} already exists, log in and link it instead")]
+    UsernameTaken(String),
+    #[error("Failed to create session: {0}")]
+    SessionError(#[from] SessionError),
+    #[error("Failed to create login challenge: {0}")]
+    JWTError(#[from] jsonwebtoken::errors::Error),
+}
+
+response_codes!(OidcError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    RequestError(BAD_GATEWAY),
+    ProviderError(BAD_GATEWAY),
+    InvalidToken(UNAUTHORIZED),
+    InvalidState(BAD_REQUEST),
+    TooManyLogins(SERVICE_UNAVAILABLE),
+    Disabled(NOT_FOUND),
+    NotLinked(FORBIDDEN),
+    AlreadyLinked(CONFLICT),
+    UsernameTaken(CONFLICT),
+    SessionError(INTERNAL_SERVER_ERROR),
+    JWTError(INTERNAL_SERVER_ERROR),
+});
+
+/// A login that's been started, the browser keeps `browser` to finish it.
+pub struct Started {
+    pub url: String,
+    pub browser: String,
+}
+
+/// Starts a login, returning the provider URL to send the user to. With
+/// `link_user`, the provider account is linked to that user instead.
+pub async fn start(
+    config: &OidcConfig,
+    link_user: Option<Uuid>,
+    pool: &PgPool,
+) -> Result<Started, OidcError> {
+    let provider = Provider::discover(config).await?;
+    let browser = random();
+    let login = PendingLogin {
+        state: random(),
+        code_verifier: random(),
+        nonce: random(),
+        link_user,
+        browser_hash: hash(&browser),
+    };
+    let url = provider.authorize_url(
+        config,
+        &login.state,
+        &challenge(&login.code_verifier),
+        &login.nonce,
+    )?;
+    if !login.create(pool).await? {
+        return Err(OidcError::TooManyLogins);
+    }
+    Ok(Started { url, browser })
+}
+
+/// Finishes a login once the provider sends the user back with a code.
+/// Logins have to be finished by the browser that started them, and links
+/// by the user who started them, `linking_user`. Otherwise someone could
+/// send a victim their callback, to log them in as someone else or get a
+/// provider account linked to the victim's Waitress account.
+pub async fn finish(
+    config: &OidcConfig,
+    code: &str,
+    state: &str,
+    browser: &str,
+    linking_user: Option<Uuid>,
+    pool: &PgPool,
+) -> Result<User, OidcError> {
+    let login = PendingLogin::take(state, pool)
+        .await?
+        .ok_or(OidcError::InvalidState)?;
+    if login.browser_hash != hash(browser) || login.link_user != linking_user {
+        return Err(OidcError::InvalidState);
+    }
+    let provider = Provider::discover(config).await?;
+    let id_token = provider
+        .exchange(config, code, &login.code_verifier)
+        .await?;
+    let claims = provider.validate(config, &id_token, &login.nonce).await?;
+
+    let user_id = match login.link_user {
+        Some(user_id) => {
+            Identity::link(&provider.issuer, &claims.subject, user_id, pool)
+                .await?
+                .ok_or(OidcError::AlreadyLinked)?;
+            Some(user_id)
+        }
+        None => Identity::user_id(&provider.issuer, &claims.subject, pool).await?,
+    };
+    let user = match user_id {
+        Some(user_id) => User::from_id(user_id, pool)
+            .await
+            .ok_or(OidcError::NotLinked)?,
+        None if config.auto_provision => provision(&provider.issuer, &claims, pool).await?,
+        None => return Err(OidcError::NotLinked),
+    };
+    sync_role(config, user, &claims, pool).await
+}
+
+async fn provision(issuer: &str, claims: &IdClaims, pool: &PgPool) -> Result<User, OidcError> {
+    let username = claims
+        .username
+        .clone()
+        .ok_or_else(|| OidcError::InvalidToken("it has no username claim".to_string()))?;
+    let user = User::create_external(&username, pool)
+        .await
+        .map_err(|e| match e {
+            UserCreationError::UserAlreadyExists => OidcError::UsernameTaken(username),
+            e => OidcError::ProviderError(e.to_string()),
+        })?;
+    Identity::link(issuer, &claims.subject, user.id, pool)
+        .await?
+        .ok_or(OidcError::AlreadyLinked)?;
+    log::info!("created user {} from single sign-on", user.username);
+    Ok(user)
+}
+
+// the provider's groups are the source of truth for admins, if configured
+async fn sync_role(
+    config: &OidcConfig,
+    user: User,
+    claims: &IdClaims,
+    pool: &PgPool,
+) -> Result<User, OidcError> {
+    let Some(admin_group) = &config.admin_group else {
+        return Ok(user);
+    };
+    let role = if claims.groups.contains(admin_group) {
+        UserRole::Admin
+    } else {
+        UserRole::User
+    };
+    if user.role == role {
+        return Ok(user);
+    }
+    match User::set_role(user.id, role, pool).await {
+        Ok(user) => Ok(user),
+        Err(UserManagementError::DatabaseError(e)) => Err(e.into()),
+        Err(e) => {
+            log::warn!(
+                "couldn't update {}'s role from their groups: {}",
+                user.username,
+                e
+            );
+            Ok(user)
+        }
+    }
+}
+
+fn random() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+// the browser secret is random enough that a plain hash is fine
+fn hash(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+/// The S256 PKCE challenge for a verifier.
+pub fn challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::OidcError;
+use crate::config::OidcConfig;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The parts of a provider's discovery document that are needed. It's fetched
+/// on every login, which are rare enough that caching it (and the signing
+/// keys) isn't worth missing a key rotation.
+#[derive(Debug, Deserialize)]
+pub struct Provider {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+/// What's used from a validated ID token.
+#[derive(Debug, PartialEq)]
+pub struct IdClaims {
+    pub subject: String,
+    pub username: Option<String>,
+    pub groups: Vec<String>,
+}
+
+impl Provider {
+    pub async fn discover(config: &OidcConfig) -> Result<Self, OidcError> {
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            config.issuer.trim_end_matches('/')
+        );
+        let provider: Self = client()?
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        // a document for another issuer could hand out its tokens as ours
+        if provider.issuer.trim_end_matches('/') != config.issuer.trim_end_matches('/') {
+            return Err(OidcError::ProviderError(format!(
+                "its discovery document is for {}",
+                provider.issuer
+            )));
+        }
+        Ok(provider)
+    }
+
+    pub fn authorize_url(
+        &self,
+        config: &OidcConfig,
+        state: &str,
+        challenge: &str,
+        nonce: &str,
+    ) -> Result<String, OidcError> {
+        let url = Url::parse_with_params(
+            &self.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &config.client_id),
+                ("redirect_uri", &config.redirect_url),
+                ("scope", &config.scopes),
+                ("state", state),
+                ("nonce", nonce),
+                ("code_challenge", challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| OidcError::ProviderError(format!("bad authorization endpoint: {}", e)))?;
+        Ok(url.into())
+    }
+
+    /// Trades an authorization code for an ID token.
+    pub async fn exchange(
+        &self,
+        config: &OidcConfig,
+        code: &str,
+        verifier: &str,
+    ) -> Result<String, OidcError> {
+        let mut request = client()?.post(&self.token_endpoint).form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &config.redirect_url),
+            ("client_id", &config.client_id),
+            ("code_verifier", verifier),
+        ]);
+        if let Some(secret) = &config.client_secret {
+            request = request.basic_auth(&config.client_id, Some(secret));
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(OidcError::ProviderError(format!(
+                "the code exchange failed with {}: {}",
+                status,
+                body.chars().take(256).collect::<String>()
+            )));
+        }
+        response
+            .json::<TokenResponse>()
+            .await?
+            .id_token
+            .ok_or_else(|| OidcError::ProviderError("no ID token was returned".to_string()))
+    }
+
+    /// Checks the ID token's signature against the provider's keys, along
+    /// with its issuer, audience, expiry and nonce.
+    pub async fn validate(
+        &self,
+        config: &OidcConfig,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<IdClaims, OidcError> {
+        let invalid = |e: jsonwebtoken::errors::Error| OidcError::InvalidToken(e.to_string());
+        let header = jsonwebtoken::decode_header(id_token).map_err(invalid)?;
+        // the provider's keys are public, so one used as an HMAC secret would
+        // let anyone sign tokens
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(OidcError::InvalidToken(
+                "it isn't signed with the provider's keys".to_string(),
+            ));
+        }
+
+        let keys: JwkSet = client()?
+            .get(&self.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let key = match &header.kid {
+            Some(kid) => keys.find(kid),
+            None if keys.keys.len() == 1 => keys.keys.first(),
+            None => None,
+        }
+        .ok_or_else(|| OidcError::InvalidToken("it's signed with an unknown key".to_string()))?;
+        let key = DecodingKey::from_jwk(key).map_err(invalid)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        let claims = jsonwebtoken::decode::<Value>(id_token, &key, &validation)
+            .map_err(invalid)?
+            .claims;
+
+        if claims["nonce"].as_str() != Some(nonce) {
+            return Err(OidcError::InvalidToken(
+                "the nonce doesn't match".to_string(),
+            ));
+        }
+        let claims = IdClaims::from_claims(config, &claims);
+        if claims.subject.is_empty() {
+            return Err(OidcError::InvalidToken("it has no subject".to_string()));
+        }
+        Ok(claims)
+    }
+}
+
+impl IdClaims {
+    fn from_claims(config: &OidcConfig, claims: &Value) -> Self {
+        // some providers send a single group as a string
+        let groups = match &claims[&config.groups_claim] {
+            Value::Array(groups) => groups
+                .iter()
+                .filter_map(|group| group.as_str().map(String::from))
+                .collect(),
+            Value::String(group) => vec![group.clone()],
+            _ => Vec::new(),
+        };
+        Self {
+            subject: claims["sub"].as_str().unwrap_or_default().to_string(),
+            username: claims[&config.username_claim].as_str().map(String::from),
+            groups,
+        }
+    }
+}
+
+fn client() -> Result<Client, reqwest::Error> {
+    Client::builder().timeout(TIMEOUT).build()
+}
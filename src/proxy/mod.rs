@@ -0,0 +1,90 @@
+pub mod proxy_protocol;
+
+use actix_web::rt;
+use sqlx::PgPool;
+use std::{io, net::SocketAddr, time::Duration};
+use tokio::{
+    io::AsyncWriteExt as _,
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    db::server::{Server, ServerStatus},
+    minecraft::protocol::{login_disconnect, read_packet, write_varint, Handshake},
+};
+
+// the handshake is small, anything bigger isn't a minecraft client
+const MAX_HANDSHAKE_LENGTH: usize = 1024;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Accepts players on a single port and forwards them to the server whose
+/// hostname they connected with.
+pub async fn run(address: SocketAddr, pool: PgPool) {
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("failed to start the proxy on {}: {}", address, e);
+            return;
+        }
+    };
+    log::info!("proxy is listening on {}", address);
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                log::error!("failed to accept proxy connection: {}", e);
+                continue;
+            }
+        };
+        let pool = pool.clone();
+        rt::spawn(async move {
+            if let Err(e) = forward(stream, &pool).await {
+                log::debug!("proxy connection failed: {}", e);
+            }
+        });
+    }
+}
+
+async fn forward(mut client: TcpStream, pool: &PgPool) -> io::Result<()> {
+    let body = tokio::time::timeout(
+        HANDSHAKE_TIMEOUT,
+        read_packet(&mut client, MAX_HANDSHAKE_LENGTH),
+    )
+    .await
+    .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "no handshake"))??;
+    let handshake = Handshake::parse(&body)?;
+
+    let hostname = normalize(&handshake.server_address);
+    let Some(server) = Server::from_hostname(&hostname, pool).await else {
+        if handshake.next_state != 1 {
+            client
+                .write_all(&login_disconnect("There is no server at this address"))
+                .await?;
+        }
+        return Ok(());
+    };
+
+    let mut backend = TcpStream::connect(("127.0.0.1", server.port as u16)).await?;
+    // waitress answers for sleeping servers itself, and doesn't expect a header
+    if server.proxy_protocol && server.status != ServerStatus::Sleeping {
+        let header = proxy_protocol::header(client.peer_addr()?, client.local_addr()?);
+        backend.write_all(&header).await?;
+    }
+    // pass the handshake on as it was, the server might want the extra data
+    // that some clients put in the address
+    let mut packet = Vec::with_capacity(body.len() + 5);
+    write_varint(&mut packet, body.len() as i32);
+    packet.extend_from_slice(&body);
+    backend.write_all(&packet).await?;
+
+    tokio::io::copy_bidirectional(&mut client, &mut backend).await?;
+    Ok(())
+}
+
+// forge and bungeecord append data to the address after a null byte, and
+// some clients keep the trailing dot of a fully qualified name
+fn normalize(address: &str) -> String {
+    let address = address.split('\0').next().unwrap_or_default();
+    address.trim_end_matches('.').to_lowercase()
+}
@@ -0,0 +1,54 @@
+use std::net::{IpAddr, SocketAddr};
+
+const SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+// version 2, PROXY command
+const VERSION_COMMAND: u8 = 0x21;
+// version 2, LOCAL command
+const VERSION_LOCAL: u8 = 0x20;
+const UNSPECIFIED: u8 = 0x00;
+const TCP_OVER_IPV4: u8 = 0x11;
+const TCP_OVER_IPV6: u8 = 0x21;
+
+/// Builds a PROXY protocol v2 header telling the backend that `source`
+/// connected to `destination`.
+pub fn header(source: SocketAddr, destination: SocketAddr) -> Vec<u8> {
+    let mut header = Vec::with_capacity(52);
+    header.extend_from_slice(SIGNATURE);
+    header.push(VERSION_COMMAND);
+
+    match (source.ip(), destination.ip()) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            header.push(TCP_OVER_IPV4);
+            header.extend_from_slice(&12u16.to_be_bytes());
+            header.extend_from_slice(&src.octets());
+            header.extend_from_slice(&dst.octets());
+        }
+        // both addresses have to be the same family, so mixed ones are
+        // mapped to ipv6
+        (src, dst) => {
+            let v6 = |ip: IpAddr| match ip {
+                IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+                IpAddr::V6(ip) => ip,
+            };
+            header.push(TCP_OVER_IPV6);
+            header.extend_from_slice(&36u16.to_be_bytes());
+            header.extend_from_slice(&v6(src).octets());
+            header.extend_from_slice(&v6(dst).octets());
+        }
+    }
+
+    header.extend_from_slice(&source.port().to_be_bytes());
+    header.extend_from_slice(&destination.port().to_be_bytes());
+    header
+}
+
+/// Builds a PROXY protocol v2 header for a connection waitress makes itself,
+/// like a ping, so the backend uses the connection's own addresses.
+pub fn local_header() -> Vec<u8> {
+    let mut header = Vec::with_capacity(16);
+    header.extend_from_slice(SIGNATURE);
+    header.push(VERSION_LOCAL);
+    header.push(UNSPECIFIED);
+    header.extend_from_slice(&0u16.to_be_bytes());
+    header
+}
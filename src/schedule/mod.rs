@@ -0,0 +1,41 @@
+pub mod runner;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+
+use crate::db::schedule::ScheduleError;
+
+/// Parses a cron expression. The usual five fields are accepted, as well as
+/// the six or seven field form with seconds (and years).
+pub fn parse_cron(expression: &str) -> Result<cron::Schedule, ScheduleError> {
+    let expression = expression.trim();
+    let expression = if expression.split_whitespace().count() == 5 {
+        format!("0 {}", expression)
+    } else {
+        expression.to_string()
+    };
+    expression
+        .parse()
+        .map_err(|e: cron::error::Error| ScheduleError::InvalidCron(e.to_string()))
+}
+
+pub fn parse_timezone(timezone: &str) -> Result<Tz, ScheduleError> {
+    timezone
+        .parse()
+        .map_err(|_| ScheduleError::InvalidTimezone(timezone.to_string()))
+}
+
+/// The first time after `after` that the expression matches in the given
+/// timezone, or `None` if it never will.
+pub fn next_run(
+    expression: &str,
+    timezone: &str,
+    after: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, ScheduleError> {
+    let cron = parse_cron(expression)?;
+    let timezone = parse_timezone(timezone)?;
+    Ok(cron
+        .after(&after.with_timezone(&timezone))
+        .next()
+        .map(|time| time.with_timezone(&Utc)))
+}
@@ -0,0 +1,201 @@
+use actix_web::rt;
+use chrono::{TimeDelta, Utc};
+use lazy_static::lazy_static;
+use sqlx::PgPool;
+use std::{collections::HashSet, sync::Mutex, time::Duration};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    console::{Console, ConsoleError},
+    db::{
+        backup::{Backup, BackupError, BackupTrigger},
+        schedule::{Schedule, ScheduleAction, ScheduleResult},
+        server::{Server, ServerStartError, ServerStatus, ServerStopError},
+    },
+    minecraft::ping,
+    sleep,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+// runs missed by more than this (e.g. while waitress was down) are skipped
+// instead of all firing at once on startup
+const MISSED_GRACE: TimeDelta = TimeDelta::minutes(5);
+
+lazy_static! {
+    static ref RUNNING: Mutex<HashSet<Uuid>> = Mutex::new(HashSet::new());
+}
+
+#[derive(Debug, Error)]
+enum ActionError {
+    #[error("Console error: {0}")]
+    Console(#[from] ConsoleError),
+    #[error("Failed to start server: {0}")]
+    Start(#[from] ServerStartError),
+    #[error("Failed to stop server: {0}")]
+    Stop(#[from] ServerStopError),
+    #[error("Backup failed: {0}")]
+    Backup(#[from] BackupError),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+// marks a schedule as running until dropped, so a slow run doesn't overlap
+// with the next one
+struct RunGuard(Uuid);
+
+impl RunGuard {
+    fn acquire(schedule_id: Uuid) -> Option<Self> {
+        let mut running = RUNNING.lock().unwrap();
+        running.insert(schedule_id).then_some(Self(schedule_id))
+    }
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        RUNNING.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Runs schedules as they come due. The next run of every schedule is stored
+/// in the database, so they carry on where they left off after a restart.
+pub async fn run(pool: PgPool) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let due = match Schedule::due(&pool).await {
+            Ok(due) => due,
+            Err(e) => {
+                log::error!("failed to fetch due schedules: {}", e);
+                continue;
+            }
+        };
+
+        for schedule in due {
+            let pool = pool.clone();
+            rt::spawn(async move {
+                if let Err(e) = start(schedule, &pool).await {
+                    log::error!("failed to run schedule: {}", e);
+                }
+            });
+        }
+    }
+}
+
+async fn start(schedule: Schedule, pool: &PgPool) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+    // the schedule was validated when it was saved, but timezones can
+    // disappear from the database between releases
+    let next_run_at = crate::schedule::next_run(&schedule.cron, &schedule.timezone, now)
+        .unwrap_or_else(|e| {
+            log::error!("disabling schedule {}: {}", schedule.id, e);
+            None
+        });
+    // claiming the run before doing anything means a crash halfway through
+    // doesn't repeat it
+    if !schedule.advance(next_run_at, pool).await? {
+        return Ok(());
+    }
+
+    if schedule
+        .next_run_at
+        .is_some_and(|due| now - due > MISSED_GRACE)
+    {
+        log::warn!("schedule {} missed its run, skipping", schedule.id);
+        return schedule
+            .record(
+                now,
+                ScheduleResult::Skipped,
+                Some("missed while waitress was offline".to_string()),
+                pool,
+            )
+            .await;
+    }
+
+    let Some(_guard) = RunGuard::acquire(schedule.id) else {
+        return schedule
+            .record(
+                now,
+                ScheduleResult::Skipped,
+                Some("the previous run has not finished".to_string()),
+                pool,
+            )
+            .await;
+    };
+
+    let Some(server) = Server::from_id(schedule.server_id, pool).await else {
+        return Ok(());
+    };
+
+    if schedule.only_when_players_online && !players_online(&server).await {
+        return schedule
+            .record(
+                now,
+                ScheduleResult::Skipped,
+                Some("no players online".to_string()),
+                pool,
+            )
+            .await;
+    }
+
+    log::info!("running schedule {} for {}", schedule.id, server.id);
+    let (result, message) = match execute(&schedule.actions, &server, pool).await {
+        Ok(()) => (ScheduleResult::Success, None),
+        Err(e) => {
+            log::error!("schedule {} failed: {}", schedule.id, e);
+            (ScheduleResult::Failed, Some(e.to_string()))
+        }
+    };
+    schedule.record(now, result, message, pool).await
+}
+
+async fn players_online(server: &Server) -> bool {
+    if !server.is_running().await.unwrap_or(false) {
+        return false;
+    }
+    match ping::ping("127.0.0.1", server.port as u16, server.proxy_protocol).await {
+        Ok(status) => status.players.online > 0,
+        // still starting up, or not a server that answers pings
+        Err(_) => false,
+    }
+}
+
+// runs the actions in order, stopping at the first one that fails
+async fn execute(
+    actions: &[ScheduleAction],
+    server: &Server,
+    pool: &PgPool,
+) -> Result<(), ActionError> {
+    for action in actions {
+        match action {
+            ScheduleAction::Command(command) => {
+                Console::get(server.id)
+                    .await?
+                    .send(command.as_str())
+                    .await?
+            }
+            ScheduleAction::Start => {
+                server.start(pool).await?;
+                Server::set_should_run(server.id, true, pool).await?;
+            }
+            ScheduleAction::Stop => {
+                server.stop().await?;
+                // a sleeping server shouldn't be woken up by players anymore
+                sleep::release(server.id).await;
+                Server::set_status(server.id, ServerStatus::Stopped, pool).await?;
+                Server::set_should_run(server.id, false, pool).await?;
+            }
+            ScheduleAction::Restart => {
+                server.stop().await?;
+                server.start(pool).await?;
+            }
+            ScheduleAction::Backup => {
+                Backup::create(server, BackupTrigger::Scheduled, pool).await?;
+            }
+            ScheduleAction::Wait(seconds) => {
+                tokio::time::sleep(Duration::from_secs(*seconds)).await
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use actix_web::dev::ServerHandle;
+use futures::future::join_all;
+use lazy_static::lazy_static;
+use sqlx::PgPool;
+use std::str::FromStr;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::CONFIG,
+    db::server::{Server, ServerStatus},
+};
+
+lazy_static! {
+    static ref SHUTDOWN: CancellationToken = CancellationToken::new();
+}
+
+/// What happens to the game servers when waitress shuts down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownPolicy {
+    /// Leave them running, they're picked up again on the next start.
+    Leave,
+    /// Stop them cleanly, for when the host is going down too.
+    Stop,
+}
+
+impl FromStr for ShutdownPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "leave" => Ok(Self::Leave),
+            "stop" => Ok(Self::Stop),
+            _ => anyhow::bail!("unknown shutdown policy {:?}", s),
+        }
+    }
+}
+
+/// Resolves once waitress starts shutting down.
+pub async fn requested() {
+    SHUTDOWN.cancelled().await
+}
+
+/// Waits for SIGINT or SIGTERM, then tells everything listening for
+/// [`requested`] to wrap up and stops the http server. This replaces actix's
+/// own signal handling, so that websockets are closed before actix waits on
+/// them.
+pub async fn listen(server: ServerHandle) {
+    wait_for_signal().await;
+    log::info!("shutting down");
+    SHUTDOWN.cancel();
+    server.stop(true).await;
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(e) => {
+            log::error!("failed to listen for SIGTERM: {}", e);
+            tokio::signal::ctrl_c().await.ok();
+            return;
+        }
+    };
+    tokio::select! {
+        _ = terminate.recv() => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    tokio::signal::ctrl_c().await.ok();
+}
+
+/// Applies the shutdown policy, stopping every running server in parallel if
+/// it says to. Servers that haven't stopped by the deadline are left to
+/// whatever happens to them next.
+pub async fn stop_servers(pool: &PgPool) {
+    if CONFIG.shutdown_policy != ShutdownPolicy::Stop {
+        return;
+    }
+
+    let servers = match Server::all(pool).await {
+        Ok(servers) => servers,
+        Err(e) => {
+            log::error!("failed to fetch servers to stop: {}", e);
+            return;
+        }
+    };
+    let running = servers
+        .iter()
+        .filter(|s| s.status == ServerStatus::Running)
+        .collect::<Vec<_>>();
+    log::info!("stopping {} servers", running.len());
+
+    let stops = join_all(running.iter().map(|server| async move {
+        if let Err(e) = server.stop().await {
+            log::error!("failed to stop {}: {}", server.container_name(), e);
+        }
+    }));
+    if tokio::time::timeout(CONFIG.shutdown_timeout, stops)
+        .await
+        .is_err()
+    {
+        log::warn!(
+            "servers did not stop within {}s, giving up on them",
+            CONFIG.shutdown_timeout.as_secs()
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use serde_json::json;
+use std::{io, time::Duration};
+use tokio::{io::AsyncWriteExt as _, net::TcpStream};
+
+use crate::minecraft::protocol::{
+    login_disconnect, packet, read_packet, write_string, Handshake, PacketReader,
+};
+
+// nothing a client sends before logging in comes close to this
+const MAX_PACKET_LENGTH: usize = 1024;
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Answers a connection to a sleeping server. Returns whether the client was
+/// trying to join, in which case the server should be woken up.
+pub async fn handle(mut stream: TcpStream, motd: &str) -> io::Result<bool> {
+    tokio::time::timeout(CONNECTION_TIMEOUT, handle_inner(&mut stream, motd))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "client timed out"))?
+}
+
+async fn handle_inner(stream: &mut TcpStream, motd: &str) -> io::Result<bool> {
+    let handshake = Handshake::parse(&read_packet(stream, MAX_PACKET_LENGTH).await?)?;
+    match handshake.next_state {
+        1 => {
+            status(stream, handshake.protocol_version, motd).await?;
+            Ok(false)
+        }
+        // login, or a transfer from another server
+        2 | 3 => {
+            disconnect(stream, "The server is starting up, try again in a moment").await?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+async fn status(stream: &mut TcpStream, protocol_version: i32, motd: &str) -> io::Result<()> {
+    // status request, which has no fields
+    read_packet(stream, MAX_PACKET_LENGTH).await?;
+
+    let status = json!({
+        // echoing the client's version keeps it from showing as incompatible
+        "version": { "name": "Sleeping", "protocol": protocol_version },
+        "players": { "max": 0, "online": 0 },
+        "description": { "text": motd },
+    });
+    let mut body = Vec::new();
+    write_string(&mut body, &status.to_string());
+    stream.write_all(&packet(0x00, &body)).await?;
+
+    // the client measures latency with a ping, which is echoed back as is
+    let ping = read_packet(stream, MAX_PACKET_LENGTH).await?;
+    let mut reader = PacketReader::new(&ping);
+    if reader.varint()? == 0x01 {
+        stream.write_all(&packet(0x01, reader.remaining())).await?;
+    }
+    Ok(())
+}
+
+async fn disconnect(stream: &mut TcpStream, reason: &str) -> io::Result<()> {
+    stream.write_all(&login_disconnect(reason)).await?;
+    stream.shutdown().await
+}
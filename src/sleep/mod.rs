@@ -0,0 +1,188 @@
+pub mod listener;
+
+use actix_web::rt;
+use futures::future::join_all;
+use lazy_static::lazy_static;
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::{net::TcpListener, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        server::{Server, ServerStatus},
+        sleep::{SleepError, SleepPolicy, DEFAULT_MOTD},
+    },
+    minecraft::ping,
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    static ref LISTENERS: Mutex<HashMap<Uuid, Listener>> = Mutex::new(HashMap::new());
+}
+
+// listens on a sleeping server's port in its place
+struct Listener {
+    cancel: CancellationToken,
+    handle: JoinHandle<()>,
+}
+
+/// Puts servers to sleep once they've had no players for as long as their
+/// sleep policy says.
+pub async fn run(pool: PgPool) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    let mut idle_since: HashMap<Uuid, Instant> = HashMap::new();
+    loop {
+        interval.tick().await;
+        let policies = match SleepPolicy::enabled(&pool).await {
+            Ok(policies) => policies,
+            Err(e) => {
+                log::error!("failed to fetch sleep policies: {}", e);
+                continue;
+            }
+        };
+
+        let checks = policies.into_iter().map(|(server_id, idle_minutes)| {
+            let pool = &pool;
+            async move {
+                let server = Server::from_id(server_id, pool).await?;
+                // a server that doesn't answer is probably still starting up,
+                // which doesn't count as idle
+                let status = ping::ping("127.0.0.1", server.port as u16, server.proxy_protocol)
+                    .await
+                    .ok()?;
+                (status.players.online == 0).then_some((server, idle_minutes))
+            }
+        });
+        let idle = join_all(checks).await.into_iter().flatten();
+
+        let mut still_idle = HashMap::new();
+        for (server, idle_minutes) in idle {
+            let since = idle_since
+                .get(&server.id)
+                .copied()
+                .unwrap_or_else(Instant::now);
+            if since.elapsed() < Duration::from_secs(idle_minutes as u64 * 60) {
+                still_idle.insert(server.id, since);
+                continue;
+            }
+            let pool = pool.clone();
+            rt::spawn(async move {
+                if let Err(e) = sleep(&server, &pool).await {
+                    log::error!("failed to put {} to sleep: {}", server.id, e);
+                }
+            });
+        }
+        idle_since = still_idle;
+    }
+}
+
+/// Stops the server and listens on its port until someone tries to join.
+pub async fn sleep(server: &Server, pool: &PgPool) -> Result<(), SleepError> {
+    log::info!("putting {} to sleep", server.id);
+    let policy = SleepPolicy::get(server.id, pool).await?;
+    let motd = policy.motd.unwrap_or_else(|| DEFAULT_MOTD.to_string());
+    // set first, so the container stopping isn't mistaken for a plain stop
+    Server::set_status(server.id, ServerStatus::Sleeping, pool).await?;
+    if let Err(e) = server.stop().await {
+        Server::set_status(server.id, ServerStatus::Running, pool).await?;
+        return Err(e.into());
+    }
+
+    // the port is only free once the container has stopped, so if something
+    // else took it the server has to come back up instead
+    let listener = match bind(server.port as u16).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            Server::set_status(server.id, ServerStatus::Running, pool).await?;
+            if let Err(e) = server.start(pool).await {
+                log::error!("failed to start {} again: {}", server.id, e);
+            }
+            return Err(e.into());
+        }
+    };
+
+    let cancel = CancellationToken::new();
+    let handle = rt::spawn(listen(
+        server.id,
+        listener,
+        motd,
+        cancel.clone(),
+        pool.clone(),
+    ));
+    LISTENERS
+        .lock()
+        .unwrap()
+        .insert(server.id, Listener { cancel, handle });
+    Ok(())
+}
+
+// docker can take a moment to let go of the port after the container stops
+async fn bind(port: u16) -> std::io::Result<TcpListener> {
+    let mut attempts = 0;
+    loop {
+        match TcpListener::bind(("127.0.0.1", port)).await {
+            Ok(listener) => return Ok(listener),
+            Err(e) if attempts >= 10 => return Err(e),
+            Err(_) => {
+                attempts += 1;
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+        }
+    }
+}
+
+async fn listen(
+    server_id: Uuid,
+    listener: TcpListener,
+    motd: String,
+    cancel: CancellationToken,
+    pool: PgPool,
+) {
+    loop {
+        let stream = tokio::select! {
+            _ = cancel.cancelled() => return,
+            stream = listener.accept() => stream,
+        };
+        let stream = match stream {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                log::error!("failed to accept connection for {}: {}", server_id, e);
+                continue;
+            }
+        };
+        match listener::handle(stream, &motd).await {
+            Ok(true) => break,
+            Ok(false) => {}
+            Err(e) => log::debug!("bad connection to sleeping {}: {}", server_id, e),
+        }
+    }
+
+    // hand the port back before starting the container, which needs it
+    drop(listener);
+    LISTENERS.lock().unwrap().remove(&server_id);
+
+    log::info!("waking {} up", server_id);
+    let Some(server) = Server::from_id(server_id, &pool).await else {
+        return;
+    };
+    if let Err(e) = server.start(&pool).await {
+        log::error!("failed to wake {} up: {}", server_id, e);
+    }
+}
+
+/// Stops listening in place of a sleeping server, once the port is free
+/// again. Does nothing if the server isn't asleep.
+pub async fn release(server_id: Uuid) {
+    let listener = LISTENERS.lock().unwrap().remove(&server_id);
+    if let Some(listener) = listener {
+        listener.cancel.cancel();
+        listener.handle.await.ok();
+    }
+}
@@ -0,0 +1,436 @@
+//! Converts Pterodactyl eggs into templates. Anything an egg does that
+//! waitress can't is reported back as a warning rather than dropped quietly.
+
+use regex::Regex;
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use serde_json::Value;
+use std::{collections::HashSet, fmt};
+
+use super::{
+    ConfigFile, ConfigFormat, Protocol, Template, TemplateError, TemplatePort, TemplateVariable,
+    VariableKind, VariableRules,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Egg {
+    #[serde(default)]
+    meta: Option<EggMeta>,
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    features: Option<Vec<String>>,
+    /// Keeps the order of the egg, the first image is the default one.
+    #[serde(default, deserialize_with = "ordered_map")]
+    docker_images: Vec<(String, String)>,
+    // eggs exported before PTDL_v2 only have a single image
+    #[serde(default)]
+    docker_image: Option<String>,
+    #[serde(default)]
+    file_denylist: Vec<String>,
+    startup: String,
+    #[serde(default)]
+    config: EggConfig,
+    #[serde(default)]
+    scripts: EggScripts,
+    #[serde(default)]
+    variables: Vec<EggVariable>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EggMeta {
+    version: Option<String>,
+}
+
+// these are JSON encoded as strings in exported eggs
+#[derive(Debug, Default, Deserialize)]
+struct EggConfig {
+    #[serde(default)]
+    files: Value,
+    #[serde(default)]
+    startup: Value,
+    #[serde(default)]
+    logs: Value,
+    #[serde(default)]
+    stop: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct EggScripts {
+    #[serde(default)]
+    installation: Option<EggInstallation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EggInstallation {
+    #[serde(default)]
+    script: String,
+    #[serde(default)]
+    container: Option<String>,
+    #[serde(default)]
+    entrypoint: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EggVariable {
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    env_variable: String,
+    #[serde(default)]
+    default_value: Option<String>,
+    #[serde(default)]
+    user_viewable: Option<bool>,
+    #[serde(default)]
+    user_editable: Option<bool>,
+    #[serde(default)]
+    rules: Option<String>,
+}
+
+/// A converted egg, with everything that couldn't be carried over.
+#[derive(Debug, Serialize)]
+pub struct Import {
+    pub template: Template,
+    pub warnings: Vec<String>,
+}
+
+/// Converts an egg. The id defaults to one made from the egg's name, and
+/// `port` is the port the server listens on inside its container, since eggs
+/// leave that to Pterodactyl.
+pub fn convert(egg: Egg, id: Option<String>, port: u16) -> Result<Import, TemplateError> {
+    let mut warnings = Vec::new();
+
+    if let Some(version) = egg.meta.as_ref().and_then(|m| m.version.as_deref()) {
+        if version != "PTDL_v1" && version != "PTDL_v2" {
+            warnings.push(format!(
+                "the egg is in the {} format, which might not convert correctly",
+                version
+            ));
+        }
+    }
+
+    let (image, other_images) = match (egg.docker_images.split_first(), egg.docker_image) {
+        (Some(((_, image), others)), _) => (image.clone(), others.to_vec()),
+        (None, Some(image)) => (image, Vec::new()),
+        (None, None) => return Err(invalid("the egg has no docker image")),
+    };
+    if !other_images.is_empty() {
+        let others = other_images
+            .iter()
+            .map(|(label, image)| format!("{} ({})", label, image))
+            .collect::<Vec<_>>()
+            .join(", ");
+        warnings.push(format!(
+            "only the first docker image is used, {} can't be picked",
+            others
+        ));
+    }
+
+    for feature in egg.features.unwrap_or_default() {
+        warnings.push(format!("the {} feature isn't supported", feature));
+    }
+    if !egg.file_denylist.is_empty() {
+        warnings.push("the file denylist isn't supported, all files can be edited".to_string());
+    }
+
+    let names = egg
+        .variables
+        .iter()
+        .map(|v| v.env_variable.clone())
+        .collect::<HashSet<_>>();
+    let startup = convert_command(&egg.startup, &names, port)?;
+
+    let install = match egg.scripts.installation {
+        Some(installation) if !installation.script.trim().is_empty() => {
+            let container = installation.container.as_deref().unwrap_or(&image);
+            let entrypoint = installation.entrypoint.as_deref().unwrap_or("sh");
+            if container != image || entrypoint != "sh" {
+                // tools like curl and jq might be missing from the image
+                warnings.push(format!(
+                    "the install script runs with sh in {} instead of {} in {}",
+                    image, entrypoint, container
+                ));
+            }
+            // pterodactyl mounts the server's folder somewhere else while
+            // installing
+            installation.script.replace("/mnt/server", "/data")
+        }
+        _ => String::new(),
+    };
+
+    let stop = match egg.config.stop.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(signal) if signal.starts_with('^') => {
+            warnings.push(format!(
+                "the server is stopped with SIGTERM instead of {}",
+                signal
+            ));
+            None
+        }
+        Some(command) => Some(command.to_string()),
+    };
+
+    let files = convert_files(&egg.config.files, &mut warnings)?;
+    if let Some(done) = decode(&egg.config.startup)?
+        .get("done")
+        .filter(|done| !is_empty(done))
+    {
+        warnings.push(format!(
+            "waitress doesn't wait for the server to print {} before considering it started",
+            done
+        ));
+    }
+    if !is_empty(&decode(&egg.config.logs)?) {
+        warnings.push("custom log settings aren't supported".to_string());
+    }
+
+    let variables = egg
+        .variables
+        .into_iter()
+        .map(|v| convert_variable(v, &mut warnings))
+        .collect();
+
+    let template = Template {
+        id: id.unwrap_or_else(|| slug(&egg.name)),
+        name: egg.name,
+        description: egg.description.unwrap_or_default(),
+        image,
+        resolver: None,
+        install,
+        startup,
+        stop,
+        save: None,
+        ports: vec![TemplatePort {
+            port,
+            protocol: Protocol::Tcp,
+        }],
+        files,
+        variables,
+    };
+    template
+        .validate()
+        .map_err(TemplateError::InvalidTemplate)?;
+    Ok(Import { template, warnings })
+}
+
+fn invalid(message: &str) -> TemplateError {
+    TemplateError::InvalidTemplate(message.to_string())
+}
+
+// egg variables are passed to the container, so the shell can fill them in
+fn convert_command(
+    command: &str,
+    variables: &HashSet<String>,
+    port: u16,
+) -> Result<String, TemplateError> {
+    let mut converted = String::with_capacity(command.len());
+    let mut rest = command;
+    while let Some(start) = rest.find("{{") {
+        converted.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| invalid("unclosed {{ in the startup command"))?;
+        let name = rest[start + 2..start + end].trim();
+        let name = name.strip_prefix("env.").unwrap_or(name);
+        match name {
+            "SERVER_MEMORY" => converted.push_str("{{MEMORY_MB}}"),
+            "SERVER_PORT" => converted.push_str(&port.to_string()),
+            "SERVER_IP" => converted.push_str("0.0.0.0"),
+            name if variables.contains(name) => converted.push_str(&format!("${{{}}}", name)),
+            name => {
+                return Err(TemplateError::InvalidTemplate(format!(
+                    "the startup command uses {{{{{}}}}}, which isn't supported",
+                    name
+                )))
+            }
+        }
+        rest = &rest[start + end + 2..];
+    }
+    converted.push_str(rest);
+    Ok(converted)
+}
+
+fn convert_files(
+    files: &Value,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<ConfigFile>, TemplateError> {
+    let Value::Object(files) = decode(files)? else {
+        return Ok(Vec::new());
+    };
+
+    let mut converted = Vec::new();
+    for (path, config) in files {
+        let parser = config
+            .get("parser")
+            .and_then(Value::as_str)
+            .unwrap_or("file");
+        match parser {
+            "properties" => converted.push(ConfigFile {
+                path: path.clone(),
+                format: ConfigFormat::Properties,
+            }),
+            parser => {
+                warnings.push(format!(
+                    "{} uses the {} parser, which isn't supported",
+                    path, parser
+                ));
+                continue;
+            }
+        }
+        if config.get("find").is_some_and(|find| !is_empty(find)) {
+            warnings.push(format!(
+                "the values pterodactyl writes to {} on startup aren't applied",
+                path
+            ));
+        }
+    }
+    Ok(converted)
+}
+
+fn convert_variable(variable: EggVariable, warnings: &mut Vec<String>) -> TemplateVariable {
+    let name = variable.env_variable;
+    if variable.user_viewable == Some(false) || variable.user_editable == Some(false) {
+        warnings.push(format!(
+            "{} is hidden or read-only in pterodactyl, but can be changed in waitress",
+            name
+        ));
+    }
+
+    let mut rules = VariableRules::default();
+    let mut required = false;
+    for rule in variable.rules.as_deref().unwrap_or_default().split('|') {
+        let (rule, argument) = rule.split_once(':').unwrap_or((rule, ""));
+        let number = |s: &str| s.trim().parse::<f64>().ok();
+        match rule.trim() {
+            "" | "sometimes" | "present" => {}
+            "required" => required = true,
+            "nullable" => rules.nullable = true,
+            "string" => rules.kind = VariableKind::String,
+            "numeric" => rules.kind = VariableKind::Number,
+            "integer" | "int" => rules.kind = VariableKind::Integer,
+            "boolean" | "bool" => rules.kind = VariableKind::Boolean,
+            "min" => rules.min = number(argument),
+            "max" => rules.max = number(argument),
+            "size" => {
+                rules.min = number(argument);
+                rules.max = rules.min;
+            }
+            "between" => {
+                let (min, max) = argument.split_once(',').unwrap_or((argument, ""));
+                rules.min = number(min);
+                rules.max = number(max);
+            }
+            "in" => rules.one_of = argument.split(',').map(str::to_string).collect(),
+            "alpha" => rules.pattern = Some("^[a-zA-Z]+$".to_string()),
+            "alpha_num" => rules.pattern = Some("^[a-zA-Z0-9]+$".to_string()),
+            "alpha_dash" => rules.pattern = Some("^[a-zA-Z0-9_-]+$".to_string()),
+            "regex" => match convert_regex(argument) {
+                Some(pattern) => rules.pattern = Some(pattern),
+                None => warnings.push(format!(
+                    "{}: the pattern {} isn't supported",
+                    name, argument
+                )),
+            },
+            rule => warnings.push(format!("{}: the {} rule isn't supported", name, rule)),
+        }
+    }
+
+    let default = variable.default_value.unwrap_or_default();
+    let description = match variable.description.filter(|d| !d.trim().is_empty()) {
+        Some(description) => format!("{} - {}", variable.name, description),
+        None => variable.name,
+    };
+    TemplateVariable {
+        name,
+        description,
+        // pterodactyl makes people fill in required variables without a
+        // default when creating the server
+        default: (!required || !default.is_empty()).then_some(default),
+        rules,
+    }
+}
+
+// laravel takes PCRE patterns, like /^[\w.-]+$/i
+fn convert_regex(pattern: &str) -> Option<String> {
+    let delimiter = pattern.chars().next()?;
+    let end = pattern.rfind(delimiter).filter(|end| *end > 0)?;
+    let flags = &pattern[end + 1..];
+    let mut converted = String::new();
+    for flag in flags.chars() {
+        match flag {
+            'i' | 'm' | 's' | 'x' | 'u' => {}
+            _ => return None,
+        }
+    }
+    let flags = flags.replace('u', "");
+    if !flags.is_empty() {
+        converted.push_str(&format!("(?{})", flags));
+    }
+    converted.push_str(&pattern[delimiter.len_utf8()..end]);
+    // lookarounds and backreferences don't compile
+    Regex::new(&converted).ok()?;
+    Some(converted)
+}
+
+fn decode(value: &Value) -> Result<Value, TemplateError> {
+    match value {
+        Value::String(s) if s.trim().is_empty() => Ok(Value::Null),
+        Value::String(s) => serde_json::from_str(s)
+            .map_err(|e| TemplateError::InvalidTemplate(format!("invalid egg config: {}", e))),
+        value => Ok(value.clone()),
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.is_empty(),
+        Value::Array(a) => a.is_empty(),
+        Value::Object(o) => o.is_empty(),
+        _ => false,
+    }
+}
+
+fn slug(name: &str) -> String {
+    let slug = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>();
+    slug.split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn ordered_map<'de, D>(deserializer: D) -> Result<Vec<(String, String)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct OrderedMap;
+
+    impl<'de> Visitor<'de> for OrderedMap {
+        type Value = Vec<(String, String)>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a map of docker images")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut entries = Vec::new();
+            while let Some(entry) = map.next_entry()? {
+                entries.push(entry);
+            }
+            Ok(entries)
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E> {
+            Ok(Vec::new())
+        }
+    }
+
+    deserializer.deserialize_any(OrderedMap)
+}
@@ -0,0 +1,483 @@
+pub mod egg;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+use thiserror::Error;
+
+use crate::{
+    config::CONFIG,
+    db::startup::StartupConfig,
+    response_codes,
+    version::{manifest::VersionManifest, server::ServerError},
+};
+
+const BUILTIN: &[&str] = &[include_str!("minecraft.toml")];
+
+lazy_static! {
+    static ref TEMPLATES: RwLock<HashMap<String, Arc<Template>>> = RwLock::new(load());
+}
+
+/// Describes how to install and run a kind of game server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// The docker image, which can use variables like `openjdk:{{JAVA_VERSION}}`.
+    /// Only resolved variables and ones limited to a `one_of` list can be
+    /// used, so users can't run any image they like.
+    pub image: String,
+    /// Works out extra variables when a server is created.
+    #[serde(default)]
+    pub resolver: Option<Resolver>,
+    /// A shell script that runs in the server's folder the first time the
+    /// container starts.
+    #[serde(default)]
+    pub install: String,
+    /// The default startup command, see [`StartupConfig`].
+    pub startup: String,
+    /// Sent to the console to stop the server gracefully. Without one, the
+    /// container is sent a SIGTERM.
+    #[serde(default)]
+    pub stop: Option<String>,
+    /// How to pause saving while a running server is backed up. Without it,
+    /// running servers are archived as they are.
+    #[serde(default)]
+    pub save: Option<SaveCommands>,
+    /// The first port is the game port, bound to the port allocated to the
+    /// server. Any others are published on ports picked by docker.
+    pub ports: Vec<TemplatePort>,
+    #[serde(default)]
+    pub files: Vec<ConfigFile>,
+    /// Passed to the container as environment variables, and can be changed
+    /// in the startup settings afterwards.
+    #[serde(default)]
+    pub variables: Vec<TemplateVariable>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveCommands {
+    /// Sent to the console before archiving, to write everything to disk and
+    /// stop writing until `resume`.
+    pub pause: Vec<String>,
+    /// Sent once the archive is done, even if it failed.
+    pub resume: Vec<String>,
+    /// A line the server logs once the save finished. Without one, archiving
+    /// starts right after the commands are sent.
+    #[serde(default)]
+    pub saved: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolver {
+    /// Sets `JAR_URL` and `JAVA_VERSION` for the Minecraft `VERSION`.
+    Minecraft,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplatePort {
+    pub port: u16,
+    #[serde(default)]
+    pub protocol: Protocol,
+}
+
+impl TemplatePort {
+    /// How docker refers to the port, like `25565/tcp`.
+    pub fn docker_name(&self) -> String {
+        let protocol = match self.protocol {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+        };
+        format!("{}/{}", self.port, protocol)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Protocol {
+    #[default]
+    Tcp,
+    Udp,
+}
+
+/// A config file in the server's folder that waitress knows how to edit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigFile {
+    pub path: String,
+    pub format: ConfigFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigFormat {
+    Properties,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateVariable {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Variables without a default have to be given when creating a server.
+    #[serde(default)]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub rules: VariableRules,
+}
+
+/// What values a variable accepts. `min` and `max` limit the length of
+/// strings, and the value of numbers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VariableRules {
+    #[serde(default)]
+    pub kind: VariableKind,
+    /// Allows leaving the variable empty, whatever the other rules say.
+    #[serde(default)]
+    pub nullable: bool,
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+    /// The only values allowed, if not empty.
+    #[serde(default)]
+    pub one_of: Vec<String>,
+    /// A regular expression the value has to match, use `^` and `$` to match
+    /// all of it.
+    #[serde(default)]
+    pub pattern: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableKind {
+    #[default]
+    String,
+    Number,
+    Integer,
+    Boolean,
+}
+
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    #[error("Template not found: {0}")]
+    TemplateNotFound(String),
+    #[error("Missing variable: {0}")]
+    MissingVariable(String),
+    #[error("Unknown variable: {0}")]
+    UnknownVariable(String),
+    #[error("Invalid value for {0}: {1}")]
+    InvalidVariable(String, String),
+    #[error("Invalid template: {0}")]
+    InvalidTemplate(String),
+    #[error("A template with the id {0} already exists")]
+    TemplateExists(String),
+    #[error("Filesystem error: {0}")]
+    FilesystemError(#[from] std::io::Error),
+    #[error("Version not found")]
+    VersionNotFound,
+    #[error("Mojang API error: {0}")]
+    VersionError(#[from] reqwest::Error),
+    #[error("Server error: {0}")]
+    ServerInfoError(#[from] ServerError),
+}
+
+response_codes!(TemplateError {
+    TemplateNotFound(NOT_FOUND),
+    MissingVariable(BAD_REQUEST),
+    UnknownVariable(BAD_REQUEST),
+    InvalidVariable(BAD_REQUEST),
+    InvalidTemplate(BAD_REQUEST),
+    TemplateExists(CONFLICT),
+    FilesystemError(INTERNAL_SERVER_ERROR),
+    VersionNotFound(NOT_FOUND),
+    VersionError(INTERNAL_SERVER_ERROR),
+    ServerInfoError(INTERNAL_SERVER_ERROR),
+});
+
+pub fn get(id: &str) -> Option<Arc<Template>> {
+    TEMPLATES.read().unwrap().get(id).cloned()
+}
+
+pub fn all() -> Vec<Template> {
+    let templates = TEMPLATES.read().unwrap();
+    let mut templates = templates
+        .values()
+        .map(|t| t.as_ref().clone())
+        .collect::<Vec<_>>();
+    templates.sort_by(|a, b| a.id.cmp(&b.id));
+    templates
+}
+
+/// Saves a template to the template directory and makes it available right
+/// away. Servers already using a replaced template pick up the changes the
+/// next time their container is recreated.
+pub async fn add(template: Template, replace: bool) -> Result<(), TemplateError> {
+    if !replace && get(&template.id).is_some() {
+        return Err(TemplateError::TemplateExists(template.id));
+    }
+
+    tokio::fs::create_dir_all(&CONFIG.template_dir).await?;
+    let path = PathBuf::from(&CONFIG.template_dir).join(format!("{}.json", template.id));
+    let json = serde_json::to_string_pretty(&template).expect("templates serialize");
+    tokio::fs::write(path, json).await?;
+
+    TEMPLATES
+        .write()
+        .unwrap()
+        .insert(template.id.clone(), Arc::new(template));
+    Ok(())
+}
+
+// templates in the template directory replace built-in ones with the same id
+fn load() -> HashMap<String, Arc<Template>> {
+    let mut templates = HashMap::new();
+    for source in BUILTIN {
+        let template = Template::parse(source, "toml").expect("built-in templates are valid");
+        templates.insert(template.id.clone(), Arc::new(template));
+    }
+
+    let entries = match fs::read_dir(&CONFIG.template_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return templates,
+        Err(e) => {
+            log::error!("failed to read {}: {}", CONFIG.template_dir, e);
+            return templates;
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match load_file(&path) {
+            Ok(Some(template)) => {
+                log::info!("loaded template {} from {}", template.id, path.display());
+                templates.insert(template.id.clone(), Arc::new(template));
+            }
+            Ok(None) => {}
+            Err(e) => log::error!("invalid template {}: {}", path.display(), e),
+        }
+    }
+    templates
+}
+
+fn load_file(path: &Path) -> Result<Option<Template>, String> {
+    let Some(format @ ("toml" | "json")) = path.extension().and_then(|e| e.to_str()) else {
+        return Ok(None);
+    };
+    let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    Template::parse(&source, format).map(Some)
+}
+
+impl Template {
+    /// Parses and checks a template written in `format`, either `toml` or
+    /// `json`.
+    pub fn parse(source: &str, format: &str) -> Result<Self, String> {
+        let template: Template = match format {
+            "toml" => toml::from_str(source).map_err(|e| e.to_string())?,
+            "json" => serde_json::from_str(source).map_err(|e| e.to_string())?,
+            _ => return Err(format!("unsupported format {}", format)),
+        };
+        template.validate()?;
+        Ok(template)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.id.is_empty()
+            || !self
+                .id
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return Err("the id can only have lowercase letters, digits and dashes".to_string());
+        }
+        if self.ports.is_empty() {
+            return Err("a template needs at least one port".to_string());
+        }
+
+        for file in &self.files {
+            if file.path.starts_with('/') || file.path.split('/').any(|part| part == "..") {
+                return Err(format!("{} is outside of the server's folder", file.path));
+            }
+        }
+        for variable in &self.variables {
+            if let Some(pattern) = &variable.rules.pattern {
+                Regex::new(pattern).map_err(|e| format!("{}: {}", variable.name, e))?;
+            }
+        }
+        if self.resolver == Some(Resolver::Minecraft)
+            && !self.variables.iter().any(|v| v.name == "VERSION")
+        {
+            return Err("the minecraft resolver needs a VERSION variable".to_string());
+        }
+
+        // every variable has to be known, or servers couldn't be created
+        let placeholders = self
+            .variables
+            .iter()
+            .map(|v| v.name.clone())
+            .chain(self.resolved_names().map(str::to_string))
+            .map(|name| (name, String::new()))
+            .collect::<BTreeMap<_, _>>();
+        self.image(&placeholders).map_err(|e| e.to_string())?;
+        for name in self.image_names() {
+            let fixed = self.resolved_names().any(|resolved| resolved == name)
+                || self
+                    .variables
+                    .iter()
+                    .any(|v| v.name == name && !v.rules.one_of.is_empty() && !v.rules.nullable);
+            if !fixed {
+                return Err(format!(
+                    "the image can only use {} if it is limited to a list of values",
+                    name
+                ));
+            }
+        }
+
+        let startup = StartupConfig {
+            command: self.startup.clone(),
+            env: Json(placeholders),
+            ..Default::default()
+        };
+        startup.validate().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn resolved_names(&self) -> impl Iterator<Item = &'static str> {
+        let names: &[&str] = match self.resolver {
+            Some(Resolver::Minecraft) => &["JAR_URL", "JAVA_VERSION"],
+            None => &[],
+        };
+        names.iter().copied()
+    }
+
+    /// Checks the variables given for a new server against the template,
+    /// fills in the defaults and runs the resolver.
+    pub async fn resolve(
+        &self,
+        mut given: HashMap<String, String>,
+    ) -> Result<BTreeMap<String, String>, TemplateError> {
+        let mut variables = BTreeMap::new();
+        for variable in &self.variables {
+            let value = given
+                .remove(&variable.name)
+                .or_else(|| variable.default.clone())
+                .ok_or_else(|| TemplateError::MissingVariable(variable.name.clone()))?;
+            variable
+                .rules
+                .check(&value)
+                .map_err(|reason| TemplateError::InvalidVariable(variable.name.clone(), reason))?;
+            variables.insert(variable.name.clone(), value);
+        }
+        if let Some(name) = given.into_keys().next() {
+            return Err(TemplateError::UnknownVariable(name));
+        }
+
+        match self.resolver {
+            Some(Resolver::Minecraft) => {
+                let manifest = VersionManifest::new().await?;
+                let version = manifest
+                    .get_version(variables.get("VERSION").cloned().unwrap_or_default())
+                    .ok_or(TemplateError::VersionNotFound)?;
+                let server_info = version.get_server_info().await?;
+                variables.insert("JAR_URL".to_string(), server_info.url);
+                variables.insert(
+                    "JAVA_VERSION".to_string(),
+                    server_info.java_version.to_string(),
+                );
+            }
+            None => {}
+        }
+        Ok(variables)
+    }
+
+    // the variables between `{{` and `}}` in the image
+    fn image_names(&self) -> impl Iterator<Item = &str> {
+        self.image
+            .split("{{")
+            .skip(1)
+            .filter_map(|part| part.split_once("}}"))
+            .map(|(name, _)| name.trim())
+    }
+
+    /// The docker image for a server with these variables.
+    pub fn image(&self, variables: &BTreeMap<String, String>) -> Result<String, TemplateError> {
+        let mut image = String::with_capacity(self.image.len());
+        let mut rest = self.image.as_str();
+        while let Some(start) = rest.find("{{") {
+            image.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| TemplateError::UnknownVariable(rest[start..].to_string()))?;
+            let name = rest[start + 2..start + end].trim();
+            let value = variables
+                .get(name)
+                .ok_or_else(|| TemplateError::UnknownVariable(name.to_string()))?;
+            image.push_str(value);
+            rest = &rest[start + end + 2..];
+        }
+        image.push_str(rest);
+        Ok(image)
+    }
+
+    /// The file of the given format, if the template has one.
+    pub fn file(&self, format: ConfigFormat) -> Option<&ConfigFile> {
+        self.files.iter().find(|f| f.format == format)
+    }
+}
+
+impl VariableRules {
+    /// Checks a value, returning why it isn't allowed.
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        if self.nullable && value.is_empty() {
+            return Ok(());
+        }
+        if !self.one_of.is_empty() && !self.one_of.iter().any(|v| v == value) {
+            return Err(format!("must be one of {}", self.one_of.join(", ")));
+        }
+
+        let size = match self.kind {
+            VariableKind::String => value.chars().count() as f64,
+            VariableKind::Number => value
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .ok_or("must be a number")?,
+            VariableKind::Integer => {
+                value.parse::<i64>().map_err(|_| "must be a whole number")? as f64
+            }
+            VariableKind::Boolean => {
+                if !matches!(value, "true" | "false" | "1" | "0") {
+                    return Err("must be true or false".to_string());
+                }
+                return Ok(());
+            }
+        };
+        let unit = match self.kind {
+            VariableKind::String => " characters",
+            _ => "",
+        };
+        if let Some(min) = self.min.filter(|min| size < *min) {
+            return Err(format!("must be at least {}{}", min, unit));
+        }
+        if let Some(max) = self.max.filter(|max| size > *max) {
+            return Err(format!("must be at most {}{}", max, unit));
+        }
+
+        if let Some(pattern) = &self.pattern {
+            // checked when the template was loaded
+            let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+            if !regex.is_match(value) {
+                return Err(format!("must match {}", pattern));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    fs::Metadata,
+    io::{self, ErrorKind},
+    path::Path,
+};
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+// Servers can change anything in their volume while waitress is using it, so
+// any file or directory there could be swapped for a symlink to anywhere on
+// the host.
+
+/// Opens a regular file in `volume`, without following symlinks out of it.
+pub async fn open(volume: impl AsRef<Path>, path: impl AsRef<Path>) -> io::Result<File> {
+    let path = path.as_ref();
+    if !fs::symlink_metadata(path).await?.is_file() {
+        return Err(not_a_file(path));
+    }
+    let file = File::open(path).await?;
+    // the opened file has to be the one that's in the volume now, in case
+    // anything was swapped while it was being opened
+    let real_path = fs::canonicalize(path).await?;
+    if !real_path.starts_with(fs::canonicalize(volume).await?) {
+        return Err(not_a_file(path));
+    }
+    let (opened, real) = (
+        file.metadata().await?,
+        fs::symlink_metadata(&real_path).await?,
+    );
+    if !real.is_file() || !same_file(&opened, &real) {
+        return Err(not_a_file(path));
+    }
+    Ok(file)
+}
+
+pub async fn read_to_string(
+    volume: impl AsRef<Path>,
+    path: impl AsRef<Path>,
+) -> io::Result<String> {
+    let mut contents = String::new();
+    open(volume, path)
+        .await?
+        .read_to_string(&mut contents)
+        .await?;
+    Ok(contents)
+}
+
+/// Replaces a file in `volume` through a temporary file, so a failed write
+/// can't truncate it. A symlink at `path` is replaced rather than followed.
+pub async fn write(
+    volume: impl AsRef<Path>,
+    path: impl AsRef<Path>,
+    contents: &[u8],
+) -> io::Result<()> {
+    let path = path.as_ref();
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(not_a_file(path));
+    };
+    if !fs::canonicalize(parent)
+        .await?
+        .starts_with(fs::canonicalize(volume).await?)
+    {
+        return Err(not_a_file(path));
+    }
+    match fs::symlink_metadata(path).await {
+        Ok(metadata) if !metadata.is_file() && !metadata.is_symlink() => {
+            return Err(not_a_file(path))
+        }
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+
+    let mut tmp = name.to_os_string();
+    tmp.push(".waitress-tmp");
+    let tmp = parent.join(tmp);
+    // left over from a failed write, or put there by the server
+    match fs::remove_file(&tmp).await {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    // doesn't follow a symlink put there in the meantime
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&tmp)
+        .await?;
+    file.write_all(contents).await?;
+    file.sync_all().await?;
+    fs::rename(&tmp, path).await
+}
+
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+// file ids aren't exposed on stable elsewhere, the other checks still apply
+#[cfg(not(unix))]
+fn same_file(_: &Metadata, _: &Metadata) -> bool {
+    true
+}
+
+fn not_a_file(path: &Path) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "{} isn't a regular file in the server's volume",
+            path.display()
+        ),
+    )
+}
@@ -0,0 +1,171 @@
+use bollard::{container::InspectContainerOptions, Docker};
+use chrono::{DateTime, Utc};
+use std::{
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime},
+};
+use tokio::{
+    fs,
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        crash::{Crash, CrashError, CrashOutcome, RestartPolicy},
+        server::{Server, ServerStatus},
+    },
+    volume,
+};
+
+// the server writes its reports, so they could be any size
+const MAX_REPORT_BYTES: u64 = 1024 * 1024;
+const TRUNCATED: &[u8] = b"\n[waitress: the rest of the report was cut off]\n";
+
+/// Decides whether a server exiting was a crash, records it and restarts the
+/// server according to its restart policy.
+pub async fn handle_exit(
+    server_id: Uuid,
+    exit_code: Option<i64>,
+    expected: bool,
+    pool: &sqlx::PgPool,
+) -> Result<(), CrashError> {
+    if expected {
+        log::info!("{} stopped", server_id);
+        return Ok(());
+    }
+    let exited_at = Instant::now();
+    // gone with the server
+    let Some(server) = Server::from_id(server_id, pool).await else {
+        return Ok(());
+    };
+
+    let report = find_report(&server).await;
+    // `stop` typed into the console exits cleanly without a crash report
+    if exit_code == Some(0) && report.is_none() {
+        log::info!("{} was stopped from its console", server_id);
+        return Ok(());
+    }
+    log::warn!("{} crashed with exit code {:?}", server_id, exit_code);
+
+    let id = Uuid::new_v4();
+    let report_name = match report {
+        Some((mut report, name)) => {
+            fs::create_dir_all(Crash::server_dir(server_id)).await?;
+            copy_report(&mut report, &Crash::report_path(server_id, id)).await?;
+            Some(name)
+        }
+        None => None,
+    };
+
+    let policy = RestartPolicy::get(server_id, pool).await?;
+    let crashes = Crash::count_recent(server_id, policy.window_minutes, pool).await? + 1;
+    let delay = policy.restart_delay(crashes);
+    let outcome = match delay {
+        Some(_) => CrashOutcome::Restarting,
+        None if policy.enabled => CrashOutcome::CrashLoop,
+        None => CrashOutcome::NotRestarted,
+    };
+    Crash::insert(id, server_id, exit_code, report_name, outcome, pool).await?;
+
+    let Some(delay) = delay else {
+        if outcome == CrashOutcome::CrashLoop {
+            log::error!(
+                "{} crashed {} times in {} minutes, not restarting it",
+                server_id,
+                crashes,
+                policy.window_minutes
+            );
+        }
+        return Ok(());
+    };
+
+    log::info!("restarting {} in {}s", server_id, delay.as_secs());
+    tokio::time::sleep(delay).await;
+    // it might have been started, stopped or deleted while we waited
+    let Some(server) = Server::from_id(server_id, pool).await else {
+        return Ok(());
+    };
+    if !server.should_run
+        || matches!(
+            server.status,
+            ServerStatus::Sleeping | ServerStatus::Deleting
+        )
+        || super::stop_requested_since(server_id, exited_at)
+    {
+        log::info!(
+            "not restarting {}, it was stopped in the meantime",
+            server_id
+        );
+        return Ok(());
+    }
+    if !server.is_running().await.unwrap_or(true) {
+        if let Err(e) = server.start(pool).await {
+            log::error!("failed to restart {}: {}", server_id, e);
+        }
+    }
+    Ok(())
+}
+
+/// Copies up to `MAX_REPORT_BYTES` of a crash report, noting at the end if
+/// there was more.
+pub async fn copy_report<R: AsyncRead + Unpin>(report: &mut R, dest: &Path) -> std::io::Result<()> {
+    let mut file = fs::File::create(dest).await?;
+    let copied = tokio::io::copy(&mut report.take(MAX_REPORT_BYTES), &mut file).await?;
+    if copied == MAX_REPORT_BYTES && report.read(&mut [0]).await? > 0 {
+        file.write_all(TRUNCATED).await?;
+    }
+    file.flush().await
+}
+
+// the newest file in the server's crash-reports directory, if it was written
+// since the container last started. the server can write there, so anything
+// that isn't a regular file in its volume is skipped
+async fn find_report(server: &Server) -> Option<(fs::File, String)> {
+    let started_at = started_at(server).await?;
+    let volume = PathBuf::from(server.volume_path());
+    let dir = volume.join("crash-reports");
+    if !fs::symlink_metadata(&dir).await.ok()?.is_dir() {
+        return None;
+    }
+    let mut entries = fs::read_dir(&dir).await.ok()?;
+
+    let mut newest: Option<(SystemTime, PathBuf)> = None;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        // doesn't follow symlinks
+        let Ok(metadata) = entry.metadata().await else {
+            continue;
+        };
+        let Ok(modified) = metadata.modified() else {
+            continue;
+        };
+        if !metadata.is_file()
+            || modified < started_at
+            || newest.as_ref().is_some_and(|(t, _)| *t >= modified)
+        {
+            continue;
+        }
+        newest = Some((modified, entry.path()));
+    }
+
+    let (_, path) = newest?;
+    let name = path.file_name()?.to_string_lossy().to_string();
+    match volume::open(&volume, &path).await {
+        Ok(file) => Some((file, name)),
+        Err(e) => {
+            log::warn!("ignoring crash report {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+async fn started_at(server: &Server) -> Option<SystemTime> {
+    let docker = Docker::connect_with_local_defaults().ok()?;
+    let container = docker
+        .inspect_container(&server.container_name(), None::<InspectContainerOptions>)
+        .await
+        .ok()?;
+    let started_at = container.state?.started_at?;
+    let started_at = DateTime::parse_from_rfc3339(&started_at).ok()?;
+    Some(started_at.with_timezone(&Utc).into())
+}
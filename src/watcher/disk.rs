@@ -0,0 +1,59 @@
+use sqlx::PgPool;
+use std::{collections::HashSet, time::Duration};
+use thiserror::Error;
+
+use crate::db::{
+    quota::{Quota, QuotaExceeded},
+    server::{Server, ServerStatus},
+};
+
+// walking every volume is slow, and a few minutes over the quota is fine
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Error)]
+enum DiskCheckError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("{0}")]
+    QuotaExceeded(#[from] QuotaExceeded),
+}
+
+pub async fn run(pool: PgPool) {
+    let mut interval = tokio::time::interval(DISK_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = check(&pool).await {
+            log::error!("failed to check disk usage: {}", e);
+        }
+    }
+}
+
+/// Stops the running servers of users who use more disk space than their
+/// quota allows. They can't be started again until enough is freed.
+pub async fn check(pool: &PgPool) -> Result<(), sqlx::Error> {
+    let running = Server::all(pool)
+        .await?
+        .into_iter()
+        .filter(|s| s.status == ServerStatus::Running)
+        .collect::<Vec<_>>();
+    let owners = running.iter().map(|s| s.owner).collect::<HashSet<_>>();
+
+    for owner in owners {
+        let exceeded = match Quota::check_disk::<DiskCheckError>(owner, pool).await {
+            Ok(()) => continue,
+            Err(DiskCheckError::Database(e)) => return Err(e),
+            Err(DiskCheckError::QuotaExceeded(e)) => e,
+        };
+        for server in running.iter().filter(|s| s.owner == owner) {
+            log::warn!("stopping {}, its owner is over their quota", server.id);
+            if let Err(e) = server.stop().await {
+                log::error!("failed to stop {}: {}", server.id, e);
+                continue;
+            }
+            Server::set_should_run(server.id, false, pool).await?;
+            Server::flag(server.id, ServerStatus::Stopped, exceeded.to_string(), pool).await?;
+        }
+    }
+
+    Ok(())
+}
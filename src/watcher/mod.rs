@@ -0,0 +1,153 @@
+pub mod crash;
+pub mod disk;
+pub mod reconcile;
+
+use actix_web::rt;
+use bollard::{system::EventsOptions, Docker};
+use futures::StreamExt as _;
+use lazy_static::lazy_static;
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+use crate::db::server::{Server, ServerStatus};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+// a stop that hasn't shown up as an event by now never will
+const EXPECTED_STOP_TTL: Duration = Duration::from_secs(5 * 60);
+
+lazy_static! {
+    static ref EXPECTED_STOPS: Mutex<HashMap<Uuid, Instant>> = Mutex::new(HashMap::new());
+    // unlike expected stops these aren't used up by events, a server that
+    // already exited has none to come
+    static ref STOP_REQUESTS: Mutex<HashMap<Uuid, Instant>> = Mutex::new(HashMap::new());
+}
+
+/// Marks the server's next exit as requested by waitress, so that it isn't
+/// treated as a crash.
+pub fn expect_stop(server_id: Uuid) {
+    EXPECTED_STOPS
+        .lock()
+        .unwrap()
+        .insert(server_id, Instant::now());
+    stop_requested(server_id);
+}
+
+/// Records that waitress wants the server stopped, even if it isn't running,
+/// so that a pending crash restart doesn't start it again.
+pub fn stop_requested(server_id: Uuid) {
+    STOP_REQUESTS
+        .lock()
+        .unwrap()
+        .insert(server_id, Instant::now());
+}
+
+fn stop_requested_since(server_id: Uuid, since: Instant) -> bool {
+    STOP_REQUESTS
+        .lock()
+        .unwrap()
+        .get(&server_id)
+        .is_some_and(|at| *at >= since)
+}
+
+// container names are `waitress-<server id>`
+fn server_id(container_name: &str) -> Option<Uuid> {
+    container_name.strip_prefix("waitress-")?.parse().ok()
+}
+
+fn take_expected_stop(server_id: Uuid) -> bool {
+    EXPECTED_STOPS
+        .lock()
+        .unwrap()
+        .remove(&server_id)
+        .is_some_and(|at| at.elapsed() < EXPECTED_STOP_TTL)
+}
+
+/// Follows docker's event stream for waitress containers, keeping the servers'
+/// state up to date. Reconnects if the stream drops.
+pub async fn run(pool: PgPool) {
+    let mut since = None;
+    loop {
+        if let Err(e) = watch(&pool, &mut since).await {
+            log::error!("docker event stream failed: {}", e);
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn watch(pool: &PgPool, since: &mut Option<i64>) -> Result<(), bollard::errors::Error> {
+    let docker = Docker::connect_with_local_defaults()?;
+    let filters = HashMap::from([
+        ("type".to_string(), vec!["container".to_string()]),
+        (
+            "event".to_string(),
+            vec![
+                "start".to_string(),
+                "die".to_string(),
+                "destroy".to_string(),
+            ],
+        ),
+    ]);
+    // picking up from the last event means nothing is missed while reconnecting
+    let mut events = docker.events(Some(EventsOptions {
+        since: since.map(|s| s.to_string()),
+        until: None,
+        filters,
+    }));
+
+    while let Some(event) = events.next().await {
+        let event = event?;
+        if let Some(time) = event.time {
+            *since = Some(time + 1);
+        }
+        let Some(attributes) = event.actor.and_then(|a| a.attributes) else {
+            continue;
+        };
+        let Some(server_id) = attributes.get("name").and_then(|n| server_id(n)) else {
+            continue;
+        };
+
+        let status = match event.action.as_deref() {
+            Some("start") => ServerStatus::Running,
+            Some("die") => ServerStatus::Stopped,
+            Some("destroy") => ServerStatus::Missing,
+            _ => continue,
+        };
+        // a deleted server's row is already gone, so this does nothing
+        let updated = match status {
+            ServerStatus::Stopped => Server::mark_stopped(server_id, pool).await,
+            status => Server::set_status(server_id, status, pool).await,
+        };
+        if let Err(e) = updated {
+            log::error!("failed to update status of {}: {}", server_id, e);
+        }
+
+        if status == ServerStatus::Missing {
+            let docker = docker.clone();
+            let pool = pool.clone();
+            rt::spawn(async move {
+                let Some(server) = Server::from_id(server_id, &pool).await else {
+                    return;
+                };
+                if let Err(e) = reconcile::recreate(&server, &docker, &pool).await {
+                    log::error!("failed to recreate container for {}: {}", server_id, e);
+                }
+            });
+        } else if status == ServerStatus::Stopped {
+            let exit_code = attributes.get("exitCode").and_then(|c| c.parse().ok());
+            let expected = take_expected_stop(server_id);
+            let pool = pool.clone();
+            // restarts wait out their backoff, which shouldn't hold up other events
+            rt::spawn(async move {
+                if let Err(e) = crash::handle_exit(server_id, exit_code, expected, &pool).await {
+                    log::error!("failed to handle exit of {}: {}", server_id, e);
+                }
+            });
+        }
+    }
+    Ok(())
+}
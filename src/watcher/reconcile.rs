@@ -0,0 +1,153 @@
+use bollard::{container::ListContainersOptions, volume::ListVolumesOptions, Docker};
+use sqlx::PgPool;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::fs;
+
+use super::server_id;
+use crate::db::{
+    orphan::OrphanKind,
+    server::{Server, ServerStatus},
+};
+
+// events keep things in sync between passes, this catches anything they miss
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Error)]
+pub enum ReconcileError {
+    #[error("Docker error: {0}")]
+    Docker(#[from] bollard::errors::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("Filesystem error: {0}")]
+    Filesystem(#[from] std::io::Error),
+}
+
+pub async fn run(pool: PgPool) {
+    let mut interval = tokio::time::interval(RECONCILE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = reconcile(&pool).await {
+            log::error!("failed to reconcile servers: {}", e);
+        }
+    }
+}
+
+/// Compares every server with its container, recording the container's state
+/// and recreating containers that have gone missing. Containers and volumes
+/// without a server are flagged as orphans.
+pub async fn reconcile(pool: &PgPool) -> Result<(), ReconcileError> {
+    let docker = Docker::connect_with_local_defaults()?;
+    let servers = Server::all(pool).await?;
+
+    // the name filter matches substrings, so this still needs checking below
+    let containers = docker
+        .list_containers(Some(ListContainersOptions {
+            all: true,
+            filters: HashMap::from([("name", vec!["waitress-"])]),
+            ..Default::default()
+        }))
+        .await?;
+    let containers = containers
+        .into_iter()
+        .filter_map(|c| {
+            let name = c.names?.into_iter().next()?;
+            let name = name.trim_start_matches('/').to_string();
+            server_id(&name)?;
+            Some((name, c.state.as_deref() == Some("running")))
+        })
+        .collect::<HashMap<_, _>>();
+
+    for server in &servers {
+        let status = match containers.get(&server.container_name()) {
+            Some(true) => ServerStatus::Running,
+            Some(false) => ServerStatus::Stopped,
+            None => ServerStatus::Missing,
+        };
+        match server.status {
+            ServerStatus::Provisioning | ServerStatus::NeedsReview | ServerStatus::Deleting => {
+                continue
+            }
+            // errored servers are left alone until their container is back
+            ServerStatus::Errored if status == ServerStatus::Missing => continue,
+            ServerStatus::Sleeping if status == ServerStatus::Stopped => continue,
+            _ => {}
+        }
+        if status != server.status {
+            Server::set_status(server.id, status, pool).await?;
+        }
+        if status == ServerStatus::Missing {
+            recreate(server, &docker, pool).await?;
+        }
+    }
+
+    let known = servers
+        .iter()
+        .map(|s| s.container_name())
+        .collect::<HashSet<_>>();
+    let orphaned = |names: Vec<String>| {
+        names
+            .into_iter()
+            .filter(|n| server_id(n).is_some() && !known.contains(n))
+            .collect::<Vec<_>>()
+    };
+
+    let volumes = docker
+        .list_volumes(Some(ListVolumesOptions {
+            filters: HashMap::from([("name", vec!["waitress-"])]),
+        }))
+        .await?
+        .volumes
+        .unwrap_or_default()
+        .into_iter()
+        .map(|v| v.name)
+        .collect();
+
+    let mut directories = Vec::new();
+    match fs::read_dir("volumes").await {
+        Ok(mut entries) => {
+            while let Some(entry) = entries.next_entry().await? {
+                directories.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    for (kind, names) in [
+        (
+            OrphanKind::Container,
+            orphaned(containers.into_keys().collect()),
+        ),
+        (OrphanKind::Volume, orphaned(volumes)),
+        (OrphanKind::Directory, orphaned(directories)),
+    ] {
+        for name in kind.sync(&names, pool).await? {
+            log::warn!("found orphaned {:?} {} with no server", kind, name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Recreates the container of a server that should be running. Servers that
+/// were stopped on purpose are left for whoever starts them next.
+pub async fn recreate(
+    server: &Server,
+    docker: &Docker,
+    pool: &PgPool,
+) -> Result<(), ReconcileError> {
+    // the server might have been stopped or deleted since it was fetched
+    let Some(server) = Server::from_id(server.id, pool).await else {
+        return Ok(());
+    };
+    if !server.should_run || server.status == ServerStatus::Deleting {
+        return Ok(());
+    }
+    log::info!("recreating container for {}", server.id);
+    server.restore_or_flag(docker, pool).await?;
+    Ok(())
+}
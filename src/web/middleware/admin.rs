@@ -0,0 +1,32 @@
+use crate::{db::user::User, middleware_error};
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    Error, HttpMessage,
+};
+
+// has to run after `authenticated`, which puts the user in the extensions
+fn admin_middleware(req: &ServiceRequest) -> Result<(), Error> {
+    let extensions = req.extensions();
+    let user = extensions
+        .get::<User>()
+        .ok_or_else(|| middleware_error!(ErrorInternalServerError, "User is missing"))?;
+    if !user.is_admin() {
+        return Err(middleware_error!(
+            ErrorForbidden,
+            "You need to be an admin to do this"
+        ));
+    }
+    Ok(())
+}
+
+pub async fn admin(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Err(e) = admin_middleware(&req) {
+        return Ok(req.error_response(e));
+    }
+    next.call(req).await.map(|res| res.map_into_boxed_body())
+}
@@ -1,5 +1,5 @@
 use crate::{
-    db::{user::User, Database},
+    db::{api_key::ApiKey, session::Session, Database},
     middleware_error,
 };
 use actix_web::{
@@ -33,12 +33,35 @@ async fn authenticated_middleware(req: &ServiceRequest) -> Result<(), Error> {
         .to_str()
         .map_err(|_| middleware_error!(ErrorUnauthorized, "Invalid Authorization header"))?;
 
-    let user = User::from_token(auth_header, &data.pool)
+    if ApiKey::is_api_key(auth_header) {
+        // keys are scoped to servers, so they're only good for server routes
+        let regex = Regex::new(
+            r"^/api/server/[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}(/.*)?$",
+        )
+        .unwrap();
+        if !regex.is_match(req.path()) {
+            return Err(middleware_error!(
+                ErrorForbidden,
+                "API keys can only be used on server routes"
+            ));
+        }
+        let (key, user) = ApiKey::authenticate(auth_header, &data.pool)
+            .await
+            .map_err(|err| middleware_error!(ErrorUnauthorized, "Invalid token: {}", err))?;
+
+        let mut extensions = req.extensions_mut();
+        extensions.insert(user);
+        extensions.insert(key);
+        return Ok(());
+    }
+
+    let (session, user) = Session::from_token(auth_header, &data.pool)
         .await
         .map_err(|err| middleware_error!(ErrorUnauthorized, "Invalid token: {}", err))?;
 
     let mut extensions = req.extensions_mut();
     extensions.insert(user);
+    extensions.insert(session);
     Ok(())
 }
 
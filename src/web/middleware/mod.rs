@@ -1,5 +1,8 @@
+pub mod admin;
 pub mod auth;
-pub mod owns_server;
+pub mod rate_limit;
+pub mod requires;
+pub mod server_access;
 
 #[macro_export]
 macro_rules! middleware_error {
@@ -0,0 +1,22 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    Error,
+};
+
+use crate::web::rate_limit::{client_ip, ip_key, AUTH_REQUESTS};
+
+/// Limits how often an IP can hit the login and signup routes, which are
+/// expensive thanks to argon2.
+pub async fn rate_limited(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Some(ip) = client_ip(req.request()) {
+        if let Err(e) = AUTH_REQUESTS.check(ip_key(ip)) {
+            return Ok(req.error_response(e));
+        }
+    }
+    next.call(req).await.map(|res| res.map_into_boxed_body())
+}
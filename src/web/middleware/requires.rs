@@ -0,0 +1,87 @@
+use crate::{
+    db::member::{Permission, ServerAccess},
+    middleware_error,
+};
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage,
+};
+use futures::future::LocalBoxFuture;
+use std::future::{ready, Ready};
+
+/// Declares what a server route needs, like
+/// `#[get("/startup", wrap = "Requires::Permission(Permission::Settings)")]`.
+/// Has to run inside `server_access`, which works out what the user can do.
+#[derive(Debug, Clone, Copy)]
+pub enum Requires {
+    /// Only the owner and admins.
+    Owner,
+    Permission(Permission),
+}
+
+impl Requires {
+    fn check(&self, req: &ServiceRequest) -> Result<(), Error> {
+        let extensions = req.extensions();
+        let access = extensions.get::<ServerAccess>().ok_or_else(|| {
+            middleware_error!(ErrorInternalServerError, "Server access is missing")
+        })?;
+        match self {
+            Self::Owner if *access != ServerAccess::Owner => Err(middleware_error!(
+                ErrorForbidden,
+                "Only the owner of this server can do this"
+            )),
+            Self::Permission(permission) if !access.allows(*permission) => Err(middleware_error!(
+                ErrorForbidden,
+                "You need the {:?} permission to do this",
+                permission
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Requires
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequiresMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequiresMiddleware {
+            service,
+            requires: *self,
+        }))
+    }
+}
+
+pub struct RequiresMiddleware<S> {
+    service: S,
+    requires: Requires,
+}
+
+impl<S, B> Service<ServiceRequest> for RequiresMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if let Err(e) = self.requires.check(&req) {
+            let res = req.error_response(e).map_into_right_body();
+            return Box::pin(async { Ok(res) });
+        }
+        let fut = self.service.call(req);
+        Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) })
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    db::{server::Server, user::User, Database},
+    db::{api_key::ApiKey, member::ServerAccess, server::Server, user::User, Database},
     middleware_error,
 };
 use actix_web::{
@@ -11,7 +11,7 @@ use actix_web::{
 };
 use regex::Regex;
 
-async fn owns_server_middleware(req: &ServiceRequest) -> Result<(), Error> {
+async fn server_access_middleware(req: &ServiceRequest) -> Result<(), Error> {
     let regex = Regex::new(
         r"^/api/server/[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}/ws$",
     )
@@ -20,7 +20,7 @@ async fn owns_server_middleware(req: &ServiceRequest) -> Result<(), Error> {
         return Ok(());
     }
 
-    let (server, user_id) = {
+    let (server, access) = {
         let extensions = req.extensions();
         let user = extensions
             .get::<User>()
@@ -37,30 +37,39 @@ async fn owns_server_middleware(req: &ServiceRequest) -> Result<(), Error> {
             middleware_error!(ErrorInternalServerError, "Database connection is missing")
         })?;
 
-        (
-            Server::from_id(id, &data.pool)
-                .await
-                .ok_or_else(|| middleware_error!(ErrorNotFound, "Server not found"))?,
-            user.id,
-        )
+        let server = Server::from_id(id, &data.pool)
+            .await
+            .ok_or_else(|| middleware_error!(ErrorNotFound, "Server not found"))?;
+        let access = ServerAccess::get(&server, user, &data.pool)
+            .await
+            .map_err(|e| {
+                middleware_error!(ErrorInternalServerError, "Failed to check access: {}", e)
+            })?;
+        let access = match extensions.get::<ApiKey>() {
+            Some(key) => access.and_then(|access| key.restrict(server.id, access)),
+            None => access,
+        };
+        (server, access)
     };
 
-    if server.owner != user_id {
+    // what the user is allowed to do is checked by each route with `Requires`
+    let Some(access) = access else {
         return Err(middleware_error!(
             ErrorForbidden,
-            "You do not own this server"
+            "You do not have access to this server"
         ));
-    }
+    };
     let mut extensions = req.extensions_mut();
     extensions.insert(server);
+    extensions.insert(access);
     Ok(())
 }
 
-pub async fn owns_server(
+pub async fn server_access(
     req: ServiceRequest,
     next: Next<impl MessageBody + 'static>,
 ) -> Result<ServiceResponse<impl MessageBody>, Error> {
-    if let Err(e) = owns_server_middleware(&req).await {
+    if let Err(e) = server_access_middleware(&req).await {
         return Ok(req.error_response(e));
     }
     next.call(req).await.map(|res| res.map_into_boxed_body())
@@ -1,5 +1,6 @@
 mod error;
 mod middleware;
+pub mod rate_limit;
 pub mod response;
 mod services;
 
@@ -8,6 +9,7 @@ use actix_web::web;
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api")
+            .configure(services::admin::configure)
             .configure(services::auth::configure)
             .configure(services::server::configure),
     );
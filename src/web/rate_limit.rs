@@ -0,0 +1,346 @@
+use actix_web::{
+    http::{header, StatusCode},
+    HttpRequest, HttpResponse, ResponseError,
+};
+use lazy_static::lazy_static;
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    net::IpAddr,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{config::CONFIG, web::response::ApiResponse};
+
+/// Past this many tracked keys, stale ones are cleared out.
+const PRUNE_AT: usize = 10_000;
+/// Past this many, the oldest are dropped even if they aren't stale, so
+/// memory stays bounded however many keys are thrown at it.
+pub const MAX_TRACKED: usize = 100_000;
+// usernames are far shorter, anything longer is only there to use up memory
+const MAX_USERNAME_KEY: usize = 64;
+
+lazy_static! {
+    /// Requests to the login and signup routes, per IP.
+    pub static ref AUTH_REQUESTS: RateLimiter<IpAddr> = RateLimiter::new(CONFIG.auth_rate_limit);
+    pub static ref LOGIN_ATTEMPTS: LoginAttempts = LoginAttempts::new(
+        LockoutPolicy {
+            max_failures: CONFIG.login_max_failures,
+            base: CONFIG.login_lockout,
+            max: CONFIG.login_lockout_max,
+        },
+        LockoutPolicy {
+            max_failures: CONFIG.login_ip_max_failures,
+            base: CONFIG.login_lockout,
+            max: CONFIG.login_lockout_max,
+        },
+    );
+    /// Console commands sent over websockets, per user.
+    pub static ref WS_COMMANDS: RateLimiter<Uuid> = RateLimiter::new(CONFIG.ws_command_rate_limit);
+}
+
+/// At most `count` requests per `period`, written as `count/seconds`. A
+/// count of 0 turns the limit off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub count: u32,
+    pub period: Duration,
+}
+
+impl FromStr for RateLimit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((count, seconds)) = s.split_once('/') else {
+            anyhow::bail!("rate limits look like count/seconds, not {:?}", s);
+        };
+        let period = Duration::from_secs(seconds.trim().parse()?);
+        if period.is_zero() {
+            anyhow::bail!("the period of rate limit {:?} can't be 0", s);
+        }
+        Ok(Self {
+            count: count.trim().parse()?,
+            period,
+        })
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+#[error("Too many requests, try again in {} seconds", self.retry_after_secs())]
+pub struct TooManyRequests {
+    pub retry_after: Duration,
+}
+
+impl TooManyRequests {
+    fn retry_after_secs(&self) -> u64 {
+        self.retry_after.as_secs_f64().ceil().max(1.0) as u64
+    }
+}
+
+impl ResponseError for TooManyRequests {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::TOO_MANY_REQUESTS
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, self.retry_after_secs().to_string()))
+            .json(ApiResponse::Error::<()>(self.to_string()))
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// A token bucket per key, so short bursts are fine but the average rate is
+/// limited.
+pub struct RateLimiter<K> {
+    limit: RateLimit,
+    buckets: Mutex<Tracked<K, Bucket>>,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: Mutex::new(Tracked::new()),
+        }
+    }
+
+    /// Uses up one request, or says how long until the next one is allowed.
+    pub fn check(&self, key: K) -> Result<(), TooManyRequests> {
+        self.check_at(key, Instant::now())
+    }
+
+    pub fn check_at(&self, key: K, now: Instant) -> Result<(), TooManyRequests> {
+        if self.limit.count == 0 {
+            return Ok(());
+        }
+        let capacity = self.limit.count as f64;
+        let rate = capacity / self.limit.period.as_secs_f64();
+        let refill = |bucket: &Bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated);
+            (bucket.tokens + elapsed.as_secs_f64() * rate).min(capacity)
+        };
+
+        let mut buckets = self.buckets.lock().unwrap();
+        // full buckets are the same as no bucket
+        buckets.prune(|bucket| refill(bucket) >= capacity, |bucket| bucket.updated);
+        let bucket = buckets.map.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        bucket.tokens = refill(bucket);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(TooManyRequests {
+            retry_after: Duration::from_secs_f64((1.0 - bucket.tokens) / rate),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LockoutPolicy {
+    /// Failures allowed before the first lockout.
+    pub max_failures: u32,
+    /// The first lockout, each failure after it doubles it.
+    pub base: Duration,
+    /// The longest lockout, failures are also forgotten after this long.
+    pub max: Duration,
+}
+
+struct Failures {
+    count: u32,
+    last: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Locks a key out for longer and longer as failures pile up.
+pub struct Lockout<K> {
+    policy: LockoutPolicy,
+    failures: Mutex<Tracked<K, Failures>>,
+}
+
+impl<K: Eq + Hash> Lockout<K> {
+    pub fn new(policy: LockoutPolicy) -> Self {
+        Self {
+            policy,
+            failures: Mutex::new(Tracked::new()),
+        }
+    }
+
+    pub fn check_at(&self, key: &K, now: Instant) -> Result<(), TooManyRequests> {
+        let failures = self.failures.lock().unwrap();
+        match failures.map.get(key).and_then(|f| f.locked_until) {
+            Some(until) if until > now => Err(TooManyRequests {
+                retry_after: until - now,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn fail_at(&self, key: K, now: Instant) {
+        if self.policy.max_failures == 0 {
+            return;
+        }
+        let policy = self.policy;
+        let forgotten = |f: &Failures| now.saturating_duration_since(f.last) > policy.max;
+
+        let mut failures = self.failures.lock().unwrap();
+        failures.prune(forgotten, |f| f.last);
+        let entry = failures.map.entry(key).or_insert(Failures {
+            count: 0,
+            last: now,
+            locked_until: None,
+        });
+        if forgotten(entry) {
+            entry.count = 0;
+        }
+        entry.count += 1;
+        entry.last = now;
+        if entry.count >= policy.max_failures {
+            let doublings = (entry.count - policy.max_failures).min(20);
+            let lockout = policy.base.saturating_mul(1 << doublings).min(policy.max);
+            entry.locked_until = Some(now + lockout);
+        }
+    }
+
+    pub fn reset(&self, key: &K) {
+        self.failures.lock().unwrap().map.remove(key);
+    }
+}
+
+/// Per-key state that's cleared out as it grows.
+struct Tracked<K, V> {
+    map: HashMap<K, V>,
+    prune_at: usize,
+}
+
+impl<K: Eq + Hash, V> Tracked<K, V> {
+    fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+            prune_at: PRUNE_AT,
+        }
+    }
+
+    /// Drops stale entries once there are enough of them to bother, and the
+    /// oldest tenth if that still leaves too many.
+    fn prune(&mut self, stale: impl Fn(&V) -> bool, last_used: impl Fn(&V) -> Instant) {
+        if self.map.len() < self.prune_at {
+            return;
+        }
+        self.map.retain(|_, value| !stale(value));
+        if self.map.len() >= MAX_TRACKED {
+            let mut times = self.map.values().map(&last_used).collect::<Vec<_>>();
+            let (_, cutoff, _) = times.select_nth_unstable(self.map.len() / 10);
+            let cutoff = *cutoff;
+            self.map.retain(|_, value| last_used(value) > cutoff);
+        }
+        // scanning on every new key would be slow, so wait for it to grow again
+        self.prune_at = (self.map.len() * 2).clamp(PRUNE_AT, MAX_TRACKED);
+    }
+}
+
+/// Failed logins, tracked by username to stop guessing one account's
+/// password, and by IP to stop trying one password on every account.
+pub struct LoginAttempts {
+    usernames: Lockout<String>,
+    ips: Lockout<IpAddr>,
+}
+
+impl LoginAttempts {
+    pub fn new(username_policy: LockoutPolicy, ip_policy: LockoutPolicy) -> Self {
+        Self {
+            usernames: Lockout::new(username_policy),
+            ips: Lockout::new(ip_policy),
+        }
+    }
+
+    pub fn check(&self, username: &str, ip: Option<IpAddr>) -> Result<(), TooManyRequests> {
+        self.check_at(username, ip, Instant::now())
+    }
+
+    pub fn check_at(
+        &self,
+        username: &str,
+        ip: Option<IpAddr>,
+        now: Instant,
+    ) -> Result<(), TooManyRequests> {
+        self.usernames.check_at(&username_key(username), now)?;
+        if let Some(ip) = ip {
+            self.ips.check_at(&ip_key(ip), now)?;
+        }
+        Ok(())
+    }
+
+    pub fn failed(&self, username: &str, ip: Option<IpAddr>) {
+        self.failed_at(username, ip, Instant::now())
+    }
+
+    pub fn failed_at(&self, username: &str, ip: Option<IpAddr>, now: Instant) {
+        self.usernames.fail_at(username_key(username), now);
+        if let Some(ip) = ip {
+            self.ips.fail_at(ip_key(ip), now);
+        }
+    }
+
+    /// Clears the username's failures. The IP's are left alone, or logging
+    /// into one account in between would let someone keep guessing others.
+    pub fn succeeded(&self, username: &str) {
+        self.usernames.reset(&username_key(username));
+    }
+}
+
+fn username_key(username: &str) -> String {
+    username.chars().take(MAX_USERNAME_KEY).collect()
+}
+
+/// The address limits are kept for. An IPv6 client usually has a whole /64
+/// to pick addresses from, so that counts as one.
+pub fn ip_key(ip: IpAddr) -> IpAddr {
+    match ip.to_canonical() {
+        IpAddr::V6(ip) => {
+            let prefix = u128::from(ip) & !((1 << 64) - 1);
+            IpAddr::V6(prefix.into())
+        }
+        ip => ip,
+    }
+}
+
+/// The client's address. `X-Forwarded-For` is only believed from trusted
+/// proxies, and only as far back as the hops they added themselves.
+pub fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let peer = req.peer_addr()?.ip();
+    let forwarded = req
+        .headers()
+        .get_all("X-Forwarded-For")
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",");
+    Some(resolve_ip(peer, &forwarded, &CONFIG.trusted_proxies))
+}
+
+pub fn resolve_ip(peer: IpAddr, forwarded: &str, trusted_proxies: &[IpAddr]) -> IpAddr {
+    let mut ip = peer;
+    // the client can put anything at the start, so walk back from the end
+    for hop in forwarded.rsplit(',') {
+        if !trusted_proxies.contains(&ip) {
+            break;
+        }
+        match hop.trim().parse() {
+            Ok(hop) => ip = hop,
+            Err(_) => break,
+        }
+    }
+    ip
+}
@@ -0,0 +1,34 @@
+use actix_web::{
+    post,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+use serde::Serialize;
+
+use crate::{
+    db::{
+        invite::{Invite, InviteBody, InviteError},
+        user::User,
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[derive(Serialize)]
+struct CreatedInvite {
+    #[serde(flatten)]
+    invite: Invite,
+    /// Only shown once, it's stored hashed.
+    code: String,
+}
+
+#[post("")]
+pub async fn create(
+    body: Json<InviteBody>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, InviteError> {
+    let created_by = req.extensions().get::<User>().map(|u| u.id);
+    let (invite, code) = Invite::create(created_by, body.into_inner(), &data.pool).await?;
+    Ok(ApiResponse::Success(CreatedInvite { invite, code }))
+}
@@ -0,0 +1,20 @@
+use actix_web::{
+    delete,
+    web::{Data, Path},
+    Responder,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        invite::{Invite, InviteError},
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[delete("/{invite_id}")]
+pub async fn delete(path: Path<Uuid>, data: Data<Database>) -> Result<impl Responder, InviteError> {
+    Invite::delete(path.into_inner(), &data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
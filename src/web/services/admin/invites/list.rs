@@ -0,0 +1,15 @@
+use actix_web::{get, web::Data, Responder};
+
+use crate::{
+    db::{
+        invite::{Invite, InviteError},
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[get("")]
+pub async fn list(data: Data<Database>) -> Result<impl Responder, InviteError> {
+    let invites = Invite::list(&data.pool).await?;
+    Ok(ApiResponse::Success(invites))
+}
@@ -0,0 +1,16 @@
+mod create;
+mod delete;
+mod list;
+mod uses;
+
+use actix_web::web::{self, ServiceConfig};
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::scope("/invites")
+            .service(list::list)
+            .service(create::create)
+            .service(delete::delete)
+            .service(uses::uses),
+    );
+}
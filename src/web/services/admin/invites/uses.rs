@@ -0,0 +1,21 @@
+use actix_web::{
+    get,
+    web::{Data, Path},
+    Responder,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        invite::{Invite, InviteError},
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+/// Who signed up with the invite, and when.
+#[get("/{invite_id}/uses")]
+pub async fn uses(path: Path<Uuid>, data: Data<Database>) -> Result<impl Responder, InviteError> {
+    let uses = Invite::uses(path.into_inner(), &data.pool).await?;
+    Ok(ApiResponse::Success(uses))
+}
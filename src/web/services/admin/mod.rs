@@ -0,0 +1,27 @@
+mod invites;
+mod quota;
+mod servers;
+mod templates;
+mod users;
+
+use actix_web::{
+    middleware::from_fn,
+    web::{self, ServiceConfig},
+};
+
+use crate::web::middleware::{admin::admin, auth::authenticated};
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::scope("/admin")
+            .configure(invites::configure)
+            .service(quota::get)
+            .service(quota::set)
+            .service(servers::servers)
+            .service(templates::import)
+            .configure(users::configure)
+            // the last middleware runs first, admin needs the user
+            .wrap(from_fn(admin))
+            .wrap(from_fn(authenticated)),
+    );
+}
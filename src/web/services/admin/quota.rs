@@ -0,0 +1,27 @@
+use actix_web::{
+    get, put,
+    web::{Data, Json},
+    Responder,
+};
+
+use crate::{
+    db::{
+        quota::{Quota, QuotaError},
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+/// The quota for users without their own.
+#[get("/quota")]
+pub async fn get(data: Data<Database>) -> Result<impl Responder, QuotaError> {
+    let quota = Quota::default_quota(&data.pool).await?;
+    Ok(ApiResponse::Success(quota))
+}
+
+#[put("/quota")]
+pub async fn set(body: Json<Quota>, data: Data<Database>) -> Result<impl Responder, QuotaError> {
+    let quota = body.into_inner();
+    quota.set_default(&data.pool).await?;
+    Ok(ApiResponse::Success(quota))
+}
@@ -0,0 +1,26 @@
+use actix_web::{get, web::Data, Responder};
+use thiserror::Error;
+
+use crate::{
+    db::{server::Server, Database},
+    response_codes,
+    web::response::ApiResponse,
+};
+
+#[derive(Debug, Error)]
+enum ServerListError {
+    #[error("A database error occurred: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+response_codes!(ServerListError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+});
+
+/// Every server, whoever owns it. Admins manage them through the usual
+/// `/api/server/{id}` routes.
+#[get("/servers")]
+pub async fn servers(data: Data<Database>) -> Result<impl Responder, ServerListError> {
+    let servers = Server::all(&data.pool).await?;
+    Ok(ApiResponse::Success(servers))
+}
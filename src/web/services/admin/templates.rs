@@ -0,0 +1,44 @@
+use actix_web::{post, web::Json, Responder};
+use serde::Deserialize;
+
+use crate::{
+    template::{
+        self,
+        egg::{self, Egg},
+        TemplateError,
+    },
+    web::response::ApiResponse,
+};
+
+#[derive(Deserialize)]
+struct EggImportRequest {
+    /// The egg, as exported from Pterodactyl.
+    egg: serde_json::Value,
+    id: Option<String>,
+    /// The port the server listens on inside its container.
+    #[serde(default = "default_port")]
+    port: u16,
+    /// Whether to replace an existing template with the same id.
+    #[serde(default)]
+    replace: bool,
+}
+
+fn default_port() -> u16 {
+    25565
+}
+
+#[post("/templates/import")]
+pub async fn import(body: Json<EggImportRequest>) -> Result<impl Responder, TemplateError> {
+    let EggImportRequest {
+        egg,
+        id,
+        port,
+        replace,
+    } = body.into_inner();
+    let egg: Egg = serde_json::from_value(egg)
+        .map_err(|e| TemplateError::InvalidTemplate(format!("invalid egg: {}", e)))?;
+
+    let import = egg::convert(egg, id, port)?;
+    template::add(import.template.clone(), replace).await?;
+    Ok(ApiResponse::Success(import))
+}
@@ -0,0 +1,26 @@
+use actix_web::{
+    delete,
+    web::{Data, Path},
+    Responder,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        user::{User, UserManagementError},
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[delete("/{user_id}")]
+pub async fn delete(
+    path: Path<Uuid>,
+    data: Data<Database>,
+) -> Result<impl Responder, UserManagementError> {
+    let user = User::from_id(path.into_inner(), &data.pool)
+        .await
+        .ok_or(UserManagementError::UserNotFound)?;
+    user.delete(&data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
@@ -0,0 +1,15 @@
+use actix_web::{get, web::Data, Responder};
+
+use crate::{
+    db::{
+        user::{User, UserManagementError},
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[get("")]
+pub async fn list(data: Data<Database>) -> Result<impl Responder, UserManagementError> {
+    let users = User::all(&data.pool).await?;
+    Ok(ApiResponse::Success(users))
+}
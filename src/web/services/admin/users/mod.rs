@@ -0,0 +1,22 @@
+mod delete;
+mod list;
+mod password;
+mod quota;
+mod two_factor;
+mod update;
+
+use actix_web::web::{self, ServiceConfig};
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::scope("/users")
+            .service(list::list)
+            .service(update::update)
+            .service(password::reset)
+            .service(delete::delete)
+            .service(quota::get)
+            .service(quota::set)
+            .service(quota::reset)
+            .service(two_factor::reset),
+    );
+}
@@ -0,0 +1,37 @@
+use actix_web::{
+    put,
+    web::{Data, Json, Path},
+    Responder,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        api_key::ApiKey,
+        session::Session,
+        user::{AccountError, User},
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[derive(Deserialize)]
+struct PasswordReset {
+    password: String,
+}
+
+/// Sets a new password for the user, logs them out everywhere and revokes
+/// their API keys.
+#[put("/{user_id}/password")]
+pub async fn reset(
+    path: Path<Uuid>,
+    body: Json<PasswordReset>,
+    data: Data<Database>,
+) -> Result<impl Responder, AccountError> {
+    let user_id = path.into_inner();
+    User::set_password(user_id, body.into_inner().password, &data.pool).await?;
+    Session::revoke_all(user_id, &data.pool).await?;
+    ApiKey::revoke_all(user_id, &data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
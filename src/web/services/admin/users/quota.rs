@@ -0,0 +1,43 @@
+use actix_web::{
+    delete, get, put,
+    web::{Data, Json, Path},
+    Responder,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        quota::{Quota, QuotaError},
+        user::User,
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[get("/{user_id}/quota")]
+pub async fn get(path: Path<Uuid>, data: Data<Database>) -> Result<impl Responder, QuotaError> {
+    let user = User::from_id(path.into_inner(), &data.pool)
+        .await
+        .ok_or(QuotaError::UserNotFound)?;
+    let status = Quota::status(user.id, &data.pool).await?;
+    Ok(ApiResponse::Success(status))
+}
+
+/// Gives the user their own quota instead of the default one.
+#[put("/{user_id}/quota")]
+pub async fn set(
+    path: Path<Uuid>,
+    body: Json<Quota>,
+    data: Data<Database>,
+) -> Result<impl Responder, QuotaError> {
+    let quota = body.into_inner();
+    Quota::set_custom(path.into_inner(), Some(&quota), &data.pool).await?;
+    Ok(ApiResponse::Success(quota))
+}
+
+/// Puts the user back on the default quota.
+#[delete("/{user_id}/quota")]
+pub async fn reset(path: Path<Uuid>, data: Data<Database>) -> Result<impl Responder, QuotaError> {
+    Quota::set_custom(path.into_inner(), None, &data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
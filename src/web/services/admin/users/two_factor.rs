@@ -0,0 +1,28 @@
+use actix_web::{
+    delete,
+    web::{Data, Path},
+    Responder,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        two_factor::{TwoFactor, TwoFactorError},
+        user::User,
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+/// For users who've lost both their authenticator and recovery codes.
+#[delete("/{user_id}/2fa")]
+pub async fn reset(
+    path: Path<Uuid>,
+    data: Data<Database>,
+) -> Result<impl Responder, TwoFactorError> {
+    let user = User::from_id(path.into_inner(), &data.pool)
+        .await
+        .ok_or(TwoFactorError::UserNotFound)?;
+    TwoFactor::disable(user.id, &data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
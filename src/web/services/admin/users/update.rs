@@ -0,0 +1,30 @@
+use actix_web::{
+    patch,
+    web::{Data, Json, Path},
+    Responder,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        user::{User, UserManagementError, UserRole},
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[derive(Deserialize)]
+struct UserUpdate {
+    role: UserRole,
+}
+
+#[patch("/{user_id}")]
+pub async fn update(
+    path: Path<Uuid>,
+    body: Json<UserUpdate>,
+    data: Data<Database>,
+) -> Result<impl Responder, UserManagementError> {
+    let user = User::set_role(path.into_inner(), body.role, &data.pool).await?;
+    Ok(ApiResponse::Success(user))
+}
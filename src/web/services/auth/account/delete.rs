@@ -0,0 +1,40 @@
+use actix_web::{
+    delete,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+use serde::Deserialize;
+
+use crate::{
+    db::{
+        user::{AccountError, User},
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[derive(Deserialize)]
+struct Confirmation {
+    /// Can be left out by users without a password.
+    password: Option<String>,
+}
+
+/// Deletes the account along with all of its servers, their containers,
+/// volumes and backups.
+#[delete("")]
+pub async fn delete(
+    body: Json<Confirmation>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, AccountError> {
+    let user = req
+        .extensions_mut()
+        .remove::<User>()
+        .ok_or(AccountError::UserNotFound)?;
+    let password = body.into_inner().password.unwrap_or_default();
+    if user.has_password(&data.pool).await? && !user.verify_password(password, &data.pool).await {
+        return Err(AccountError::WrongPassword);
+    }
+    user.delete(&data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
@@ -0,0 +1,20 @@
+mod delete;
+mod password;
+mod username;
+
+use actix_web::{
+    middleware::from_fn,
+    web::{self, ServiceConfig},
+};
+
+use crate::web::middleware::auth::authenticated;
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::scope("/account")
+            .service(password::password)
+            .service(username::username)
+            .service(delete::delete)
+            .wrap(from_fn(authenticated)),
+    );
+}
@@ -0,0 +1,61 @@
+use actix_web::{
+    put,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+use serde::Deserialize;
+
+use crate::{
+    db::{
+        api_key::ApiKey,
+        session::Session,
+        user::{AccountError, User},
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PasswordChange {
+    /// Can be left out by users who log in with single sign-on and don't
+    /// have a password yet.
+    current_password: Option<String>,
+    new_password: String,
+}
+
+/// Changes the password, logs out every other session and revokes the
+/// user's API keys.
+#[put("/password")]
+pub async fn password(
+    body: Json<PasswordChange>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, AccountError> {
+    let user = req
+        .extensions_mut()
+        .remove::<User>()
+        .ok_or(AccountError::UserNotFound)?;
+    let session = req
+        .extensions()
+        .get::<Session>()
+        .map(|s| (s.id, s.created_at));
+    let PasswordChange {
+        current_password,
+        new_password,
+    } = body.into_inner();
+    user.change_password(
+        current_password,
+        new_password,
+        session.map(|(_, created_at)| created_at),
+        &data.pool,
+    )
+    .await?;
+
+    match session {
+        Some((id, _)) => Session::revoke_others(user.id, id, &data.pool).await?,
+        None => Session::revoke_all(user.id, &data.pool).await?,
+    }
+    ApiKey::revoke_all(user.id, &data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
@@ -0,0 +1,33 @@
+use actix_web::{
+    put,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+use serde::Deserialize;
+
+use crate::{
+    db::{
+        user::{AccountError, User},
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[derive(Deserialize)]
+struct Rename {
+    username: String,
+}
+
+#[put("/username")]
+pub async fn username(
+    body: Json<Rename>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, AccountError> {
+    let user = req
+        .extensions_mut()
+        .remove::<User>()
+        .ok_or(AccountError::UserNotFound)?;
+    let user = user.rename(&body.username, &data.pool).await?;
+    Ok(ApiResponse::Success(user))
+}
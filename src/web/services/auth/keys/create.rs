@@ -0,0 +1,38 @@
+use actix_web::{
+    post,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+use serde::Serialize;
+
+use crate::{
+    db::{
+        api_key::{ApiKey, ApiKeyBody, ApiKeyError},
+        user::User,
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[derive(Serialize)]
+struct CreatedKey {
+    #[serde(flatten)]
+    key: ApiKey,
+    /// Only shown once, it's stored hashed.
+    token: String,
+}
+
+#[post("")]
+pub async fn create(
+    body: Json<ApiKeyBody>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, ApiKeyError> {
+    let user_id = req
+        .extensions()
+        .get::<User>()
+        .map(|u| u.id)
+        .ok_or(ApiKeyError::KeyNotFound)?;
+    let (key, token) = ApiKey::create(user_id, body.into_inner(), &data.pool).await?;
+    Ok(ApiResponse::Success(CreatedKey { key, token }))
+}
@@ -0,0 +1,30 @@
+use actix_web::{
+    delete,
+    web::{Data, Path},
+    HttpMessage, HttpRequest, Responder,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        api_key::{ApiKey, ApiKeyError},
+        user::User,
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[delete("/{key_id}")]
+pub async fn delete(
+    path: Path<Uuid>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, ApiKeyError> {
+    let user_id = req
+        .extensions()
+        .get::<User>()
+        .map(|u| u.id)
+        .ok_or(ApiKeyError::KeyNotFound)?;
+    ApiKey::delete(path.into_inner(), user_id, &data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
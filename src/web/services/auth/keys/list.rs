@@ -0,0 +1,21 @@
+use actix_web::{get, web::Data, HttpMessage, HttpRequest, Responder};
+
+use crate::{
+    db::{
+        api_key::{ApiKey, ApiKeyError},
+        user::User,
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[get("")]
+pub async fn list(req: HttpRequest, data: Data<Database>) -> Result<impl Responder, ApiKeyError> {
+    let user_id = req
+        .extensions()
+        .get::<User>()
+        .map(|u| u.id)
+        .ok_or(ApiKeyError::KeyNotFound)?;
+    let keys = ApiKey::list(user_id, &data.pool).await?;
+    Ok(ApiResponse::Success(keys))
+}
@@ -0,0 +1,20 @@
+mod create;
+mod delete;
+mod list;
+
+use actix_web::{
+    middleware::from_fn,
+    web::{self, ServiceConfig},
+};
+
+use crate::web::middleware::auth::authenticated;
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::scope("/keys")
+            .service(list::list)
+            .service(create::create)
+            .service(delete::delete)
+            .wrap(from_fn(authenticated)),
+    );
+}
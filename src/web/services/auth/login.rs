@@ -1,12 +1,23 @@
 use crate::{
-    db::{user::User, Database},
+    db::{
+        session::{Session, SessionError, Tokens},
+        two_factor::{TwoFactor, TwoFactorError},
+        user::User,
+        Database,
+    },
     response_codes,
-    web::response::ApiResponse,
+    web::{
+        middleware::rate_limit::rate_limited,
+        rate_limit::{client_ip, LOGIN_ATTEMPTS},
+        response::ApiResponse,
+    },
 };
 use actix_web::{
+    http::header,
+    middleware::from_fn,
     post,
     web::{Data, Json},
-    Responder,
+    HttpRequest, Responder,
 };
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -17,34 +28,125 @@ struct Login {
     password: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct TwoFactorLogin {
+    challenge: String,
+    /// From the authenticator app, or a recovery code.
+    code: String,
+}
+
 #[derive(Debug, Error)]
 enum LoginError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("Failed to create login challenge: {0}")]
+    JWTError(#[from] jsonwebtoken::errors::Error),
     #[error("Invalid credentials")]
     InvalidCredentials,
-    #[error("Failed to create auth token: {0}")]
-    AuthCreationError(#[from] crate::db::user::AuthCreationError),
+    #[error("Failed to create session: {0}")]
+    SessionError(#[from] SessionError),
 }
 
 response_codes!(LoginError {
+    DatabaseError(INTERNAL_SERVER_ERROR),
+    JWTError(INTERNAL_SERVER_ERROR),
     InvalidCredentials(BAD_REQUEST),
-    AuthCreationError(INTERNAL_SERVER_ERROR)
+    SessionError(INTERNAL_SERVER_ERROR)
 });
 
 #[derive(Serialize)]
-struct LoginResponse {
-    user: User,
-    token: String,
+#[serde(untagged)]
+pub(super) enum LoginResponse {
+    LoggedIn {
+        user: User,
+        #[serde(flatten)]
+        tokens: Tokens,
+    },
+    /// The password was right, but a code is needed too, sent to
+    /// `/login/2fa` along with the challenge.
+    #[serde(rename_all = "camelCase")]
+    TwoFactorRequired {
+        two_factor_required: bool,
+        challenge: String,
+    },
 }
 
-#[post("/login")]
-pub async fn login(body: Json<Login>, data: Data<Database>) -> Result<impl Responder, LoginError> {
+#[post("/login", wrap = "from_fn(rate_limited)")]
+pub async fn login(
+    body: Json<Login>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> actix_web::Result<impl Responder> {
     let Login { username, password } = body.into_inner();
-    let user = User::from_username(username, &data.pool)
+    let ip = client_ip(&req);
+    LOGIN_ATTEMPTS.check(&username, ip)?;
+    let user = match User::from_username(&username, &data.pool).await {
+        Some(user) if user.verify_password(password, &data.pool).await => user,
+        _ => {
+            LOGIN_ATTEMPTS.failed(&username, ip);
+            return Err(LoginError::InvalidCredentials.into());
+        }
+    };
+    // the failures are only cleared once the code is right too, or the
+    // password could be used to keep guessing codes
+    if TwoFactor::is_enabled(user.id, &data.pool)
         .await
-        .ok_or(LoginError::InvalidCredentials)?;
-    if !user.verify_password(password, &data.pool).await {
-        return Err(LoginError::InvalidCredentials);
+        .map_err(LoginError::from)?
+    {
+        return Ok(ApiResponse::Success(LoginResponse::TwoFactorRequired {
+            two_factor_required: true,
+            challenge: TwoFactor::challenge(user.id).map_err(LoginError::from)?,
+        }));
     }
-    let jwt = user.create_token().await?;
-    Ok(ApiResponse::Success(LoginResponse { user, token: jwt }))
+    LOGIN_ATTEMPTS.succeeded(&username);
+
+    let (_, tokens) = Session::create(&user, user_agent(&req), ip_string(&req), &data.pool)
+        .await
+        .map_err(LoginError::from)?;
+    Ok(ApiResponse::Success(LoginResponse::LoggedIn {
+        user,
+        tokens,
+    }))
+}
+
+/// Codes count as failed logins, so they can't be guessed either.
+#[post("/login/2fa", wrap = "from_fn(rate_limited)")]
+pub async fn login_two_factor(
+    body: Json<TwoFactorLogin>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> actix_web::Result<impl Responder> {
+    let user_id = TwoFactor::from_challenge(&body.challenge)?;
+    let user = User::from_id(user_id, &data.pool)
+        .await
+        .ok_or(TwoFactorError::InvalidChallenge)?;
+    let ip = client_ip(&req);
+    LOGIN_ATTEMPTS.check(&user.username, ip)?;
+    if let Err(e) = TwoFactor::verify(user.id, &body.code, &data.pool).await {
+        if matches!(e, TwoFactorError::InvalidCode) {
+            LOGIN_ATTEMPTS.failed(&user.username, ip);
+        }
+        return Err(e.into());
+    }
+    LOGIN_ATTEMPTS.succeeded(&user.username);
+
+    let (_, tokens) = Session::create(&user, user_agent(&req), ip_string(&req), &data.pool)
+        .await
+        .map_err(TwoFactorError::from)?;
+    Ok(ApiResponse::Success(LoginResponse::LoggedIn {
+        user,
+        tokens,
+    }))
+}
+
+// shown in the session list, to tell devices apart
+pub(super) fn user_agent(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::USER_AGENT)
+        .and_then(|ua| ua.to_str().ok())
+        .map(|ua| ua.chars().take(256).collect())
+}
+
+pub(super) fn ip_string(req: &HttpRequest) -> Option<String> {
+    client_ip(req).map(|ip| ip.to_string())
 }
@@ -0,0 +1,22 @@
+use actix_web::{middleware::from_fn, post, web::Data, HttpMessage, HttpRequest, Responder};
+
+use crate::{
+    db::{
+        session::{Session, SessionError},
+        Database,
+    },
+    web::{middleware::auth::authenticated, response::ApiResponse},
+};
+
+#[post("/logout", wrap = "from_fn(authenticated)")]
+pub async fn logout(
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, SessionError> {
+    let session = req
+        .extensions_mut()
+        .remove::<Session>()
+        .ok_or(SessionError::SessionNotFound)?;
+    Session::revoke(session.id, session.user_id, &data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
@@ -1,5 +1,12 @@
+mod account;
+mod keys;
 mod login;
+mod logout;
+mod oidc;
+mod refresh;
+mod sessions;
 mod signup;
+mod two_factor;
 
 use actix_web::web::{self, ServiceConfig};
 
@@ -7,6 +14,14 @@ pub fn configure(cfg: &mut ServiceConfig) {
     cfg.service(
         web::scope("/auth")
             .service(signup::signup)
-            .service(login::login),
+            .service(login::login)
+            .service(login::login_two_factor)
+            .service(refresh::refresh)
+            .service(logout::logout)
+            .configure(account::configure)
+            .configure(keys::configure)
+            .configure(sessions::configure)
+            .configure(two_factor::configure)
+            .configure(oidc::configure),
     );
 }
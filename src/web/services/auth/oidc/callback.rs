@@ -0,0 +1,54 @@
+use actix_web::{
+    post,
+    web::{Data, Json},
+    HttpRequest, Responder,
+};
+use serde::Deserialize;
+
+use super::super::login::{ip_string, user_agent, LoginResponse};
+use crate::{
+    config::CONFIG,
+    db::{session::Session, two_factor::TwoFactor, Database},
+    oidc::{self, OidcError},
+    web::response::ApiResponse,
+};
+
+/// The query parameters the provider sent the user back with, and the secret
+/// from `/start`.
+#[derive(Debug, Deserialize)]
+pub(super) struct Callback {
+    pub code: String,
+    pub state: String,
+    pub browser: String,
+}
+
+/// Logs the user in once they're back from the provider. Users with 2FA on
+/// get the same challenge as from `/login`, whatever the provider asked for.
+#[post("/callback")]
+pub async fn callback(
+    body: Json<Callback>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, OidcError> {
+    let config = CONFIG.oidc.as_ref().ok_or(OidcError::Disabled)?;
+    let user = oidc::finish(
+        config,
+        &body.code,
+        &body.state,
+        &body.browser,
+        None,
+        &data.pool,
+    )
+    .await?;
+    if TwoFactor::is_enabled(user.id, &data.pool).await? {
+        return Ok(ApiResponse::Success(LoginResponse::TwoFactorRequired {
+            two_factor_required: true,
+            challenge: TwoFactor::challenge(user.id)?,
+        }));
+    }
+    let (_, tokens) = Session::create(&user, user_agent(&req), ip_string(&req), &data.pool).await?;
+    Ok(ApiResponse::Success(LoginResponse::LoggedIn {
+        user,
+        tokens,
+    }))
+}
@@ -0,0 +1,52 @@
+use actix_web::{
+    middleware::from_fn,
+    post,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+
+use super::{callback::Callback, start::Redirect};
+use crate::{
+    config::CONFIG,
+    db::{user::User, Database},
+    oidc::{self, OidcError},
+    web::{middleware::auth::authenticated, response::ApiResponse},
+};
+
+/// Like `/start`, but the provider account gets linked to the logged in user,
+/// so they can log in with it from then on.
+#[post("/link", wrap = "from_fn(authenticated)")]
+pub async fn link(req: HttpRequest, data: Data<Database>) -> Result<impl Responder, OidcError> {
+    let config = CONFIG.oidc.as_ref().ok_or(OidcError::Disabled)?;
+    let user_id = req
+        .extensions()
+        .get::<User>()
+        .map(|u| u.id)
+        .ok_or(OidcError::NotLinked)?;
+    let started = oidc::start(config, Some(user_id), &data.pool).await?;
+    Ok(ApiResponse::Success(Redirect::from(started)))
+}
+
+#[post("/link/callback", wrap = "from_fn(authenticated)")]
+pub async fn link_callback(
+    body: Json<Callback>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, OidcError> {
+    let config = CONFIG.oidc.as_ref().ok_or(OidcError::Disabled)?;
+    let user_id = req
+        .extensions()
+        .get::<User>()
+        .map(|u| u.id)
+        .ok_or(OidcError::NotLinked)?;
+    let user = oidc::finish(
+        config,
+        &body.code,
+        &body.state,
+        &body.browser,
+        Some(user_id),
+        &data.pool,
+    )
+    .await?;
+    Ok(ApiResponse::Success(user))
+}
@@ -0,0 +1,17 @@
+mod callback;
+mod link;
+mod start;
+mod status;
+
+use actix_web::web::{self, ServiceConfig};
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::scope("/oidc")
+            .service(status::status)
+            .service(start::start)
+            .service(link::link)
+            .service(link::link_callback)
+            .service(callback::callback),
+    );
+}
@@ -0,0 +1,31 @@
+use actix_web::{middleware::from_fn, post, web::Data, Responder};
+use serde::Serialize;
+
+use crate::{
+    config::CONFIG,
+    db::Database,
+    oidc::{self, OidcError, Started},
+    web::{middleware::rate_limit::rate_limited, response::ApiResponse},
+};
+
+#[derive(Serialize)]
+pub(super) struct Redirect {
+    /// Where to send the user to log in with the provider.
+    pub url: String,
+    /// Kept by the browser, in session storage for example, and sent along
+    /// with the callback. Only this browser can finish the login.
+    pub browser: String,
+}
+
+impl From<Started> for Redirect {
+    fn from(Started { url, browser }: Started) -> Self {
+        Self { url, browser }
+    }
+}
+
+#[post("/start", wrap = "from_fn(rate_limited)")]
+pub async fn start(data: Data<Database>) -> Result<impl Responder, OidcError> {
+    let config = CONFIG.oidc.as_ref().ok_or(OidcError::Disabled)?;
+    let started = oidc::start(config, None, &data.pool).await?;
+    Ok(ApiResponse::Success(Redirect::from(started)))
+}
@@ -0,0 +1,17 @@
+use actix_web::{get, Responder};
+use serde::Serialize;
+
+use crate::{config::CONFIG, web::response::ApiResponse};
+
+#[derive(Serialize)]
+struct OidcStatus {
+    /// Whether to show a single sign-on button.
+    enabled: bool,
+}
+
+#[get("")]
+pub async fn status() -> impl Responder {
+    ApiResponse::Success(OidcStatus {
+        enabled: CONFIG.oidc.is_some(),
+    })
+}
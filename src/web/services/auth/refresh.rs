@@ -0,0 +1,31 @@
+use actix_web::{
+    post,
+    web::{Data, Json},
+    Responder,
+};
+use serde::Deserialize;
+
+use crate::{
+    db::{
+        session::{Session, SessionError},
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Refresh {
+    refresh_token: String,
+}
+
+/// Trades a refresh token for new tokens, the old refresh token stops
+/// working.
+#[post("/refresh")]
+pub async fn refresh(
+    body: Json<Refresh>,
+    data: Data<Database>,
+) -> Result<impl Responder, SessionError> {
+    let (_, tokens) = Session::refresh(&body.refresh_token, &data.pool).await?;
+    Ok(ApiResponse::Success(tokens))
+}
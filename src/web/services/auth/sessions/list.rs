@@ -0,0 +1,29 @@
+use actix_web::{get, web::Data, HttpMessage, HttpRequest, Responder};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        session::{Session, SessionError},
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[derive(Serialize)]
+struct SessionList {
+    /// The session making this request.
+    current: Uuid,
+    sessions: Vec<Session>,
+}
+
+#[get("")]
+pub async fn list(req: HttpRequest, data: Data<Database>) -> Result<impl Responder, SessionError> {
+    let (current, user_id) = req
+        .extensions()
+        .get::<Session>()
+        .map(|s| (s.id, s.user_id))
+        .ok_or(SessionError::SessionNotFound)?;
+    let sessions = Session::list(user_id, &data.pool).await?;
+    Ok(ApiResponse::Success(SessionList { current, sessions }))
+}
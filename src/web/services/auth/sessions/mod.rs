@@ -0,0 +1,20 @@
+mod list;
+mod revoke;
+mod revoke_all;
+
+use actix_web::{
+    middleware::from_fn,
+    web::{self, ServiceConfig},
+};
+
+use crate::web::middleware::auth::authenticated;
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::scope("/sessions")
+            .service(list::list)
+            .service(revoke_all::revoke_all)
+            .service(revoke::revoke)
+            .wrap(from_fn(authenticated)),
+    );
+}
@@ -0,0 +1,29 @@
+use actix_web::{
+    delete,
+    web::{Data, Path},
+    HttpMessage, HttpRequest, Responder,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        session::{Session, SessionError},
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[delete("/{session_id}")]
+pub async fn revoke(
+    path: Path<Uuid>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, SessionError> {
+    let user_id = req
+        .extensions()
+        .get::<Session>()
+        .map(|s| s.user_id)
+        .ok_or(SessionError::SessionNotFound)?;
+    Session::revoke(path.into_inner(), user_id, &data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
@@ -0,0 +1,24 @@
+use actix_web::{delete, web::Data, HttpMessage, HttpRequest, Responder};
+
+use crate::{
+    db::{
+        session::{Session, SessionError},
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+/// Logs out everywhere, including this session.
+#[delete("")]
+pub async fn revoke_all(
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, SessionError> {
+    let user_id = req
+        .extensions()
+        .get::<Session>()
+        .map(|s| s.user_id)
+        .ok_or(SessionError::SessionNotFound)?;
+    Session::revoke_all(user_id, &data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
@@ -1,34 +1,52 @@
 use actix_web::{
+    middleware::from_fn,
     post,
     web::{Data, Json},
-    Responder,
+    HttpRequest, Responder,
 };
 use serde::Deserialize;
 
+use super::login::ip_string;
 use crate::{
     config::CONFIG,
     db::{
+        invite::Invite,
         user::{User, UserCreationError},
         Database,
     },
-    web::response::ApiResponse,
+    web::{middleware::rate_limit::rate_limited, response::ApiResponse},
 };
 
 #[derive(Deserialize)]
 struct Signup {
     username: String,
     password: String,
+    /// Lets the user sign up even when signups are disabled.
+    invite: Option<String>,
 }
 
-#[post("/signup")]
+#[post("/signup", wrap = "from_fn(rate_limited)")]
 pub async fn signup(
     body: Json<Signup>,
+    req: HttpRequest,
     data: Data<Database>,
-) -> Result<impl Responder, UserCreationError> {
-    if !CONFIG.signups_enabled {
-        return Err(UserCreationError::SignupsDisabled);
+) -> actix_web::Result<impl Responder> {
+    let Signup {
+        username,
+        password,
+        invite,
+    } = body.into_inner();
+    if let Some(code) = invite {
+        let user = Invite::redeem(&code, username, password, ip_string(&req), &data.pool).await?;
+        return Ok(ApiResponse::Success(user));
+    }
+
+    // without an admin from the config, the first user has to be able to sign
+    // up to become the admin
+    let first = CONFIG.first_admin.is_none() && User::none_exist(&data.pool).await.unwrap_or(false);
+    if !CONFIG.signups_enabled && !first {
+        return Err(UserCreationError::SignupsDisabled.into());
     }
-    let Signup { username, password } = body.into_inner();
     let user = User::create(username, password, &data.pool).await?;
     Ok(ApiResponse::Success(user))
 }
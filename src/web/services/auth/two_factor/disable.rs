@@ -0,0 +1,37 @@
+use actix_web::{
+    post,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+use serde::Deserialize;
+
+use crate::{
+    db::{
+        two_factor::{TwoFactor, TwoFactorError},
+        user::User,
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[derive(Debug, Deserialize)]
+struct Code {
+    /// From the authenticator app, or a recovery code.
+    code: String,
+}
+
+#[post("/disable")]
+pub async fn disable(
+    body: Json<Code>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, TwoFactorError> {
+    let user_id = req
+        .extensions()
+        .get::<User>()
+        .map(|u| u.id)
+        .ok_or(TwoFactorError::UserNotFound)?;
+    TwoFactor::verify(user_id, &body.code, &data.pool).await?;
+    TwoFactor::disable(user_id, &data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
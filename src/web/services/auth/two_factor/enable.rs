@@ -0,0 +1,42 @@
+use actix_web::{
+    post,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{
+        two_factor::{TwoFactor, TwoFactorError},
+        user::User,
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[derive(Debug, Deserialize)]
+struct Code {
+    code: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Enabled {
+    /// Each works once in place of a code, and they can't be seen again.
+    recovery_codes: Vec<String>,
+}
+
+#[post("/enable")]
+pub async fn enable(
+    body: Json<Code>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, TwoFactorError> {
+    let user_id = req
+        .extensions()
+        .get::<User>()
+        .map(|u| u.id)
+        .ok_or(TwoFactorError::UserNotFound)?;
+    let recovery_codes = TwoFactor::enable(user_id, &body.code, &data.pool).await?;
+    Ok(ApiResponse::Success(Enabled { recovery_codes }))
+}
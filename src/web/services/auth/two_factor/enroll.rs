@@ -0,0 +1,29 @@
+use actix_web::{post, web::Data, HttpMessage, HttpRequest, Responder};
+
+use crate::{
+    db::{
+        two_factor::{TwoFactor, TwoFactorError},
+        user::User,
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+/// Makes a new secret to add to an authenticator app. 2FA isn't on until a
+/// code from it is sent to `/enable`.
+#[post("/enroll")]
+pub async fn enroll(
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, TwoFactorError> {
+    let user_id = req
+        .extensions()
+        .get::<User>()
+        .map(|u| u.id)
+        .ok_or(TwoFactorError::UserNotFound)?;
+    let user = User::from_id(user_id, &data.pool)
+        .await
+        .ok_or(TwoFactorError::UserNotFound)?;
+    let enrollment = TwoFactor::enroll(&user, &data.pool).await?;
+    Ok(ApiResponse::Success(enrollment))
+}
@@ -0,0 +1,22 @@
+mod disable;
+mod enable;
+mod enroll;
+mod status;
+
+use actix_web::{
+    middleware::from_fn,
+    web::{self, ServiceConfig},
+};
+
+use crate::web::middleware::auth::authenticated;
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::scope("/2fa")
+            .service(status::status)
+            .service(enroll::enroll)
+            .service(enable::enable)
+            .service(disable::disable)
+            .wrap(from_fn(authenticated)),
+    );
+}
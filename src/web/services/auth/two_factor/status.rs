@@ -0,0 +1,24 @@
+use actix_web::{get, web::Data, HttpMessage, HttpRequest, Responder};
+
+use crate::{
+    db::{
+        two_factor::{TwoFactor, TwoFactorError},
+        user::User,
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+#[get("")]
+pub async fn status(
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, TwoFactorError> {
+    let user_id = req
+        .extensions()
+        .get::<User>()
+        .map(|u| u.id)
+        .ok_or(TwoFactorError::UserNotFound)?;
+    let status = TwoFactor::status(user_id, &data.pool).await?;
+    Ok(ApiResponse::Success(status))
+}
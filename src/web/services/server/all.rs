@@ -26,7 +26,7 @@ pub async fn all(req: HttpRequest, data: Data<Database>) -> Result<impl Responde
         .get::<User>()
         .ok_or_else(|| UserFetchError::UserNotFound)?;
 
-    let servers = Server::get_all(user.id, &data.pool).await?;
+    let servers = Server::accessible(user.id, &data.pool).await?;
 
     Ok(ApiResponse::Success(servers))
 }
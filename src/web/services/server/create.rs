@@ -4,14 +4,18 @@ use actix_web::{
     HttpMessage, HttpRequest, Responder,
 };
 use serde::Deserialize;
+use std::collections::HashMap;
 use thiserror::Error;
 
 use crate::{
+    config::CONFIG,
     db::{
-        server::{self, Server, ServerProvisionError},
+        quota::QuotaExceeded,
+        server::{self, Server, ServerCreationError, ServerProvisionError},
         Database,
     },
     response_codes,
+    template::{self, TemplateError},
     web::response::ApiResponse,
 };
 
@@ -21,6 +25,8 @@ enum ServerCreateError {
     InvalidName,
     #[error("Port must be between 1024 and 65535")]
     InvalidPort,
+    #[error("Port {0} is used by the proxy")]
+    ProxyPort(u16),
     #[error("{0}")]
     CreationError(#[from] server::ServerCreationError),
     #[error("{0}")]
@@ -29,22 +35,38 @@ enum ServerCreateError {
     InvalidAuth,
     #[error("Failed to start server: {0}")]
     StartError(#[from] server::ServerStartError),
+    #[error("{0}")]
+    TemplateError(#[from] TemplateError),
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(QuotaExceeded),
 }
 
 response_codes!(ServerCreateError {
     InvalidName(BAD_REQUEST),
     InvalidPort(BAD_REQUEST),
+    ProxyPort(BAD_REQUEST),
     CreationError(INTERNAL_SERVER_ERROR),
     ProvisionError(INTERNAL_SERVER_ERROR),
     InvalidAuth(UNAUTHORIZED),
     StartError(INTERNAL_SERVER_ERROR),
+    TemplateError(BAD_REQUEST),
+    QuotaExceeded(FORBIDDEN),
 });
 
 #[derive(Deserialize)]
 struct ServerCreateRequest {
     name: String,
-    version: String,
     port: u16,
+    #[serde(default = "default_template")]
+    template: String,
+    #[serde(default)]
+    variables: HashMap<String, String>,
+    /// Shorthand for the minecraft template's `VERSION` variable.
+    version: Option<String>,
+}
+
+fn default_template() -> String {
+    "minecraft".to_string()
 }
 
 #[post("/create")]
@@ -60,6 +82,8 @@ pub async fn create(
     let ServerCreateRequest {
         name,
         port,
+        template,
+        mut variables,
         version,
     } = body.into_inner();
     if name.is_empty() || name.len() > 128 {
@@ -69,8 +93,25 @@ pub async fn create(
     if port < 1024 {
         return Err(ServerCreateError::InvalidPort);
     }
+    if CONFIG
+        .proxy_address
+        .is_some_and(|address| address.port() == port)
+    {
+        return Err(ServerCreateError::ProxyPort(port));
+    }
+
+    let template = template::get(&template).ok_or(TemplateError::TemplateNotFound(template))?;
+    if let Some(version) = version {
+        variables.entry("VERSION".to_string()).or_insert(version);
+    }
+    let variables = template.resolve(variables).await?;
 
-    let server = Server::create(user.id, name, port, version, &data.pool).await?;
-    server.start().await?;
+    let server = Server::create(user.id, name, port, &template, variables, &data.pool)
+        .await
+        .map_err(|e| match e {
+            ServerCreationError::QuotaExceeded(e) => ServerCreateError::QuotaExceeded(e),
+            e => e.into(),
+        })?;
+    server.start(&data.pool).await?;
     Ok(ApiResponse::Success(server))
 }
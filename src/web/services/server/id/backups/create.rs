@@ -0,0 +1,21 @@
+use actix_web::{post, web::Data, HttpMessage, HttpRequest, Responder};
+
+use crate::{
+    db::{
+        backup::{Backup, BackupError, BackupTrigger},
+        member::Permission,
+        server::Server,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[post("", wrap = "Requires::Permission(Permission::Backups)")]
+pub async fn create(req: HttpRequest, data: Data<Database>) -> Result<impl Responder, BackupError> {
+    let server = req
+        .extensions_mut()
+        .remove::<Server>()
+        .ok_or(BackupError::BackupNotFound)?;
+    let backup = Backup::create(&server, BackupTrigger::Manual, &data.pool).await?;
+    Ok(ApiResponse::Success(backup))
+}
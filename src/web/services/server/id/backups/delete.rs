@@ -0,0 +1,35 @@
+use actix_web::{
+    delete,
+    web::{Data, Path},
+    HttpMessage, HttpRequest, Responder,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        backup::{Backup, BackupError},
+        member::Permission,
+        server::Server,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[delete("/{backup_id}", wrap = "Requires::Permission(Permission::Backups)")]
+pub async fn delete(
+    req: HttpRequest,
+    path: Path<(Uuid, Uuid)>,
+    data: Data<Database>,
+) -> Result<impl Responder, BackupError> {
+    let (_, backup_id) = path.into_inner();
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(BackupError::BackupNotFound)?;
+    let backup = Backup::from_id(backup_id, server_id, &data.pool)
+        .await
+        .ok_or(BackupError::BackupNotFound)?;
+    backup.delete(&data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
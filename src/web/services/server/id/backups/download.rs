@@ -0,0 +1,67 @@
+use actix_web::{
+    get,
+    http::header::{ContentDisposition, DispositionParam, DispositionType},
+    web::{Bytes, Data, Path},
+    HttpMessage, HttpRequest, HttpResponse,
+};
+use futures::{stream::BoxStream, StreamExt as _, TryStreamExt as _};
+use tokio::fs::File;
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use crate::{
+    backup::target::BackupTarget,
+    db::{
+        backup::{Backup, BackupError},
+        member::Permission,
+        server::Server,
+        Database,
+    },
+    web::middleware::requires::Requires,
+};
+
+#[get(
+    "/{backup_id}/download",
+    wrap = "Requires::Permission(Permission::Backups)"
+)]
+pub async fn download(
+    req: HttpRequest,
+    path: Path<(Uuid, Uuid)>,
+    data: Data<Database>,
+) -> Result<HttpResponse, BackupError> {
+    let (_, backup_id) = path.into_inner();
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(BackupError::BackupNotFound)?;
+    let backup = Backup::from_id(backup_id, server_id, &data.pool)
+        .await
+        .ok_or(BackupError::BackupNotFound)?;
+    let body: BoxStream<'static, Result<Bytes, BackupError>> = match backup.target()? {
+        BackupTarget::Local => {
+            let file = File::open(backup.path()).await?;
+            ReaderStream::new(file).map_err(BackupError::from).boxed()
+        }
+        BackupTarget::S3(s3) => {
+            let name = Backup::object_name(backup.server_id, backup.id);
+            let response = s3.get(&s3.key(&name)).await?;
+            response
+                .bytes_stream()
+                .map_err(|e| BackupError::StorageError(e.into()))
+                .boxed()
+        }
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/gzip")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "waitress-{}-{}.tar.gz",
+                server_id,
+                backup.created_at.format("%Y%m%d-%H%M%S")
+            ))],
+        })
+        .streaming(body))
+}
@@ -0,0 +1,22 @@
+use actix_web::{get, web::Data, HttpMessage, HttpRequest, Responder};
+
+use crate::{
+    db::{
+        backup::{Backup, BackupError},
+        member::Permission,
+        server::Server,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[get("", wrap = "Requires::Permission(Permission::Backups)")]
+pub async fn list(req: HttpRequest, data: Data<Database>) -> Result<impl Responder, BackupError> {
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(BackupError::BackupNotFound)?;
+    let backups = Backup::get_all(server_id, &data.pool).await?;
+    Ok(ApiResponse::Success(backups))
+}
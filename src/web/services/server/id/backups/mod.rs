@@ -0,0 +1,23 @@
+mod create;
+mod delete;
+mod download;
+mod list;
+mod policy;
+mod restore;
+mod upload;
+
+use actix_web::web::{self, ServiceConfig};
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::scope("/backups")
+            .service(list::list)
+            .service(create::create)
+            .service(policy::get)
+            .service(policy::set)
+            .service(upload::upload)
+            .service(restore::restore)
+            .service(download::download)
+            .service(delete::delete),
+    );
+}
@@ -0,0 +1,42 @@
+use actix_web::{
+    get, put,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+
+use crate::{
+    db::{
+        backup::{BackupError, BackupPolicy},
+        member::Permission,
+        server::Server,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[get("/policy", wrap = "Requires::Permission(Permission::Backups)")]
+pub async fn get(req: HttpRequest, data: Data<Database>) -> Result<impl Responder, BackupError> {
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(BackupError::BackupNotFound)?;
+    let policy = BackupPolicy::get(server_id, &data.pool).await?;
+    Ok(ApiResponse::Success(policy))
+}
+
+#[put("/policy", wrap = "Requires::Permission(Permission::Backups)")]
+pub async fn set(
+    body: Json<BackupPolicy>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, BackupError> {
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(BackupError::BackupNotFound)?;
+    let policy = body.into_inner();
+    policy.set(server_id, &data.pool).await?;
+    Ok(ApiResponse::Success(policy))
+}
@@ -0,0 +1,47 @@
+use actix_web::{
+    post,
+    web::{Data, Json, Path},
+    HttpMessage, HttpRequest, Responder,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        backup::{Backup, BackupError},
+        member::Permission,
+        server::Server,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RestoreRequest {
+    safety_snapshot: bool,
+}
+
+#[post(
+    "/{backup_id}/restore",
+    wrap = "Requires::Permission(Permission::Backups)"
+)]
+pub async fn restore(
+    req: HttpRequest,
+    path: Path<(Uuid, Uuid)>,
+    body: Option<Json<RestoreRequest>>,
+    data: Data<Database>,
+) -> Result<impl Responder, BackupError> {
+    let (_, backup_id) = path.into_inner();
+    let server = req
+        .extensions_mut()
+        .remove::<Server>()
+        .ok_or(BackupError::BackupNotFound)?;
+    let backup = Backup::from_id(backup_id, server.id, &data.pool)
+        .await
+        .ok_or(BackupError::BackupNotFound)?;
+    // snapshot unless explicitly told not to
+    let safety_snapshot = body.map(|b| b.safety_snapshot).unwrap_or(true);
+    backup.restore(&server, safety_snapshot, &data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
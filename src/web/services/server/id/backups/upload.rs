@@ -0,0 +1,30 @@
+use actix_web::{
+    post,
+    web::{Data, Payload},
+    HttpMessage, HttpRequest, Responder,
+};
+
+use crate::{
+    db::{
+        backup::{Backup, BackupError},
+        member::Permission,
+        server::Server,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+/// Imports a `.tar.gz` sent as the raw request body.
+#[post("/upload", wrap = "Requires::Permission(Permission::Backups)")]
+pub async fn upload(
+    req: HttpRequest,
+    body: Payload,
+    data: Data<Database>,
+) -> Result<impl Responder, BackupError> {
+    let server = req
+        .extensions_mut()
+        .remove::<Server>()
+        .ok_or(BackupError::BackupNotFound)?;
+    let backup = Backup::import(&server, body, &data.pool).await?;
+    Ok(ApiResponse::Success(backup))
+}
@@ -0,0 +1,22 @@
+use actix_web::{get, web::Data, HttpMessage, HttpRequest, Responder};
+
+use crate::{
+    db::{
+        crash::{Crash, CrashError},
+        member::Permission,
+        server::Server,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[get("", wrap = "Requires::Permission(Permission::ConsoleRead)")]
+pub async fn list(req: HttpRequest, data: Data<Database>) -> Result<impl Responder, CrashError> {
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(CrashError::CrashNotFound)?;
+    let crashes = Crash::get_all(server_id, &data.pool).await?;
+    Ok(ApiResponse::Success(crashes))
+}
@@ -0,0 +1,15 @@
+mod list;
+mod policy;
+mod report;
+
+use actix_web::web::{self, ServiceConfig};
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::scope("/crashes")
+            .service(list::list)
+            .service(policy::get)
+            .service(policy::set)
+            .service(report::report),
+    );
+}
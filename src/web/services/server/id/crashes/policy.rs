@@ -0,0 +1,42 @@
+use actix_web::{
+    get, put,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+
+use crate::{
+    db::{
+        crash::{CrashError, RestartPolicy},
+        member::Permission,
+        server::Server,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[get("/policy", wrap = "Requires::Permission(Permission::Settings)")]
+pub async fn get(req: HttpRequest, data: Data<Database>) -> Result<impl Responder, CrashError> {
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(CrashError::CrashNotFound)?;
+    let policy = RestartPolicy::get(server_id, &data.pool).await?;
+    Ok(ApiResponse::Success(policy))
+}
+
+#[put("/policy", wrap = "Requires::Permission(Permission::Settings)")]
+pub async fn set(
+    body: Json<RestartPolicy>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, CrashError> {
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(CrashError::CrashNotFound)?;
+    let policy = body.into_inner();
+    policy.set(server_id, &data.pool).await?;
+    Ok(ApiResponse::Success(policy))
+}
@@ -0,0 +1,48 @@
+use actix_web::{
+    get,
+    http::header::{ContentDisposition, DispositionParam, DispositionType},
+    web::{Data, Path},
+    HttpMessage, HttpRequest, HttpResponse,
+};
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        crash::{Crash, CrashError},
+        member::Permission,
+        server::Server,
+        Database,
+    },
+    web::middleware::requires::Requires,
+};
+
+#[get(
+    "/{crash_id}/report",
+    wrap = "Requires::Permission(Permission::ConsoleRead)"
+)]
+pub async fn report(
+    req: HttpRequest,
+    path: Path<(Uuid, Uuid)>,
+    data: Data<Database>,
+) -> Result<HttpResponse, CrashError> {
+    let (_, crash_id) = path.into_inner();
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(CrashError::CrashNotFound)?;
+    let crash = Crash::from_id(crash_id, server_id, &data.pool)
+        .await
+        .ok_or(CrashError::CrashNotFound)?;
+    let name = crash.report_name.ok_or(CrashError::ReportNotFound)?;
+    let report = fs::read(Crash::report_path(server_id, crash.id)).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Inline,
+            parameters: vec![DispositionParam::Filename(name)],
+        })
+        .body(report))
+}
@@ -2,10 +2,10 @@ use actix_web::{delete, web::Data, HttpMessage, HttpRequest, Responder};
 
 use crate::{
     db::{server::ServerDeletionError, Database},
-    web::response::ApiResponse,
+    web::{middleware::requires::Requires, response::ApiResponse},
 };
 
-#[delete("/delete")]
+#[delete("/delete", wrap = "Requires::Owner")]
 pub async fn delete(
     req: HttpRequest,
     data: Data<Database>,
@@ -0,0 +1,37 @@
+use actix_web::{
+    post,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+use serde::Deserialize;
+
+use crate::{
+    db::{
+        member::{MemberError, Permission, ServerMember},
+        server::Server,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[derive(Deserialize)]
+struct InviteBody {
+    username: String,
+    permissions: Vec<Permission>,
+}
+
+#[post("", wrap = "Requires::Owner")]
+pub async fn invite(
+    body: Json<InviteBody>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, MemberError> {
+    let server = req
+        .extensions_mut()
+        .remove::<Server>()
+        .ok_or(MemberError::ServerNotFound)?;
+    let body = body.into_inner();
+    let member =
+        ServerMember::invite(&server, &body.username, body.permissions, &data.pool).await?;
+    Ok(ApiResponse::Success(member))
+}
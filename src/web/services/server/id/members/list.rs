@@ -0,0 +1,21 @@
+use actix_web::{get, web::Data, HttpMessage, HttpRequest, Responder};
+
+use crate::{
+    db::{
+        member::{MemberError, ServerMember},
+        server::Server,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[get("", wrap = "Requires::Owner")]
+pub async fn list(req: HttpRequest, data: Data<Database>) -> Result<impl Responder, MemberError> {
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(MemberError::ServerNotFound)?;
+    let members = ServerMember::list(server_id, &data.pool).await?;
+    Ok(ApiResponse::Success(members))
+}
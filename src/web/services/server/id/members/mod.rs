@@ -0,0 +1,14 @@
+mod invite;
+mod list;
+mod revoke;
+
+use actix_web::web::{self, ServiceConfig};
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::scope("/members")
+            .service(list::list)
+            .service(invite::invite)
+            .service(revoke::revoke),
+    );
+}
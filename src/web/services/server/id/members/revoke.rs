@@ -0,0 +1,31 @@
+use actix_web::{
+    delete,
+    web::{Data, Path},
+    HttpMessage, HttpRequest, Responder,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        member::{MemberError, ServerMember},
+        server::Server,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[delete("/{user_id}", wrap = "Requires::Owner")]
+pub async fn revoke(
+    path: Path<(Uuid, Uuid)>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, MemberError> {
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(MemberError::ServerNotFound)?;
+    let (_, user_id) = path.into_inner();
+    ServerMember::revoke(server_id, user_id, &data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
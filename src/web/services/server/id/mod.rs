@@ -1,5 +1,14 @@
+mod backups;
+mod crashes;
 mod delete;
 mod get;
+mod members;
+mod power;
+mod properties;
+mod routing;
+mod schedules;
+mod sleep;
+mod startup;
 mod ws;
 
 use actix_web::{
@@ -7,7 +16,7 @@ use actix_web::{
     web::{self, ServiceConfig},
 };
 
-use crate::web::middleware::owns_server::owns_server;
+use crate::web::middleware::server_access::server_access;
 
 pub fn configure(cfg: &mut ServiceConfig) {
     cfg.service(
@@ -15,6 +24,19 @@ pub fn configure(cfg: &mut ServiceConfig) {
             .service(ws::ws)
             .service(delete::delete)
             .service(get::get)
-            .wrap(from_fn(owns_server)),
+            .service(power::power)
+            .service(properties::get)
+            .service(properties::patch)
+            .service(routing::get)
+            .service(routing::set)
+            .service(sleep::get)
+            .service(sleep::set)
+            .service(startup::get)
+            .service(startup::set)
+            .configure(backups::configure)
+            .configure(crashes::configure)
+            .configure(members::configure)
+            .configure(schedules::configure)
+            .wrap(from_fn(server_access)),
     );
 }
@@ -0,0 +1,94 @@
+use actix_web::{
+    post,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    db::{
+        member::Permission,
+        quota::QuotaExceeded,
+        server::{Server, ServerStartError, ServerStatus, ServerStopError},
+        Database,
+    },
+    response_codes, sleep,
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[derive(Debug, Error)]
+enum PowerError {
+    #[error("Server not found")]
+    ServerNotFound,
+    #[error("Failed to start server: {0}")]
+    StartError(ServerStartError),
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(QuotaExceeded),
+    #[error("Failed to stop server: {0}")]
+    StopError(#[from] ServerStopError),
+    #[error("A database error occurred: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+response_codes!(PowerError {
+    ServerNotFound(NOT_FOUND),
+    StartError(INTERNAL_SERVER_ERROR),
+    QuotaExceeded(FORBIDDEN),
+    StopError(INTERNAL_SERVER_ERROR),
+    DatabaseError(INTERNAL_SERVER_ERROR),
+});
+
+impl From<ServerStartError> for PowerError {
+    fn from(e: ServerStartError) -> Self {
+        match e {
+            ServerStartError::QuotaExceeded(e) => Self::QuotaExceeded(e),
+            e => Self::StartError(e),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum PowerAction {
+    Start,
+    Stop,
+    Restart,
+}
+
+#[derive(Deserialize)]
+struct PowerBody {
+    action: PowerAction,
+}
+
+#[post("/power", wrap = "Requires::Permission(Permission::Power)")]
+pub async fn power(
+    body: Json<PowerBody>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, PowerError> {
+    let server = req
+        .extensions_mut()
+        .remove::<Server>()
+        .ok_or(PowerError::ServerNotFound)?;
+
+    // same as the schedule actions
+    match body.action {
+        PowerAction::Start => {
+            server.start(&data.pool).await?;
+            Server::set_should_run(server.id, true, &data.pool).await?;
+        }
+        PowerAction::Stop => {
+            server.stop().await?;
+            sleep::release(server.id).await;
+            Server::set_status(server.id, ServerStatus::Stopped, &data.pool).await?;
+            Server::set_should_run(server.id, false, &data.pool).await?;
+        }
+        PowerAction::Restart => {
+            server.stop().await?;
+            server.start(&data.pool).await?;
+        }
+    }
+
+    Ok(ApiResponse::Success(()))
+}
@@ -0,0 +1,93 @@
+use actix_web::{get, patch, web::Json, HttpMessage, HttpRequest, Responder};
+use thiserror::Error;
+
+use crate::{
+    db::{member::Permission, server::Server},
+    minecraft::properties::{PropertiesError, PropertiesPatch, ServerProperties},
+    response_codes, volume,
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[derive(Debug, Error)]
+enum PropertiesEditError {
+    #[error("Server not found")]
+    ServerNotFound,
+    #[error("server.properties does not exist yet, start the server first")]
+    PropertiesNotFound,
+    #[error("This server has no properties file")]
+    NotSupported,
+    #[error("server.properties isn't a regular file")]
+    NotAFile,
+    #[error("Filesystem error: {0}")]
+    FilesystemError(#[from] std::io::Error),
+    #[error("{0}")]
+    ValidationError(#[from] PropertiesError),
+}
+
+response_codes!(PropertiesEditError {
+    ServerNotFound(NOT_FOUND),
+    PropertiesNotFound(NOT_FOUND),
+    NotSupported(NOT_FOUND),
+    NotAFile(BAD_REQUEST),
+    FilesystemError(INTERNAL_SERVER_ERROR),
+    ValidationError(BAD_REQUEST),
+});
+
+// the server can write to its volume, so the file could be a symlink to
+// anywhere on the host
+async fn read_properties(
+    server: &Server,
+    path: &str,
+) -> Result<ServerProperties, PropertiesEditError> {
+    let contents = volume::read_to_string(server.volume_path(), path)
+        .await
+        .map_err(fs_error)?;
+    Ok(ServerProperties::parse(&contents))
+}
+
+fn fs_error(e: std::io::Error) -> PropertiesEditError {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => PropertiesEditError::PropertiesNotFound,
+        std::io::ErrorKind::InvalidInput => PropertiesEditError::NotAFile,
+        _ => e.into(),
+    }
+}
+
+#[get("/properties", wrap = "Requires::Permission(Permission::Files)")]
+pub async fn get(req: HttpRequest) -> Result<impl Responder, PropertiesEditError> {
+    let server = req
+        .extensions_mut()
+        .remove::<Server>()
+        .ok_or(PropertiesEditError::ServerNotFound)?;
+    let path = server
+        .properties_path()
+        .ok_or(PropertiesEditError::NotSupported)?;
+    let properties = read_properties(&server, &path).await?;
+    Ok(ApiResponse::Success(properties.view()))
+}
+
+#[patch("/properties", wrap = "Requires::Permission(Permission::Files)")]
+pub async fn patch(
+    body: Json<PropertiesPatch>,
+    req: HttpRequest,
+) -> Result<impl Responder, PropertiesEditError> {
+    let server = req
+        .extensions_mut()
+        .remove::<Server>()
+        .ok_or(PropertiesEditError::ServerNotFound)?;
+    let path = server
+        .properties_path()
+        .ok_or(PropertiesEditError::NotSupported)?;
+    let mut properties = read_properties(&server, &path).await?;
+    properties.apply(body.into_inner())?;
+
+    volume::write(
+        server.volume_path(),
+        &path,
+        properties.to_string().as_bytes(),
+    )
+    .await
+    .map_err(fs_error)?;
+
+    Ok(ApiResponse::Success(properties.view()))
+}
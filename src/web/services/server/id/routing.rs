@@ -0,0 +1,57 @@
+use actix_web::{
+    get, put,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{
+        member::Permission,
+        server::{RoutingError, Server},
+        user::User,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Routing {
+    hostname: Option<String>,
+    #[serde(default)]
+    proxy_protocol: bool,
+}
+
+#[get("/routing", wrap = "Requires::Permission(Permission::Settings)")]
+pub async fn get(req: HttpRequest) -> Result<impl Responder, RoutingError> {
+    let extensions = req.extensions();
+    let server = extensions
+        .get::<Server>()
+        .ok_or(RoutingError::ServerNotFound)?;
+    Ok(ApiResponse::Success(Routing {
+        hostname: server.hostname.clone(),
+        proxy_protocol: server.proxy_protocol,
+    }))
+}
+
+#[put("/routing", wrap = "Requires::Permission(Permission::Settings)")]
+pub async fn set(
+    body: Json<Routing>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, RoutingError> {
+    let mut server = req
+        .extensions_mut()
+        .remove::<Server>()
+        .ok_or(RoutingError::ServerNotFound)?;
+    let is_admin = req.extensions().get::<User>().is_some_and(User::is_admin);
+    let body = body.into_inner();
+    server
+        .set_routing(body.hostname, body.proxy_protocol, is_admin, &data.pool)
+        .await?;
+    Ok(ApiResponse::Success(Routing {
+        hostname: server.hostname,
+        proxy_protocol: server.proxy_protocol,
+    }))
+}
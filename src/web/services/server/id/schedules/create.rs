@@ -0,0 +1,30 @@
+use actix_web::{
+    post,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+
+use crate::{
+    db::{
+        member::Permission,
+        schedule::{Schedule, ScheduleBody, ScheduleError},
+        server::Server,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[post("", wrap = "Requires::Permission(Permission::Schedules)")]
+pub async fn create(
+    body: Json<ScheduleBody>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, ScheduleError> {
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(ScheduleError::ScheduleNotFound)?;
+    let schedule = Schedule::create(server_id, body.into_inner(), &data.pool).await?;
+    Ok(ApiResponse::Success(schedule))
+}
@@ -0,0 +1,35 @@
+use actix_web::{
+    delete,
+    web::{Data, Path},
+    HttpMessage, HttpRequest, Responder,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        member::Permission,
+        schedule::{Schedule, ScheduleError},
+        server::Server,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[delete("/{schedule_id}", wrap = "Requires::Permission(Permission::Schedules)")]
+pub async fn delete(
+    req: HttpRequest,
+    path: Path<(Uuid, Uuid)>,
+    data: Data<Database>,
+) -> Result<impl Responder, ScheduleError> {
+    let (_, schedule_id) = path.into_inner();
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(ScheduleError::ScheduleNotFound)?;
+    let schedule = Schedule::from_id(schedule_id, server_id, &data.pool)
+        .await
+        .ok_or(ScheduleError::ScheduleNotFound)?;
+    schedule.delete(&data.pool).await?;
+    Ok(ApiResponse::Success(()))
+}
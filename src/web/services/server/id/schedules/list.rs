@@ -0,0 +1,22 @@
+use actix_web::{get, web::Data, HttpMessage, HttpRequest, Responder};
+
+use crate::{
+    db::{
+        member::Permission,
+        schedule::{Schedule, ScheduleError},
+        server::Server,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[get("", wrap = "Requires::Permission(Permission::Schedules)")]
+pub async fn list(req: HttpRequest, data: Data<Database>) -> Result<impl Responder, ScheduleError> {
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(ScheduleError::ScheduleNotFound)?;
+    let schedules = Schedule::get_all(server_id, &data.pool).await?;
+    Ok(ApiResponse::Success(schedules))
+}
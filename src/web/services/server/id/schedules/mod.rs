@@ -0,0 +1,16 @@
+mod create;
+mod delete;
+mod list;
+mod update;
+
+use actix_web::web::{self, ServiceConfig};
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::scope("/schedules")
+            .service(list::list)
+            .service(create::create)
+            .service(update::update)
+            .service(delete::delete),
+    );
+}
@@ -0,0 +1,36 @@
+use actix_web::{
+    put,
+    web::{Data, Json, Path},
+    HttpMessage, HttpRequest, Responder,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        member::Permission,
+        schedule::{Schedule, ScheduleBody, ScheduleError},
+        server::Server,
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[put("/{schedule_id}", wrap = "Requires::Permission(Permission::Schedules)")]
+pub async fn update(
+    body: Json<ScheduleBody>,
+    req: HttpRequest,
+    path: Path<(Uuid, Uuid)>,
+    data: Data<Database>,
+) -> Result<impl Responder, ScheduleError> {
+    let (_, schedule_id) = path.into_inner();
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(ScheduleError::ScheduleNotFound)?;
+    let schedule = Schedule::from_id(schedule_id, server_id, &data.pool)
+        .await
+        .ok_or(ScheduleError::ScheduleNotFound)?;
+    let schedule = schedule.update(body.into_inner(), &data.pool).await?;
+    Ok(ApiResponse::Success(schedule))
+}
@@ -0,0 +1,42 @@
+use actix_web::{
+    get, put,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+
+use crate::{
+    db::{
+        member::Permission,
+        server::Server,
+        sleep::{SleepError, SleepPolicy},
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[get("/sleep", wrap = "Requires::Permission(Permission::Settings)")]
+pub async fn get(req: HttpRequest, data: Data<Database>) -> Result<impl Responder, SleepError> {
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(SleepError::ServerNotFound)?;
+    let policy = SleepPolicy::get(server_id, &data.pool).await?;
+    Ok(ApiResponse::Success(policy))
+}
+
+#[put("/sleep", wrap = "Requires::Permission(Permission::Settings)")]
+pub async fn set(
+    body: Json<SleepPolicy>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, SleepError> {
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(SleepError::ServerNotFound)?;
+    let policy = body.into_inner();
+    policy.set(server_id, &data.pool).await?;
+    Ok(ApiResponse::Success(policy))
+}
@@ -0,0 +1,41 @@
+use actix_web::{
+    get, put,
+    web::{Data, Json},
+    HttpMessage, HttpRequest, Responder,
+};
+
+use crate::{
+    db::{
+        member::Permission,
+        server::Server,
+        startup::{StartupConfig, StartupError},
+        Database,
+    },
+    web::{middleware::requires::Requires, response::ApiResponse},
+};
+
+#[get("/startup", wrap = "Requires::Permission(Permission::Settings)")]
+pub async fn get(req: HttpRequest, data: Data<Database>) -> Result<impl Responder, StartupError> {
+    let server_id = req
+        .extensions()
+        .get::<Server>()
+        .map(|s| s.id)
+        .ok_or(StartupError::ServerNotFound)?;
+    let config = StartupConfig::get(server_id, &data.pool).await?;
+    Ok(ApiResponse::Success(config))
+}
+
+#[put("/startup", wrap = "Requires::Permission(Permission::Settings)")]
+pub async fn set(
+    body: Json<StartupConfig>,
+    req: HttpRequest,
+    data: Data<Database>,
+) -> Result<impl Responder, StartupError> {
+    let server = req
+        .extensions_mut()
+        .remove::<Server>()
+        .ok_or(StartupError::ServerNotFound)?;
+    let config = body.into_inner();
+    config.set(&server, &data.pool).await?;
+    Ok(ApiResponse::Success(config))
+}
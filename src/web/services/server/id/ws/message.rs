@@ -1,11 +1,11 @@
-use crate::db::server::Server;
-use actix_ws::{Message, MessageStream, Session};
-use bollard::Docker;
+use crate::{console::Console, shutdown, web::rate_limit::WS_COMMANDS};
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, Session};
 use bytestring::ByteString;
 use futures::StreamExt as _;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex, Notify};
+use tokio::sync::{Mutex, Notify};
+use uuid::Uuid;
 
 use super::stdin::run_command;
 
@@ -16,6 +16,7 @@ pub enum WebsocketMessage {
     Ping,            //  all directions
     Log(String),     // server -> client
     Command(String), // client -> server
+    Error(String),   // server -> client
 }
 
 impl Into<ByteString> for WebsocketMessage {
@@ -27,16 +28,36 @@ impl Into<ByteString> for WebsocketMessage {
 }
 
 pub struct WebsocketState {
-    pub docker: Arc<Docker>,
-    pub server: Arc<Server>,
     pub session: Arc<Mutex<Session>>,
     pub notify: Arc<Notify>,
     pub msg_stream: MessageStream,
-    pub tx: mpsc::Sender<String>,
+    pub console: Arc<Console>,
+    /// Whether the user has the console write permission.
+    pub can_write: bool,
+    /// Commands are rate limited per user, not per connection.
+    pub user_id: Uuid,
 }
 
 pub async fn handle_messages(mut state: WebsocketState) -> anyhow::Result<()> {
-    while let Some(Ok(msg)) = state.msg_stream.next().await {
+    loop {
+        let msg = tokio::select! {
+            msg = state.msg_stream.next() => msg,
+            _ = shutdown::requested() => {
+                state.notify.notify_waiters();
+                let session = state.session.lock().await.clone();
+                session
+                    .close(Some(CloseReason {
+                        code: CloseCode::Away,
+                        description: Some("waitress is shutting down".to_string()),
+                    }))
+                    .await
+                    .ok();
+                break;
+            }
+        };
+        let Some(Ok(msg)) = msg else {
+            break;
+        };
         match message_loop(&state, msg).await {
             Ok(true) => break,
             Err(e) => log::error!("error in message loop: {}", e),
@@ -67,8 +88,18 @@ async fn message_loop(state: &WebsocketState, msg: Message) -> anyhow::Result<bo
             };
 
             match message {
-                WebsocketMessage::Command(command) => {
-                    run_command(command, state).await?;
+                WebsocketMessage::Command(command) if state.can_write => {
+                    match WS_COMMANDS.check(state.user_id) {
+                        Ok(()) => run_command(command, state).await?,
+                        Err(e) => {
+                            let mut session = state.session.lock().await;
+                            session.text(WebsocketMessage::Error(e.to_string())).await?;
+                        }
+                    }
+                }
+
+                WebsocketMessage::Command(_) => {
+                    log::info!("ignoring a command from a read-only console");
                 }
 
                 WebsocketMessage::Ping => {}
@@ -1,17 +1,23 @@
-mod container_stream;
 mod message;
 mod ping;
 mod stdin;
 mod stdout;
 
-use crate::db::{server::Server, user::User, Database};
+use crate::{
+    console::Console,
+    db::{
+        api_key::ApiKey,
+        member::{Permission, ServerAccess},
+        server::Server,
+        user::User,
+        Database,
+    },
+};
 use actix_web::{
     get, rt,
     web::{self, Data},
     HttpRequest, Responder,
 };
-use bollard::{container::AttachContainerOptions, Docker};
-use container_stream::create_container_stream;
 use message::{handle_messages, WebsocketState};
 use ping::ping;
 use serde::Deserialize;
@@ -33,8 +39,18 @@ async fn ws(
     path: web::Path<Uuid>,
     query: web::Query<Info>,
 ) -> actix_web::Result<impl Responder> {
-    let user = match User::from_token(&query.auth, &data.pool).await {
-        Ok(user) => user,
+    // bots can watch the console with an API key
+    let auth = if ApiKey::is_api_key(&query.auth) {
+        ApiKey::authenticate(&query.auth, &data.pool)
+            .await
+            .map(|(key, user)| (user, Some(key)))
+    } else {
+        User::from_token(&query.auth, &data.pool)
+            .await
+            .map(|user| (user, None))
+    };
+    let (user, key) = match auth {
+        Ok(auth) => auth,
         Err(_) => {
             return Err(actix_web::error::ErrorUnauthorized(
                 "Invalid authentication",
@@ -48,41 +64,28 @@ async fn ws(
         None => return Err(actix_web::error::ErrorNotFound("Server not found")),
     };
 
-    if server.owner != user.id {
+    let access = ServerAccess::get(&server, &user, &data.pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let access = match &key {
+        Some(key) => access.and_then(|access| key.restrict(server.id, access)),
+        None => access,
+    };
+    let Some(access) = access.filter(|a| a.allows(Permission::ConsoleRead)) else {
         return Err(actix_web::error::ErrorUnauthorized(
             "Invalid authentication",
         ));
-    }
+    };
 
     let (response, session, msg_stream) = actix_ws::handle(&req, body)?;
 
-    let container_name = server.container_name();
+    let console = Console::get(server.id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let rx = console.subscribe();
 
-    let server = Arc::new(server);
     let session = Arc::new(Mutex::new(session));
     let notify = Arc::new(Notify::new());
-    let docker = Arc::new(Docker::connect_with_local_defaults().unwrap());
-    let stream = Arc::new(Mutex::new(
-        docker
-            .attach_container(
-                &container_name,
-                Some(AttachContainerOptions::<String> {
-                    stream: Some(true),
-                    stdin: Some(true),
-                    stdout: Some(true),
-                    stderr: Some(true),
-                    ..Default::default()
-                }),
-            )
-            .await
-            .unwrap(),
-    ));
-
-    let notify_clone = Arc::clone(&notify);
-
-    let (tx, rx) = create_container_stream(container_name.clone(), notify_clone)
-        .await
-        .unwrap();
 
     rt::spawn(ping(Arc::clone(&session), Arc::clone(&notify)));
     rt::spawn(receive_stdout(
@@ -92,12 +95,12 @@ async fn ws(
     ));
 
     let state = WebsocketState {
-        docker: Arc::clone(&docker),
-        server: Arc::clone(&server),
         session: Arc::clone(&session),
         notify: Arc::clone(&notify),
-        tx,
+        console,
         msg_stream,
+        can_write: access.allows(Permission::ConsoleWrite),
+        user_id: user.id,
     };
 
     rt::spawn(handle_messages(state));
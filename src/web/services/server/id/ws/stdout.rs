@@ -1,8 +1,5 @@
 use super::message::WebsocketMessage;
-use crate::db::server::Server;
 use actix_ws::Session;
-use bollard::{container::AttachContainerOptions, Docker};
-use futures::StreamExt as _;
 use std::sync::Arc;
 use tokio::{
     pin,
@@ -1,6 +1,8 @@
 mod all;
 mod create;
 mod id;
+mod quota;
+mod templates;
 
 use actix_web::{
     middleware::from_fn,
@@ -14,6 +16,8 @@ pub fn configure(cfg: &mut ServiceConfig) {
         web::scope("/server")
             .service(create::create)
             .service(all::all)
+            .service(quota::quota)
+            .service(templates::templates)
             .configure(id::configure)
             .wrap(from_fn(authenticated)),
     );
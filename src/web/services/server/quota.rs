@@ -0,0 +1,22 @@
+use actix_web::{get, web::Data, HttpMessage, HttpRequest, Responder};
+
+use crate::{
+    db::{
+        quota::{Quota, QuotaError},
+        user::User,
+        Database,
+    },
+    web::response::ApiResponse,
+};
+
+/// The user's quota and how much of it their servers use.
+#[get("/quota")]
+pub async fn quota(req: HttpRequest, data: Data<Database>) -> Result<impl Responder, QuotaError> {
+    let user_id = req
+        .extensions()
+        .get::<User>()
+        .map(|u| u.id)
+        .ok_or(QuotaError::UserNotFound)?;
+    let status = Quota::status(user_id, &data.pool).await?;
+    Ok(ApiResponse::Success(status))
+}
@@ -0,0 +1,8 @@
+use actix_web::{get, Responder};
+
+use crate::{template, web::response::ApiResponse};
+
+#[get("/templates")]
+pub async fn templates() -> impl Responder {
+    ApiResponse::Success(template::all())
+}